@@ -1,7 +1,29 @@
 use clap::{crate_version, Args, Parser, Subcommand, ValueEnum};
-use nimage::nsif::PrettyPrint;
+use nimage::nsif::builder::NsifBuilder;
+use nimage::nsif::chip::{chip, Window};
+use nimage::nsif::codes::{Classification, Code, Compression};
+use nimage::nsif::compression::CompressionOptions;
+use nimage::nsif::conversion::convert;
+use nimage::nsif::error::NsifError;
+use nimage::nsif::field::Field;
+use nimage::nsif::fileheader::Profile;
+use nimage::nsif::imagesegment::ImageSegment;
+use nimage::nsif::integrity::{repair, validate_with_integrity};
+use nimage::nsif::redaction::{parse_points, redact, Region};
+use nimage::nsif::sanitize::{sanitize, Action, SanitizePolicy};
+use nimage::nsif::security::Banner;
+use nimage::nsif::segments::{append_image, extract, move_segment, remove_segment};
+use nimage::nsif::validation::Location;
 use nimage::nsif::{export::export_to_jpeg, NSIF};
-use std::{fs::File, path::PathBuf, process};
+use nimage::nsif::{parse_string_from_bytes, HeaderFields, PrettyPrint};
+use std::error::Error;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+    process,
+};
 use strum::Display;
 
 fn main() {
@@ -15,10 +37,13 @@ fn main() {
             exclude_empty_fields_flag,
         }) => match File::open(input_file) {
             Ok(file) => {
-                let Ok(nsif) = NSIF::parse(&file) else {
+                let Ok((nsif, diagnostics)) = NSIF::parse_with_diagnostics(&file) else {
                     eprintln!("Failed to parse given file");
                     process::exit(1);
                 };
+                for diagnostic in diagnostics {
+                    eprintln!("Warning: {diagnostic}");
+                }
 
                 if print_image_segment_flag {
                     for (i, image_segment) in nsif.image_segments.into_iter().enumerate() {
@@ -46,6 +71,7 @@ fn main() {
             output_file,
             segment_type,
             segment_position,
+            banners,
         }) => match File::open(input_file) {
             Ok(file) => {
                 if segment_position < 1 {
@@ -64,7 +90,24 @@ fn main() {
                     eprintln!("No image segment detected at this position");
                     process::exit(1);
                 };
-                if let Err(e) = export_to_jpeg(image_segment, output_file) {
+                let banner = if banners {
+                    match (
+                        nsif.file_header.security(),
+                        image_segment.sub_header.security(),
+                    ) {
+                        (Ok(file_header), Ok(segment)) => {
+                            Some(Banner::new(&[&file_header, &segment]))
+                        }
+                        (Err(e), _) | (_, Err(e)) => {
+                            eprintln!("Failed to read the classification of the image segment");
+                            eprintln!("{e}");
+                            process::exit(1);
+                        }
+                    }
+                } else {
+                    None
+                };
+                if let Err(e) = export_to_jpeg(image_segment, output_file, banner.as_ref()) {
                     eprintln!("Failed to export image segment to file");
                     eprintln!("{e}");
                     process::exit(1);
@@ -75,6 +118,400 @@ fn main() {
                 process::exit(1);
             }
         },
+        Command::Create(create_args) => {
+            if let Err(e) = create(create_args) {
+                eprintln!("Failed to create NSIF file");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Set(set_args) => {
+            if let Err(e) = set_fields(set_args) {
+                eprintln!("Failed to set fields");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Sanitize(sanitize_args) => {
+            if let Err(e) = sanitize_file(sanitize_args) {
+                eprintln!("Failed to sanitize file");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Compose(compose_args) => {
+            if let Err(e) = compose(compose_args) {
+                eprintln!("Failed to compose file");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Extract(extract_args) => {
+            if let Err(e) = extract_file(extract_args) {
+                eprintln!("Failed to extract segment");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Chip(chip_args) => {
+            if let Err(e) = chip_file(chip_args) {
+                eprintln!("Failed to cut chip");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Convert(convert_args) => {
+            if let Err(e) = convert_file(convert_args) {
+                eprintln!("Failed to convert file");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Recompress(recompress_args) => {
+            if let Err(e) = recompress_file(recompress_args) {
+                eprintln!("Failed to recompress images");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Redact(redact_args) => {
+            if let Err(e) = redact_file(redact_args) {
+                eprintln!("Failed to redact image");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Repair(repair_args) => {
+            if let Err(e) = repair_file(repair_args) {
+                eprintln!("Failed to repair file");
+                eprintln!("{e}");
+                process::exit(1);
+            }
+        }
+        Command::Validate(validate_args) => match validate_file(validate_args) {
+            Ok(true) => {}
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                eprintln!("Failed to validate file");
+                eprintln!("{e}");
+                process::exit(2);
+            }
+        },
+    }
+}
+
+/// Wraps the given images into a new file
+fn create(args: CreateArgs) -> Result<(), Box<dyn Error>> {
+    let profile = if args.nitf {
+        Profile::Nitf21
+    } else {
+        Profile::Nsif10
+    };
+    let mut builder = NsifBuilder::new(profile);
+    for input_file in &args.input_files {
+        let image = image::open(input_file)
+            .map_err(|e| format!("Failed to read {}: {e}", input_file.display()))?;
+        let mut image_segment = ImageSegment::from_image(&image)?;
+        if let Some(classification) = args.classification {
+            image_segment
+                .sub_header
+                .set_field("ISCLAS", classification.code())?;
+        }
+        if let Some(corners) = &args.corners {
+            image_segment
+                .sub_header
+                .set_corners([corners[0], corners[1], corners[2], corners[3]])?;
+        }
+        image_segment.compress(args.compression.into())?;
+        builder = builder.image(image_segment)?;
+    }
+    if let Some(title) = &args.title {
+        builder = builder.header_field("FTITLE", title)?;
+    }
+    if let Some(originator) = &args.originator {
+        builder = builder.header_field("ONAME", originator)?;
+    }
+    if let Some(classification) = args.classification {
+        builder = builder.header_field("FSCLAS", classification.code())?;
+    }
+
+    let mut bytes = Vec::new();
+    builder.build()?.write(&mut bytes)?;
+    fs::write(args.output_file, bytes)?;
+    Ok(())
+}
+
+/// Sets the given fields, writing the result or, for a dry run, printing what would change
+fn set_fields(args: SetArgs) -> Result<(), Box<dyn Error>> {
+    if args
+        .segment
+        .as_ref()
+        .is_some_and(|segment| segment.segment_type != SegmentTypeArg::Image)
+    {
+        return Err("Only the sub headers of image segments can be edited".into());
+    }
+    let original = NSIF::parse(&File::open(&args.input_file)?)?;
+    let mut nsif = original.clone();
+    let segment = args.segment.map(|segment| segment.position);
+    for (mnemonic, value) in &args.fields {
+        nsif.set_header_field(segment, mnemonic, value)?;
+    }
+
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes)?;
+    if args.dry_run {
+        let edited = NSIF::parse(Cursor::new(&bytes))?;
+        print_diff(&original, &edited);
+    } else {
+        fs::write(args.output_file.unwrap_or(args.input_file), bytes)?;
+    }
+    Ok(())
+}
+
+/// Prints the validation report of the given file and returns whether it is free of errors
+fn validate_file(args: ValidateArgs) -> Result<bool, Box<dyn Error>> {
+    let report = validate_with_integrity(&mut File::open(&args.input_file)?)?;
+    match args.format {
+        FormatArg::Json => println!("{}", report.to_json()),
+        FormatArg::Text => {
+            for (location, issues) in report.by_location() {
+                println!("{location}:");
+                for issue in issues {
+                    println!("    {issue}");
+                }
+            }
+            let declared = report
+                .declared_complexity_level
+                .map_or(String::from("invalid"), |level| format!("{level:02}"));
+            let required = report
+                .required_complexity_level
+                .map_or(String::from("unknown"), |level| format!("{level:02}"));
+            println!("Complexity level: {declared} declared, {required} required");
+            println!(
+                "{} error(s), {} warning(s)",
+                report.errors().count(),
+                report.warnings().count()
+            );
+        }
+    }
+    Ok(!report.has_errors())
+}
+
+/// Applies the sanitize policy to the given file and writes the cleaned file and the log
+fn sanitize_file(args: SanitizeArgs) -> Result<(), Box<dyn Error>> {
+    let mut policy = match &args.policy_file {
+        Some(path) => SanitizePolicy::from_str(&fs::read_to_string(path)?)?,
+        None => SanitizePolicy::default(),
+    };
+    for (items, action) in [
+        (&args.keep, Action::Keep),
+        (&args.strip, Action::Strip),
+        (&args.blank, Action::Blank),
+    ] {
+        for item in items {
+            policy.set(item, action);
+        }
+    }
+
+    let mut nsif = NSIF::parse(&File::open(&args.input_file)?)?;
+    let removals = sanitize(&mut nsif, &policy)?;
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes)?;
+    fs::write(&args.output_file, bytes)?;
+
+    let log = removals
+        .iter()
+        .map(|removal| format!("{removal}\n"))
+        .collect::<String>();
+    match args.log_file {
+        Some(path) => fs::write(path, log)?,
+        None if removals.is_empty() => println!("Nothing was removed"),
+        None => print!("{log}"),
+    }
+    Ok(())
+}
+
+/// Combines the image segments of the given files, removing and moving segments on the way
+fn compose(args: ComposeArgs) -> Result<(), Box<dyn Error>> {
+    let (first, others) = args
+        .input_files
+        .split_first()
+        .ok_or("No input file given")?;
+    let mut nsif = NSIF::parse(&File::open(first)?)?;
+    // Removed from the back so that every position refers to the first file
+    let mut removals = args
+        .removals
+        .iter()
+        .map(SegmentArg::location)
+        .collect::<Vec<_>>();
+    removals.sort();
+    for location in removals.into_iter().rev() {
+        remove_segment(&mut nsif, location)?;
+    }
+    for path in others {
+        let other = NSIF::parse(&File::open(path)?)?;
+        for position in 1..=other.image_segments.len() {
+            append_image(&mut nsif, &other, position)?;
+        }
+    }
+    for (segment, position) in &args.moves {
+        move_segment(&mut nsif, segment.location(), *position)?;
+    }
+
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes)?;
+    fs::write(args.output_file, bytes)?;
+    Ok(())
+}
+
+/// Writes a segment, and optionally the segments attached to it, as a file of its own
+fn extract_file(args: ExtractArgs) -> Result<(), Box<dyn Error>> {
+    let nsif = NSIF::parse(&File::open(&args.input_file)?)?;
+    let extracted = extract(&nsif, args.segment.location(), args.attachments)?;
+    let mut bytes = Vec::new();
+    extracted.write(&mut bytes)?;
+    fs::write(args.output_file, bytes)?;
+    Ok(())
+}
+
+/// Writes the window of an image segment as a file of its own
+fn chip_file(args: ChipArgs) -> Result<(), Box<dyn Error>> {
+    let nsif = NSIF::parse(&File::open(&args.input_file)?)?;
+    let chipped = chip(&nsif, args.segment, &args.window)?;
+    let mut bytes = Vec::new();
+    chipped.write(&mut bytes)?;
+    fs::write(args.output_file, bytes)?;
+    Ok(())
+}
+
+/// Writes the file for another profile
+fn convert_file(args: ConvertArgs) -> Result<(), Box<dyn Error>> {
+    let mut nsif = NSIF::parse(&File::open(&args.input_file)?)?;
+    convert(
+        &mut nsif,
+        args.profile.into(),
+        args.classification_system.as_deref(),
+    )?;
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes)?;
+    fs::write(args.output_file, bytes)?;
+    Ok(())
+}
+
+/// Decodes the image segments and writes them encoded with the given compression
+fn recompress_file(args: RecompressArgs) -> Result<(), Box<dyn Error>> {
+    if args.rate.is_some() && args.compression != CompressionArg::C8 {
+        return Err("--rate only applies to C8".into());
+    }
+    if args.quality.is_some() && args.compression != CompressionArg::C3 {
+        return Err("--quality only applies to C3".into());
+    }
+    let mut nsif = NSIF::parse(&File::open(&args.input_file)?)?;
+    let options = CompressionOptions {
+        quality: args
+            .quality
+            .unwrap_or(CompressionOptions::default().quality),
+        rate: args.rate,
+        block_size: args.block_size,
+    };
+    if let Some(&position) = args
+        .segments
+        .iter()
+        .find(|&&position| position == 0 || position > nsif.image_segments.len())
+    {
+        return Err(Box::new(NsifError::MissingSegment(Location::ImageSegment(
+            position,
+        ))));
+    }
+    for (i, image_segment) in nsif.image_segments.iter_mut().enumerate() {
+        if args.segments.is_empty() || args.segments.contains(&(i + 1)) {
+            image_segment
+                .recompress(args.compression.into(), &options)
+                .map_err(|e| format!("Image segment {}: {e}", i + 1))?;
+        }
+    }
+    nsif.update_lengths_and_complexity_level()?;
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes)?;
+    fs::write(args.output_file, bytes)?;
+    Ok(())
+}
+
+/// Redacts the given regions of an image segment and writes the re-encoded file
+fn redact_file(args: RedactArgs) -> Result<(), Box<dyn Error>> {
+    let mut nsif = NSIF::parse(&File::open(&args.input_file)?)?;
+    let regions = args
+        .regions
+        .into_iter()
+        .chain(args.geo_regions)
+        .collect::<Vec<_>>();
+    let redacted = redact(&mut nsif, args.segment, &regions)?;
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes)?;
+    fs::write(args.output_file.unwrap_or(args.input_file), bytes)?;
+    println!(
+        "Redacted {redacted} pixel(s) of image segment {}",
+        args.segment
+    );
+    Ok(())
+}
+
+/// Rewrites the length fields of the given file to match its content and prints what changed
+fn repair_file(args: RepairArgs) -> Result<(), Box<dyn Error>> {
+    let repair = repair(&mut File::open(&args.input_file)?, args.keep_trailing)?;
+    if repair.changes.is_empty()
+        && repair.removed_segments.is_empty()
+        && repair.removed_trailing_length == 0
+    {
+        println!("The length fields match the content, nothing to repair");
+        return Ok(());
+    }
+
+    let mut section = None;
+    for change in &repair.changes {
+        if section != Some(change.location) {
+            println!("{}:", change.location);
+            section = Some(change.location);
+        }
+        let declared = change
+            .declared
+            .map_or(String::from("invalid"), |length| length.to_string());
+        println!("    {}: {declared} -> {}", change.mnemonic, change.repaired);
+    }
+    for location in &repair.removed_segments {
+        println!("Removed {location}, its sub header is cut off");
+    }
+    if repair.removed_trailing_length > 0 {
+        println!("Removed {} trailing bytes", repair.removed_trailing_length);
+    }
+
+    if !args.dry_run {
+        let mut bytes = Vec::new();
+        repair.nsif.write(&mut bytes)?;
+        fs::write(args.output_file.unwrap_or(args.input_file), bytes)?;
+    }
+    Ok(())
+}
+
+/// Prints every field whose value differs between the two files, including recomputed lengths
+fn print_diff(before: &NSIF, after: &NSIF) {
+    let format_value = |field: &Field| {
+        field
+            .value_bytes()
+            .iter()
+            .map(|bytes| format!("{:?}", parse_string_from_bytes(bytes).trim_end()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut section = None;
+    for (header, before, after) in before.changed_fields(after) {
+        if section.as_ref() != Some(&header) {
+            println!("{header}:");
+            section = Some(header);
+        }
+        println!("    {} ({}):", before.name(), before.mnemonic());
+        println!("        - {}", format_value(before));
+        println!("        + {}", format_value(after));
     }
 }
 
@@ -96,6 +533,28 @@ pub enum Command {
     Info(InfoArgs),
     /// Export segments of a given NSIF file as separate files
     Export(ExportArgs),
+    /// Create an NSIF file from one or more PNG, JPEG or TIFF images
+    Create(CreateArgs),
+    /// Set fields of the file header or of an image sub header
+    Set(SetArgs),
+    /// Remove originator details, user-defined data, TREs and comments before release
+    Sanitize(SanitizeArgs),
+    /// Combine the image segments of several files, removing or reordering segments
+    Compose(ComposeArgs),
+    /// Write a single segment into a new file
+    Extract(ExtractArgs),
+    /// Cut a window of an image segment into a new file
+    Chip(ChipArgs),
+    /// Convert a file between NSIF 1.0 and NITF 2.1
+    Convert(ConvertArgs),
+    /// Decode the image segments and encode them again as NC, C3 or C8
+    Recompress(RecompressArgs),
+    /// Overwrite regions of an image segment and re-encode it so the content is removed
+    Redact(RedactArgs),
+    /// Rewrite the length fields of a truncated or mis-lengthed NSIF file
+    Repair(RepairArgs),
+    /// Check a given NSIF file for conformance, exiting with 1 on errors and 2 if it cannot be read
+    Validate(ValidateArgs),
 }
 
 #[derive(Debug, Args)]
@@ -133,6 +592,345 @@ pub struct ExportArgs {
     /// The position of the segment to be exported
     #[arg(short = 'p', long, default_value = "1")]
     pub segment_position: usize,
+    /// Add classification banners above and below the image
+    #[arg(long)]
+    pub banners: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CreateArgs {
+    /// The images to be stored, one image segment each
+    #[arg(required = true)]
+    pub input_files: Vec<PathBuf>,
+    /// The path of the file to be created
+    #[arg(short = 'o', long = "output")]
+    pub output_file: PathBuf,
+    /// The file title (FTITLE)
+    #[arg(long)]
+    pub title: Option<String>,
+    /// The originator's name (ONAME)
+    #[arg(long)]
+    pub originator: Option<String>,
+    /// The security classification of the file and all images (U, R, C, S or T)
+    #[arg(long)]
+    pub classification: Option<Classification>,
+    /// The corners of every image as LAT,LON in decimal degrees, starting at the
+    /// upper left and going clockwise (IGEOLO)
+    #[arg(long = "corners", num_args = 4, value_parser = parse_corner, allow_hyphen_values = true)]
+    pub corners: Option<Vec<(f64, f64)>>,
+    /// The compression of the image segments
+    #[arg(short = 'c', long, default_value_t)]
+    pub compression: CompressionArg,
+    /// Write an NITF 2.1 instead of an NSIF 1.0 file
+    #[arg(long)]
+    pub nitf: bool,
+}
+
+fn parse_corner(s: &str) -> Result<(f64, f64), String> {
+    s.split_once(',')
+        .and_then(|(latitude, longitude)| {
+            Some((
+                latitude.trim().parse().ok()?,
+                longitude.trim().parse().ok()?,
+            ))
+        })
+        .ok_or_else(|| format!("Expected LAT,LON but got '{s}'"))
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Display, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum CompressionArg {
+    /// Not compressed
+    #[default]
+    Nc,
+    /// JPEG
+    C3,
+    /// JPEG 2000
+    C8,
+}
+
+impl From<CompressionArg> for Compression {
+    fn from(compression: CompressionArg) -> Self {
+        match compression {
+            CompressionArg::Nc => Compression::NotCompressed,
+            CompressionArg::C3 => Compression::Jpeg,
+            CompressionArg::C8 => Compression::Jpeg2000,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct SetArgs {
+    /// The path to the nsif file to be edited
+    pub input_file: PathBuf,
+    /// A field to set, given as MNEMONIC=VALUE. Fields of the sub header of the segment given by
+    /// --segment are set there, all others in the file header. Without --segment, file header
+    /// fields are set in the file header and all others in the first image sub header.
+    #[arg(short = 'f', long = "field", value_parser = parse_assignment, required = true)]
+    pub fields: Vec<(String, String)>,
+    /// The segment whose sub header is edited, given as TYPE:POSITION
+    #[arg(short = 's', long)]
+    pub segment: Option<SegmentArg>,
+    /// The path of the edited file, defaults to overwriting the input file
+    #[arg(short = 'o', long = "output")]
+    pub output_file: Option<PathBuf>,
+    /// Print the changed fields instead of writing the file
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+fn parse_assignment(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(mnemonic, value)| (mnemonic.to_uppercase(), value.to_owned()))
+        .ok_or_else(|| format!("Expected MNEMONIC=VALUE but got '{s}'"))
+}
+
+/// A segment given as TYPE:POSITION, e.g. image:2
+#[derive(Debug, Clone)]
+pub struct SegmentArg {
+    pub segment_type: SegmentTypeArg,
+    pub position: usize,
+}
+
+impl SegmentArg {
+    pub fn location(&self) -> Location {
+        match self.segment_type {
+            SegmentTypeArg::Image => Location::ImageSegment(self.position),
+            SegmentTypeArg::Graphic => Location::GraphicSegment(self.position),
+            SegmentTypeArg::Text => Location::TextSegment(self.position),
+        }
+    }
+}
+
+impl FromStr for SegmentArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (segment_type, position) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected TYPE:POSITION but got '{s}'"))?;
+        let segment_type = SegmentTypeArg::from_str(segment_type, true)?;
+        let position = position
+            .parse()
+            .ok()
+            .filter(|&position| position >= 1)
+            .ok_or_else(|| format!("Segment position must be at least 1 but got '{position}'"))?;
+        Ok(SegmentArg {
+            segment_type,
+            position,
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct SanitizeArgs {
+    /// The path to the nsif file to be sanitized
+    pub input_file: PathBuf,
+    /// The path of the sanitized file
+    pub output_file: PathBuf,
+    /// A file with one ITEM=ACTION line per field mnemonic or TRE:TAG, replacing the default
+    /// policy of blanking ONAME and OPHONE and stripping UDHD, UDID and ICOM
+    #[arg(long = "policy")]
+    pub policy_file: Option<PathBuf>,
+    /// Keep a field or TRE:TAG
+    #[arg(long, value_name = "ITEM")]
+    pub keep: Vec<String>,
+    /// Strip a field or TRE:TAG
+    #[arg(long, value_name = "ITEM")]
+    pub strip: Vec<String>,
+    /// Blank a field or TRE:TAG
+    #[arg(long, value_name = "ITEM")]
+    pub blank: Vec<String>,
+    /// Write the log of removed items to a file instead of printing it
+    #[arg(long = "log")]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ComposeArgs {
+    /// The files to combine. The first provides the file header and all of its segments, the
+    /// image segments of the others are appended.
+    #[arg(required = true)]
+    pub input_files: Vec<PathBuf>,
+    /// The path of the file to be created
+    #[arg(short = 'o', long = "output")]
+    pub output_file: PathBuf,
+    /// Remove a segment of the first file before appending, given as TYPE:POSITION
+    #[arg(long = "remove", value_name = "SEGMENT")]
+    pub removals: Vec<SegmentArg>,
+    /// Move a segment of the combined file to another position, given as TYPE:FROM=TO
+    #[arg(long = "move", value_name = "SEGMENT=POSITION", value_parser = parse_move)]
+    pub moves: Vec<(SegmentArg, usize)>,
+}
+
+fn parse_move(s: &str) -> Result<(SegmentArg, usize), String> {
+    let (segment, position) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Expected TYPE:FROM=TO but got '{s}'"))?;
+    let position = position
+        .parse()
+        .ok()
+        .filter(|&position| position >= 1)
+        .ok_or_else(|| format!("Segment position must be at least 1 but got '{position}'"))?;
+    Ok((SegmentArg::from_str(segment)?, position))
+}
+
+#[derive(Debug, Args)]
+pub struct ExtractArgs {
+    /// The path to the nsif file holding the segment
+    pub input_file: PathBuf,
+    /// The path of the file to be created
+    pub output_file: PathBuf,
+    /// The segment to extract, given as TYPE:POSITION
+    #[arg(short = 's', long, default_value = "image:1")]
+    pub segment: SegmentArg,
+    /// Include the graphic and text segments attached to the segment (IALVL, SALVL, TXTALVL)
+    #[arg(short = 'a', long)]
+    pub attachments: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ChipArgs {
+    /// The path to the nsif file holding the full image
+    pub input_file: PathBuf,
+    /// The path of the chip to be created
+    #[arg(short = 'o', long = "output")]
+    pub output_file: PathBuf,
+    /// The position of the image segment
+    #[arg(short = 's', long, default_value = "1")]
+    pub segment: usize,
+    /// The window to cut as ROW,COL,HEIGHT,WIDTH in pixels
+    #[arg(short = 'w', long)]
+    pub window: Window,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// The path to the nsif file to be converted
+    pub input_file: PathBuf,
+    /// The path of the file to be written
+    #[arg(short = 'o', long = "output")]
+    pub output_file: PathBuf,
+    /// The profile to convert to
+    #[arg(long = "to", ignore_case = true)]
+    pub profile: ProfileArg,
+    /// The classification system (xxCLSY) of the recipients, e.g. NS for NATO or a country
+    /// code, replacing that of every classified header
+    #[arg(long)]
+    pub classification_system: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum ProfileArg {
+    /// NSIF 1.0
+    Nsif,
+    /// NITF 2.1
+    Nitf,
+}
+
+impl From<ProfileArg> for Profile {
+    fn from(profile: ProfileArg) -> Self {
+        match profile {
+            ProfileArg::Nsif => Profile::Nsif10,
+            ProfileArg::Nitf => Profile::Nitf21,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RecompressArgs {
+    /// The path to the nsif file to be recompressed
+    pub input_file: PathBuf,
+    /// The path of the file to be written
+    #[arg(short = 'o', long = "output")]
+    pub output_file: PathBuf,
+    /// The compression to encode the images with
+    #[arg(long = "to", ignore_case = true)]
+    pub compression: CompressionArg,
+    /// The bit rate of C8 in bits per pixel per band, numerically lossless if not given
+    #[arg(long)]
+    pub rate: Option<f32>,
+    /// The JPEG quality of C3 from 1 to 100, 90 if not given
+    #[arg(long)]
+    pub quality: Option<u8>,
+    /// The edge length of the blocks (C3) or tiles (C8)
+    #[arg(long, default_value_t = CompressionOptions::default().block_size)]
+    pub block_size: u32,
+    /// The positions of the image segments to recompress, all if not given
+    #[arg(short = 's', long = "segment")]
+    pub segments: Vec<usize>,
+}
+
+#[derive(Debug, Args)]
+#[command(group = clap::ArgGroup::new("region").required(true).multiple(true))]
+pub struct RedactArgs {
+    /// The path to the nsif file to be redacted
+    pub input_file: PathBuf,
+    /// The path of the redacted file, defaults to overwriting the input file
+    #[arg(short = 'o', long = "output")]
+    pub output_file: Option<PathBuf>,
+    /// The position of the image segment
+    #[arg(short = 's', long, default_value = "1")]
+    pub segment: usize,
+    /// A region in pixels, either a rectangle as ROW,COL,HEIGHT,WIDTH or a polygon as
+    /// "ROW,COL ROW,COL ROW,COL ..."
+    #[arg(short = 'r', long = "region", group = "region")]
+    pub regions: Vec<Region>,
+    /// A region in decimal degrees, either a rectangle between two corners as
+    /// LAT,LON,LAT,LON or a polygon as "LAT,LON LAT,LON LAT,LON ..."
+    #[arg(
+        short = 'g',
+        long = "geo-region",
+        group = "region",
+        value_parser = parse_geo_region,
+        allow_hyphen_values = true
+    )]
+    pub geo_regions: Vec<Region>,
+}
+
+fn parse_geo_region(s: &str) -> Result<Region, String> {
+    let numbers = s
+        .split(',')
+        .map(|number| number.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>();
+    if let Ok([lat1, lon1, lat2, lon2]) = numbers.as_deref() {
+        return Ok(Region::geo_rectangle((*lat1, *lon1), (*lat2, *lon2)));
+    }
+    parse_points(s).map(Region::GeoPolygon)
+}
+
+#[derive(Debug, Args)]
+pub struct RepairArgs {
+    /// The path to the nsif file to be repaired
+    pub input_file: PathBuf,
+    /// The path of the repaired file, defaults to overwriting the input file
+    #[arg(short = 'o', long = "output")]
+    pub output_file: Option<PathBuf>,
+    /// Append bytes following the last segment to its data instead of dropping them
+    #[arg(long)]
+    pub keep_trailing: bool,
+    /// Print the changes instead of writing the file
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    /// The path to the nsif file to be validated
+    pub input_file: PathBuf,
+    /// The format of the report
+    #[arg(long, default_value_t = FormatArg::Text)]
+    pub format: FormatArg,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Display, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum FormatArg {
+    /// Human readable, grouped by segment
+    #[default]
+    Text,
+    /// A single JSON object
+    Json,
 }
 
 #[derive(Debug, Clone, Default, ValueEnum, Display, PartialEq, Eq)]
@@ -2,15 +2,20 @@
 
 use eframe::{
     egui::{
-        self, load::SizedTexture, scroll_area::ScrollBarVisibility, Context, IconData, InputState,
-        Response, TextureOptions, Window,
+        self, load::SizedTexture, scroll_area::ScrollBarVisibility, Color32, Context, IconData,
+        InputState, Response, RichText, TextureOptions, TopBottomPanel, Window,
     },
     emath::TSTransform,
 };
 use egui::TextureHandle;
 use egui_notify::Toasts;
 use image::ImageReader;
-use nimage::nsif::{export::export_to_jpeg, field::Value, NSIF};
+use nimage::nsif::codes::Classification;
+use nimage::nsif::error::NsifError;
+use nimage::nsif::field::Field;
+use nimage::nsif::geolocation::image_to_ground;
+use nimage::nsif::security::{Banner, SecurityMetadata};
+use nimage::nsif::{export::export_to_jpeg, field::Value, parse_string_from_bytes, NSIF};
 use std::{env, fs, path::PathBuf, str::FromStr};
 use std::{io::Cursor, sync::Arc};
 
@@ -51,6 +56,7 @@ struct NImageViewer {
     image_response: Option<Response>,
     selected_image_segment_index: Option<usize>,
     image_was_updated: bool,
+    banner: Option<Banner>,
     toasts: Toasts,
 }
 impl Default for NImageViewer {
@@ -67,6 +73,7 @@ impl Default for NImageViewer {
             image_response: None,
             selected_image_segment_index: None,
             image_was_updated: false,
+            banner: None,
             toasts: Toasts::default(),
         }
     }
@@ -76,134 +83,165 @@ impl eframe::App for NImageViewer {
         if let Some(path) = &self.initial_path.take() {
             self.load_nsif(path, ctx);
         }
-        Window::new(
-            self.file_name
-                .clone()
-                .unwrap_or_else(|| "NImage Viewer".to_string()),
-        )
-        .default_width(600.0)
-        .show(ctx, |ui| {
-            ui.heading("File");
-            ui.horizontal(|ui| {
-                if ui.button("Open File").clicked() {
-                    if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("NSIF files", &["nsif", "nitf", "ntf", "nsf"])
-                        .pick_file()
-                    {
-                        self.load_nsif(&path, ctx);
+        let file_name = self
+            .file_name
+            .clone()
+            .unwrap_or_else(|| "NImage Viewer".to_string());
+        let title = match &self.banner {
+            Some(banner) => RichText::new(format!("{file_name} ({})", banner.text))
+                .color(rgb(banner.text_color()))
+                .background_color(rgb(banner.color())),
+            None => RichText::new(file_name),
+        };
+        // The title changes with the banner, so the window needs an id of its own
+        Window::new(title)
+            .id(egui::Id::new("details-window"))
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.heading("File");
+                ui.horizontal(|ui| {
+                    if ui.button("Open File").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("NSIF files", &["nsif", "nitf", "ntf", "nsf"])
+                            .pick_file()
+                        {
+                            self.load_nsif(&path, ctx);
+                        }
                     }
-                }
-                if let Some(current_segment) = self.selected_image_segment_index {
-                    if ui.button("Export Current Segment").clicked() {
-                        if let Some(path) = rfd::FileDialog::new().save_file() {
-                            if self
-                                .nsif
-                                .as_ref()
-                                .and_then(|image| {
-                                    image
-                                        .image_segments
-                                        .get(current_segment)
-                                        .and_then(|segment| export_to_jpeg(segment, path).err())
-                                })
-                                .is_some()
-                            {
-                                self.toasts.error("Export failed.");
-                            } else {
-                                self.toasts.success("Export successful.");
+                    if let Some(current_segment) = self.selected_image_segment_index {
+                        if ui.button("Export Current Segment").clicked() {
+                            if let Some(path) = rfd::FileDialog::new().save_file() {
+                                if self
+                                    .nsif
+                                    .as_ref()
+                                    .and_then(|image| {
+                                        image.image_segments.get(current_segment).and_then(
+                                            |segment| {
+                                                export_to_jpeg(segment, path, self.banner.as_ref())
+                                                    .err()
+                                            },
+                                        )
+                                    })
+                                    .is_some()
+                                {
+                                    self.toasts.error("Export failed.");
+                                } else {
+                                    self.toasts.success("Export successful.");
+                                }
                             }
                         }
                     }
-                }
-                if ui.button("Quit").clicked() {
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                }
-            });
-            ui.allocate_space(egui::Vec2::new(0.0, 2.0));
-            ui.heading("Show");
-            if let Some(image) = &self.nsif {
-                let previous_selection = self.selected_image_segment_index;
-                ui.horizontal(|ui| {
-                    ui.selectable_value(&mut self.selected_image_segment_index, None, "None");
-                    for (i, _) in image.image_segments.iter().enumerate() {
-                        ui.selectable_value(
-                            &mut self.selected_image_segment_index,
-                            Some(i),
-                            format!("Image Segment {}", i + 1),
-                        );
+                    if ui.button("Quit").clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
                 });
-                if self.selected_image_segment_index != previous_selection || self.image_was_updated
-                {
-                    self.update_image_segment_display(ctx);
-                    self.image_was_updated = false;
+                ui.allocate_space(egui::Vec2::new(0.0, 2.0));
+                ui.heading("Show");
+                if let Some(image) = &self.nsif {
+                    let previous_selection = self.selected_image_segment_index;
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut self.selected_image_segment_index, None, "None");
+                        for (i, _) in image.image_segments.iter().enumerate() {
+                            ui.selectable_value(
+                                &mut self.selected_image_segment_index,
+                                Some(i),
+                                format!("Image Segment {}", i + 1),
+                            );
+                        }
+                    });
+                    if self.selected_image_segment_index != previous_selection
+                        || self.image_was_updated
+                    {
+                        self.update_banner();
+                        self.update_image_segment_display(ctx);
+                        self.image_was_updated = false;
+                    }
                 }
-            }
-            ui.allocate_space(egui::Vec2::new(0.0, 2.0));
-            ui.heading("Details");
-            egui::ScrollArea::both()
-                .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
-                .auto_shrink(false)
-                .show(ui, |ui| {
-                    if let Some(image) = &self.nsif {
-                        egui::Grid::new("details-table").show(ui, |ui| {
-                            for (header, fields) in image.fields() {
-                                egui::CollapsingHeader::new(&header).show(ui, |ui| {
-                                    egui::Grid::new(&header).striped(true).show(ui, |ui| {
-                                        for field in fields {
-                                            let value = match &field.value {
-                                                Value::SingleAlphanumeric(v) => v.value.clone(),
-                                                Value::SingleNumeric(v) => v.value.clone(),
-                                                Value::MultipleAlphanumeric(vs) => vs
-                                                    .iter()
-                                                    .map(|v| v.value.clone())
-                                                    .filter(|v| !v.trim().is_empty())
-                                                    .collect::<Vec<String>>()
-                                                    .join(","),
-                                                Value::MultipleNumeric(vs) => vs
-                                                    .iter()
-                                                    .map(|v| v.value.clone())
-                                                    .filter(|v| !v.trim().is_empty())
-                                                    .collect::<Vec<String>>()
-                                                    .join(","),
-                                                Value::NestedAlphaNumeric(vss) => vss
-                                                    .iter()
-                                                    .map(|vs| {
-                                                        vs.iter()
-                                                            .map(|v| v.value.clone())
-                                                            .filter(|v| !v.trim().is_empty())
-                                                            .collect::<Vec<String>>()
-                                                            .join(",")
-                                                    })
-                                                    .filter(|v| !v.trim().is_empty())
-                                                    .collect::<Vec<String>>()
-                                                    .join(";"),
-                                                Value::NestedNumeric(vss) => vss
-                                                    .iter()
-                                                    .map(|vs| {
-                                                        vs.iter()
-                                                            .map(|v| v.value.clone())
-                                                            .filter(|v| !v.trim().is_empty())
-                                                            .collect::<Vec<String>>()
-                                                            .join(",")
-                                                    })
-                                                    .filter(|v| !v.trim().is_empty())
-                                                    .collect::<Vec<String>>()
-                                                    .join(";"),
-                                            };
-                                            ui.label(&field.name);
-                                            ui.label(value);
-                                            ui.end_row();
-                                        }
+                ui.allocate_space(egui::Vec2::new(0.0, 2.0));
+                ui.heading("Details");
+                egui::ScrollArea::both()
+                    .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        if let Some(image) = &self.nsif {
+                            egui::Grid::new("details-table").show(ui, |ui| {
+                                for (header, fields) in image.fields() {
+                                    egui::CollapsingHeader::new(&header).show(ui, |ui| {
+                                        egui::Grid::new(&header).striped(true).show(ui, |ui| {
+                                            for field in fields {
+                                                let value = match &field.value {
+                                                    Value::SingleAlphanumeric(v) => v.value.clone(),
+                                                    Value::SingleNumeric(v) => v.value.clone(),
+                                                    Value::MultipleAlphanumeric(vs) => vs
+                                                        .iter()
+                                                        .map(|v| v.value.clone())
+                                                        .filter(|v| !v.trim().is_empty())
+                                                        .collect::<Vec<String>>()
+                                                        .join(","),
+                                                    Value::MultipleNumeric(vs) => vs
+                                                        .iter()
+                                                        .map(|v| v.value.clone())
+                                                        .filter(|v| !v.trim().is_empty())
+                                                        .collect::<Vec<String>>()
+                                                        .join(","),
+                                                    Value::NestedAlphaNumeric(vss) => vss
+                                                        .iter()
+                                                        .map(|vs| {
+                                                            vs.iter()
+                                                                .map(|v| v.value.clone())
+                                                                .filter(|v| !v.trim().is_empty())
+                                                                .collect::<Vec<String>>()
+                                                                .join(",")
+                                                        })
+                                                        .filter(|v| !v.trim().is_empty())
+                                                        .collect::<Vec<String>>()
+                                                        .join(";"),
+                                                    Value::NestedNumeric(vss) => vss
+                                                        .iter()
+                                                        .map(|vs| {
+                                                            vs.iter()
+                                                                .map(|v| v.value.clone())
+                                                                .filter(|v| !v.trim().is_empty())
+                                                                .collect::<Vec<String>>()
+                                                                .join(",")
+                                                        })
+                                                        .filter(|v| !v.trim().is_empty())
+                                                        .collect::<Vec<String>>()
+                                                        .join(";"),
+                                                };
+                                                let value = match field.description() {
+                                                    Some(description) => format!(
+                                                        "{} ({})",
+                                                        value.trim_end(),
+                                                        description
+                                                    ),
+                                                    None => value,
+                                                };
+                                                ui.label(field.name());
+                                                ui.label(value);
+                                                ui.end_row();
+                                            }
+                                        });
                                     });
-                                });
-                                ui.end_row();
-                            }
-                        });
-                    }
-                });
-        });
+                                    ui.end_row();
+                                }
+                            });
+                        }
+                    });
+            });
+        if let Some(location) = self.pointer_location() {
+            TopBottomPanel::bottom("pointer-location").show(ctx, |ui| {
+                ui.label(location);
+            });
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
-            let (id, rect) = ui.allocate_space(ui.available_size());
+            // The banners are shown above and below the image, which takes the space between
+            let banner = self.banner.clone();
+            let mut size = ui.available_size();
+            if let Some(banner) = &banner {
+                size.y -= show_banner(ui, banner).rect.height() + ui.spacing().item_spacing.y;
+            }
+            let (id, rect) = ui.allocate_space(size);
             let response = ui.interact(rect, id, egui::Sense::click_and_drag());
             let transform =
                 TSTransform::from_translation(ui.min_rect().left_top().to_vec2()) * self.transform;
@@ -253,6 +291,9 @@ impl eframe::App for NImageViewer {
                 .layer_id;
             ui.ctx().set_transform_layer(id, transform);
             ui.ctx().set_sublayer(window_layer, id);
+            if let Some(banner) = &banner {
+                show_banner(ui, banner);
+            }
         });
         self.toasts.show(ctx);
     }
@@ -275,12 +316,69 @@ impl NImageViewer {
             } else {
                 self.nsif = None;
                 self.texture = None;
+                self.banner = None;
                 self.toasts.error("Failed to parse given file");
             }
             self.image_was_updated = true;
         }
     }
 
+    /// Combines the markings of the file header and the selected image segment. A header whose
+    /// security fields are invalid still contributes its classification (xxCLAS), so that the
+    /// banner is never dropped; an invalid classification is shown as top secret.
+    fn update_banner(&mut self) {
+        self.banner = self.nsif.as_ref().map(|image| {
+            let mut metadata = vec![security_or_classification(
+                image.file_header.security(),
+                &image.file_header.fsclas,
+            )];
+            if let Some(segment) = self
+                .selected_image_segment_index
+                .and_then(|i| image.image_segments.get(i))
+            {
+                metadata.push(security_or_classification(
+                    segment.sub_header.security(),
+                    &segment.sub_header.isclas,
+                ));
+            }
+            Banner::new(&metadata.iter().collect::<Vec<_>>())
+        });
+    }
+
+    /// The pixel under the pointer in the displayed image and in the full image it was cut
+    /// from, followed by its latitude and longitude if the image has corner coordinates
+    fn pointer_location(&self) -> Option<String> {
+        let response = self.image_response.as_ref()?;
+        let pointer = response.hover_pos()?;
+        let image_segment = self
+            .nsif
+            .as_ref()?
+            .image_segments
+            .get(self.selected_image_segment_index?)?;
+        let sub_header = &image_segment.sub_header;
+        let (rows, columns) = (sub_header.num_rows().ok()?, sub_header.num_cols().ok()?);
+        let position = (pointer - response.rect.min) / response.rect.size();
+        let point = (
+            position.y as f64 * rows as f64,
+            position.x as f64 * columns as f64,
+        );
+
+        let mut location = format!("Row {}, column {}", point.0 as u32, point.1 as u32);
+        if sub_header.ichipb().ok().flatten().is_some() {
+            if let Ok((row, column)) = image_segment.chip_to_full_image(point) {
+                location.push_str(&format!(
+                    " (full image row {}, column {})",
+                    row as u32, column as u32
+                ));
+            }
+        }
+        if let Ok(to_ground) = image_to_ground(sub_header) {
+            let (latitude, longitude) = to_ground.apply(point);
+            location.push_str(&format!(", {latitude:.6}, {longitude:.6}"));
+        }
+        Some(location)
+    }
+
     fn update_image_segment_display(&mut self, ctx: &Context) {
         if let Some(image) = self.nsif.as_ref() {
             let Some(selected_segment) = self.selected_image_segment_index else {
@@ -303,3 +401,36 @@ impl NImageViewer {
         }
     }
 }
+
+fn security_or_classification(
+    security: Result<SecurityMetadata, NsifError>,
+    classification: &Field,
+) -> SecurityMetadata {
+    security.unwrap_or_else(|_| {
+        SecurityMetadata::classification_only(
+            Classification::from_str(&parse_string_from_bytes(&classification.bytes()))
+                .unwrap_or(Classification::TopSecret),
+        )
+    })
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color32 {
+    Color32::from_rgb(r, g, b)
+}
+
+fn show_banner(ui: &mut egui::Ui, banner: &Banner) -> Response {
+    egui::Frame::default()
+        .fill(rgb(banner.color()))
+        .inner_margin(4.0)
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.vertical_centered(|ui| {
+                ui.label(
+                    RichText::new(&banner.text)
+                        .strong()
+                        .color(rgb(banner.text_color())),
+                );
+            });
+        })
+        .response
+}
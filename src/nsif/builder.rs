@@ -0,0 +1,77 @@
+use super::error::NsifError;
+use super::fileheader::{FileHeader, Profile};
+use super::imagesegment::ImageSegment;
+use super::{HeaderFields, NSIF};
+use image::DynamicImage;
+
+/// Assembles an NSIF file from images, using defaults for every field that is not set
+/// explicitly. The length fields and the complexity level are computed by
+/// [`NsifBuilder::build`].
+pub struct NsifBuilder {
+    file_header: FileHeader,
+    image_segments: Vec<ImageSegment>,
+}
+
+impl NsifBuilder {
+    pub fn new(profile: Profile) -> Self {
+        NsifBuilder {
+            file_header: FileHeader::new(profile),
+            image_segments: Vec::new(),
+        }
+    }
+
+    /// Sets a field of the file header, see [`HeaderFields::set_field`]
+    pub fn header_field(mut self, mnemonic: &str, value: &str) -> Result<Self, NsifError> {
+        self.file_header.set_field(mnemonic, value)?;
+        Ok(self)
+    }
+
+    /// Adds an image segment. Its display level is set so that it is drawn above the images
+    /// added before, and every image but the first is attached to the first one.
+    pub fn image(mut self, mut image_segment: ImageSegment) -> Result<Self, NsifError> {
+        let sub_header = &mut image_segment.sub_header;
+        sub_header
+            .idlvl
+            .set_number(self.image_segments.len() as u64 + 1)?;
+        sub_header
+            .ialvl
+            .set_number(u64::from(!self.image_segments.is_empty()))?;
+        self.image_segments.push(image_segment);
+        Ok(self)
+    }
+
+    /// Adds an uncompressed image from 8-bit pixels, see [`ImageSegment::from_pixels`]
+    pub fn pixels(
+        self,
+        width: u32,
+        height: u32,
+        bands: &[&str],
+        pixels: Vec<u8>,
+    ) -> Result<Self, NsifError> {
+        self.image(ImageSegment::from_pixels(width, height, bands, pixels)?)
+    }
+
+    /// Adds an uncompressed image from the `image` crate, see [`ImageSegment::from_image`]
+    pub fn dynamic_image(self, image: &DynamicImage) -> Result<Self, NsifError> {
+        self.image(ImageSegment::from_image(image)?)
+    }
+
+    pub fn build(self) -> Result<NSIF, NsifError> {
+        let mut nsif = NSIF {
+            file_header: self.file_header,
+            image_segments: self.image_segments,
+            graphic_segments: Vec::new(),
+            text_segments: Vec::new(),
+            data_extension_segments: Vec::new(),
+            reserved_extension_segments: Vec::new(),
+        };
+        nsif.update_lengths_and_complexity_level()?;
+        Ok(nsif)
+    }
+}
+
+impl Default for NsifBuilder {
+    fn default() -> Self {
+        NsifBuilder::new(Profile::default())
+    }
+}
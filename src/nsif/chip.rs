@@ -0,0 +1,299 @@
+//! Chips, i.e. files holding a window of an image segment of a larger scene. The chip records
+//! where it was cut from in an ICHIPB TRE.
+
+use super::codes::CoordinateRepresentation;
+use super::error::NsifError;
+use super::geolocation::{corner_pixels, image_to_ground, Projective};
+use super::parse_string_from_bytes;
+use super::segments::{find_overflow, restore_overflow};
+use super::tre::{parse_tres, set_tres, Tre};
+use super::validation::Location;
+use super::NSIF;
+use std::str::FromStr;
+
+/// A window of an image in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub row: u32,
+    pub column: u32,
+    pub height: u32,
+    pub width: u32,
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    /// Parses ROW,COL,HEIGHT,WIDTH
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split(',')
+            .map(|number| number.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>();
+        match numbers.as_deref() {
+            Ok(&[row, column, height, width]) => Ok(Window {
+                row,
+                column,
+                height,
+                width,
+            }),
+            _ => Err(format!("Expected ROW,COL,HEIGHT,WIDTH but got '{s}'")),
+        }
+    }
+}
+
+/// The chipping TRE, relating four points of a chip to the same points in the full image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ichipb {
+    /// The factor by which the full image was scaled before it was chipped
+    pub scale_factor: f64,
+    /// The (row, column) of the upper left, upper right, lower left and lower right points in
+    /// the chip (OP_ROW_11 to OP_COL_22)
+    pub output_points: [(f64, f64); 4],
+    /// The same points in the full image (FI_ROW_11 to FI_COL_22)
+    pub full_image_points: [(f64, f64); 4],
+    /// The number of rows of the full image (FI_ROW)
+    pub full_image_rows: u32,
+    /// The number of columns of the full image (FI_COL)
+    pub full_image_columns: u32,
+}
+
+impl Ichipb {
+    pub const TAG: &'static str = "ICHIPB";
+
+    /// The TRE of a chip cut without scaling from the window of a full image, relating the
+    /// centres of the corner pixels
+    pub fn new(window: &Window, full_image_rows: u32, full_image_columns: u32) -> Self {
+        let [upper_left, upper_right, lower_right, lower_left] =
+            corner_pixels(window.height, window.width);
+        let output_points = [upper_left, upper_right, lower_left, lower_right];
+        Ichipb {
+            scale_factor: 1.0,
+            output_points,
+            full_image_points: output_points
+                .map(|(row, column)| (row + window.row as f64, column + window.column as f64)),
+            full_image_rows,
+            full_image_columns,
+        }
+    }
+
+    /// Parses the TRE. Non-linear transformations and anamorphic corrections are not
+    /// interpreted.
+    pub fn parse(tre: &Tre) -> Result<Self, NsifError> {
+        let invalid = || NsifError::InvalidFieldValue {
+            mnemonic: "ICHIPB",
+            value: parse_string_from_bytes(&tre.data),
+        };
+        if tre.tag != Self::TAG || tre.data.len() != 224 {
+            return Err(invalid());
+        }
+        let number = |start: usize, length: usize| {
+            parse_string_from_bytes(&tre.data[start..start + length])
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| invalid())
+        };
+        // Sixteen coordinates of 12 bytes each follow XFRM_FLAG to SCANBLK_NUM
+        let point = |i: usize| Ok((number(16 + i * 24, 12)?, number(28 + i * 24, 12)?));
+        Ok(Ichipb {
+            scale_factor: number(2, 10)?,
+            output_points: [point(0)?, point(1)?, point(2)?, point(3)?],
+            full_image_points: [point(4)?, point(5)?, point(6)?, point(7)?],
+            full_image_rows: number(208, 8)? as u32,
+            full_image_columns: number(216, 8)? as u32,
+        })
+    }
+
+    /// The transformation of (row, column) coordinates in the chip to the full image
+    pub fn chip_to_full_image(&self) -> Result<Projective, NsifError> {
+        Projective::from_points(self.output_points, self.full_image_points).ok_or_else(|| {
+            NsifError::InvalidFieldValue {
+                mnemonic: "ICHIPB",
+                value: parse_string_from_bytes(&self.to_tre().data),
+            }
+        })
+    }
+
+    /// Serializes the TRE, without a non-linear transformation or anamorphic correction
+    pub fn to_tre(&self) -> Tre {
+        let mut data = format!("00{:010.5}0000", self.scale_factor);
+        for (row, column) in self.output_points.iter().chain(&self.full_image_points) {
+            data.push_str(&format!("{row:012.3}{column:012.3}"));
+        }
+        data.push_str(&format!(
+            "{:08}{:08}",
+            self.full_image_rows, self.full_image_columns
+        ));
+        Tre::new(Self::TAG, data.into_bytes())
+    }
+}
+
+/// A file holding only the window of an image segment (counted from 1), with the file header
+/// of the original and the data extension segments holding their TRE overflow. The chip keeps
+/// the compression of the segment, its corner coordinates are recomputed, the line and sample
+/// offsets of RPC00A and RPC00B TREs are moved to the chip and an ICHIPB TRE is added. Corners
+/// in UTM cannot be recomputed and are removed.
+pub fn chip(
+    nsif: &NSIF,
+    segment: usize,
+    window: &Window,
+) -> Result<NSIF, Box<dyn std::error::Error>> {
+    let image_segment = segment
+        .checked_sub(1)
+        .and_then(|i| nsif.image_segments.get(i))
+        .ok_or(NsifError::MissingSegment(Location::ImageSegment(segment)))?;
+    let full_sub_header = &image_segment.sub_header;
+    let mut chip = image_segment.crop(window.row, window.column, window.height, window.width)?;
+
+    let sub_header = &mut chip.sub_header;
+    match full_sub_header.coordinate_representation()? {
+        CoordinateRepresentation::None => {}
+        CoordinateRepresentation::DecimalDegrees | CoordinateRepresentation::Geographic => {
+            let to_ground = image_to_ground(full_sub_header)?;
+            sub_header.set_corners(corner_pixels(window.height, window.width).map(
+                |(row, column)| {
+                    to_ground.apply((row + window.row as f64, column + window.column as f64))
+                },
+            ))?;
+        }
+        _ => sub_header.icords.set(" ")?,
+    }
+    sub_header.idlvl.set_number(1)?;
+    sub_header.ialvl.set_number(0)?;
+    sub_header.iloc.set_number(0)?;
+
+    let mut ichipb = Ichipb::new(
+        window,
+        full_sub_header.num_rows()?,
+        full_sub_header.num_cols()?,
+    );
+    // A chip of a chip refers to the original full image
+    if let Some(original) = full_sub_header.ichipb()? {
+        let to_full_image = original.chip_to_full_image()?;
+        ichipb.full_image_points = ichipb
+            .full_image_points
+            .map(|point| to_full_image.apply(point));
+        ichipb.full_image_rows = original.full_image_rows;
+        ichipb.full_image_columns = original.full_image_columns;
+    }
+    for field in [&mut sub_header.udid, &mut sub_header.ixshd] {
+        let tres = move_tres(parse_tres(field)?, window)?;
+        set_tres(field, &tres)?;
+    }
+    let mut tres = parse_tres(&sub_header.ixshd)?;
+    tres.push(ichipb.to_tre());
+    set_tres(&mut sub_header.ixshd, &tres)?;
+
+    let mut overflow = find_overflow(nsif);
+    overflow.images = vec![overflow.images.swap_remove(segment - 1)];
+    for data_extension_segment in overflow.images[0].iter_mut().flatten() {
+        let tres = move_tres(Tre::parse_all(&data_extension_segment.data)?, window)?;
+        data_extension_segment.data = tres.iter().flat_map(Tre::to_bytes).collect();
+    }
+    let mut chipped = NSIF {
+        file_header: nsif.file_header.clone(),
+        image_segments: vec![chip],
+        graphic_segments: Vec::new(),
+        text_segments: Vec::new(),
+        data_extension_segments: Vec::new(),
+        reserved_extension_segments: Vec::new(),
+    };
+    restore_overflow(&mut chipped, overflow)?;
+    chipped.update_lengths_and_complexity_level()?;
+    Ok(chipped)
+}
+
+/// Shifts the RPCs of the TREs of the full image to the window and drops its ICHIPB
+fn move_tres(mut tres: Vec<Tre>, window: &Window) -> Result<Vec<Tre>, NsifError> {
+    for tre in tres
+        .iter_mut()
+        .filter(|tre| tre.tag == "RPC00A" || tre.tag == "RPC00B")
+    {
+        shift_rpc(tre, window.row as f64, window.column as f64)?;
+    }
+    tres.retain(|tre| tre.tag != Ichipb::TAG);
+    Ok(tres)
+}
+
+/// The offsets of the fields of RPC00A and RPC00B that depend on the image coordinates
+const LINE_OFFSET: usize = 15;
+const SAMPLE_OFFSET: usize = 21;
+const LINE_SCALE: usize = 48;
+const SAMPLE_SCALE: usize = 54;
+const LINE_NUMERATOR: usize = 81;
+const LINE_DENOMINATOR: usize = 321;
+const SAMPLE_NUMERATOR: usize = 561;
+const SAMPLE_DENOMINATOR: usize = 801;
+const RPC_LENGTH: usize = 1041;
+
+/// Moves the image coordinates of rational polynomial coefficients by the given number of
+/// rows and columns. The offsets (LINE_OFF, SAMP_OFF) cannot be negative, so the part of a
+/// shift that would make them negative is moved into the numerator coefficients.
+fn shift_rpc(tre: &mut Tre, rows: f64, columns: f64) -> Result<(), NsifError> {
+    if tre.data.len() < RPC_LENGTH {
+        return Err(NsifError::LengthMismatch {
+            field: tre.tag.clone(),
+            declared: RPC_LENGTH as u64,
+            consumed: tre.data.len() as u64,
+        });
+    }
+    for (offset, length, scale, numerator, denominator, shift) in [
+        (
+            LINE_OFFSET,
+            6,
+            LINE_SCALE,
+            LINE_NUMERATOR,
+            LINE_DENOMINATOR,
+            rows,
+        ),
+        (
+            SAMPLE_OFFSET,
+            5,
+            SAMPLE_SCALE,
+            SAMPLE_NUMERATOR,
+            SAMPLE_DENOMINATOR,
+            columns,
+        ),
+    ] {
+        let number = |data: &[u8], start: usize, length: usize| {
+            let value = parse_string_from_bytes(&data[start..start + length]);
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| NsifError::InvalidFieldValue {
+                    mnemonic: "RPC",
+                    value,
+                })
+        };
+        let shifted = number(&tre.data, offset, length)? - shift;
+        if shifted < 0.0 {
+            // numerator / denominator * scale + shifted
+            //     = (numerator + k * denominator) / denominator * scale with k = shifted / scale
+            let k = shifted / number(&tre.data, scale, length)?;
+            for i in 0..20 {
+                let start = numerator + i * 12;
+                let coefficient = number(&tre.data, start, 12)?
+                    + k * number(&tre.data, denominator + i * 12, 12)?;
+                tre.data[start..start + 12]
+                    .copy_from_slice(format_coefficient(coefficient)?.as_bytes());
+            }
+        }
+        let shifted = format!("{:0length$}", shifted.max(0.0).round() as u64);
+        tre.data[offset..offset + length].copy_from_slice(shifted.as_bytes());
+    }
+    Ok(())
+}
+
+/// Formats an RPC coefficient as ±d.ddddddE±d
+fn format_coefficient(value: f64) -> Result<String, NsifError> {
+    let formatted = format!("{value:+.6E}");
+    let (mantissa, exponent) = formatted.split_once('E').unwrap_or_default();
+    match exponent.parse::<i32>() {
+        _ if value == 0.0 => Ok(String::from("+0.000000E+0")),
+        Ok(exponent) if exponent < -9 => Ok(String::from("+0.000000E+0")),
+        Ok(exponent) if exponent <= 9 => Ok(format!("{mantissa}E{exponent:+}")),
+        _ => Err(NsifError::InvalidFieldValue {
+            mnemonic: "RPC",
+            value: formatted,
+        }),
+    }
+}
@@ -0,0 +1,366 @@
+//! Enumerations for fields whose values are drawn from a fixed set of codes.
+
+use std::str::FromStr;
+use strum::{AsRefStr, Display, EnumIter, EnumMessage, EnumString};
+
+/// A value of a coded field together with its human-readable description
+pub trait Code: EnumMessage + AsRef<str> {
+    fn code(&self) -> &str {
+        self.as_ref()
+    }
+
+    fn description(&self) -> &'static str {
+        self.get_message().unwrap_or_default()
+    }
+}
+
+/// Looks up the description of the code `value` of the field with the given mnemonic
+pub fn describe(mnemonic: &str, value: &str) -> Option<&'static str> {
+    fn lookup<T: Code + FromStr>(value: &str) -> Option<&'static str> {
+        T::from_str(value.trim_end())
+            .ok()
+            .map(|code| code.description())
+    }
+
+    match mnemonic {
+        "IC" => lookup::<Compression>(value),
+        "IMODE" => lookup::<ImageMode>(value),
+        "IREP" => lookup::<ImageRepresentation>(value),
+        "ICAT" => lookup::<ImageCategory>(value),
+        "PVTYPE" => lookup::<PixelValueType>(value),
+        "ICORDS" => lookup::<CoordinateRepresentation>(value),
+        _ => match security_field(mnemonic) {
+            Some("CLAS") => lookup::<Classification>(value),
+            Some("DCTP") => lookup::<DeclassificationType>(value),
+            Some("DG") => lookup::<Downgrade>(value),
+            Some("CATP") => lookup::<AuthorityType>(value),
+            Some("CRSN") => lookup::<ClassificationReason>(value),
+            _ => None,
+        },
+    }
+}
+
+/// The prefixes of the security fields in the file header and the sub headers
+pub const SECURITY_PREFIXES: [&str; 6] = ["FS", "IS", "SS", "TS", "DES", "RES"];
+
+/// Strips the prefix from the mnemonic of a security field, e.g. ISDCTP gives DCTP
+fn security_field(mnemonic: &str) -> Option<&str> {
+    SECURITY_PREFIXES
+        .iter()
+        .find_map(|prefix| mnemonic.strip_prefix(prefix))
+}
+
+/// Image compression (IC)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum Compression {
+    #[strum(serialize = "NC", message = "Not compressed")]
+    NotCompressed,
+    #[strum(serialize = "NM", message = "Not compressed, masked")]
+    NotCompressedMasked,
+    #[strum(serialize = "C1", message = "Bi-level")]
+    BiLevel,
+    #[strum(serialize = "C3", message = "JPEG")]
+    Jpeg,
+    #[strum(serialize = "C4", message = "Vector quantization")]
+    VectorQuantization,
+    #[strum(serialize = "C5", message = "Lossless JPEG")]
+    LosslessJpeg,
+    #[strum(serialize = "C6", message = "Reserved")]
+    Reserved,
+    #[strum(serialize = "C7", message = "Complex SAR")]
+    ComplexSar,
+    #[strum(serialize = "C8", message = "JPEG 2000")]
+    Jpeg2000,
+    #[strum(serialize = "I1", message = "Downsampled JPEG")]
+    DownsampledJpeg,
+    #[strum(serialize = "M1", message = "Bi-level, masked")]
+    BiLevelMasked,
+    #[strum(serialize = "M3", message = "JPEG, masked")]
+    JpegMasked,
+    #[strum(serialize = "M4", message = "Vector quantization, masked")]
+    VectorQuantizationMasked,
+    #[strum(serialize = "M5", message = "Lossless JPEG, masked")]
+    LosslessJpegMasked,
+    #[strum(serialize = "M6", message = "Reserved, masked")]
+    ReservedMasked,
+    #[strum(serialize = "M7", message = "Complex SAR, masked")]
+    ComplexSarMasked,
+    #[strum(serialize = "M8", message = "JPEG 2000, masked")]
+    Jpeg2000Masked,
+}
+
+/// Image mode (IMODE), i.e. how the bands of an image are interleaved
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum ImageMode {
+    #[strum(serialize = "B", message = "Band interleaved by block")]
+    BandInterleavedByBlock,
+    #[strum(serialize = "P", message = "Band interleaved by pixel")]
+    BandInterleavedByPixel,
+    #[strum(serialize = "R", message = "Band interleaved by row")]
+    BandInterleavedByRow,
+    #[strum(serialize = "S", message = "Band sequential")]
+    BandSequential,
+}
+
+/// Pixel value type (PVTYPE)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum PixelValueType {
+    #[strum(serialize = "INT", message = "Integer")]
+    Integer,
+    #[strum(serialize = "B", message = "Bi-level")]
+    BiLevel,
+    #[strum(serialize = "SI", message = "Signed integer")]
+    SignedInteger,
+    #[strum(serialize = "R", message = "Real")]
+    Real,
+    #[strum(serialize = "C", message = "Complex")]
+    Complex,
+}
+
+/// Security classification (FSCLAS, ISCLAS, ...), ordered from least to most restrictive
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumString,
+    Display,
+    AsRefStr,
+    EnumIter,
+    EnumMessage,
+)]
+pub enum Classification {
+    #[strum(serialize = "U", message = "Unclassified")]
+    Unclassified,
+    #[strum(serialize = "R", message = "Restricted")]
+    Restricted,
+    #[strum(serialize = "C", message = "Confidential")]
+    Confidential,
+    #[strum(serialize = "S", message = "Secret")]
+    Secret,
+    #[strum(serialize = "T", message = "Top Secret")]
+    TopSecret,
+}
+
+/// Declassification type (FSDCTP, ISDCTP, ...)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum DeclassificationType {
+    #[strum(serialize = "DD", message = "Declassify on a specific date")]
+    Date,
+    #[strum(serialize = "DE", message = "Declassify upon occurrence of an event")]
+    Event,
+    #[strum(
+        serialize = "GD",
+        message = "Downgrade to a specified level on a specific date"
+    )]
+    DowngradeDate,
+    #[strum(
+        serialize = "GE",
+        message = "Downgrade to a specified level upon occurrence of an event"
+    )]
+    DowngradeEvent,
+    #[strum(
+        serialize = "O",
+        message = "Originating agency's determination required"
+    )]
+    Oadr,
+    #[strum(serialize = "X", message = "Exempt from automatic declassification")]
+    Exempt,
+}
+
+/// The classification a segment is downgraded to (FSDG, ISDG, ...)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum Downgrade {
+    #[strum(serialize = "S", message = "Secret")]
+    Secret,
+    #[strum(serialize = "C", message = "Confidential")]
+    Confidential,
+    #[strum(serialize = "R", message = "Restricted")]
+    Restricted,
+}
+
+/// Classification authority type (FSCATP, ISCATP, ...)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum AuthorityType {
+    #[strum(serialize = "O", message = "Original classification authority")]
+    Original,
+    #[strum(serialize = "D", message = "Derivative from a single source")]
+    Derivative,
+    #[strum(serialize = "M", message = "Derivative from multiple sources")]
+    Multiple,
+}
+
+/// Classification reason (FSCRSN, ISCRSN, ...)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum ClassificationReason {
+    #[strum(
+        serialize = "A",
+        message = "Military plans, weapon systems or operations"
+    )]
+    A,
+    #[strum(serialize = "B", message = "Foreign government information")]
+    B,
+    #[strum(
+        serialize = "C",
+        message = "Intelligence activities, sources or methods, or cryptology"
+    )]
+    C,
+    #[strum(serialize = "D", message = "Foreign relations or foreign activities")]
+    D,
+    #[strum(
+        serialize = "E",
+        message = "Scientific, technological or economic matters relating to national security"
+    )]
+    E,
+    #[strum(
+        serialize = "F",
+        message = "Programs for safeguarding nuclear materials or facilities"
+    )]
+    F,
+    #[strum(
+        serialize = "G",
+        message = "Vulnerabilities or capabilities of systems, installations or plans"
+    )]
+    G,
+}
+
+/// Image representation (IREP)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum ImageRepresentation {
+    #[strum(serialize = "MONO", message = "Monochrome")]
+    Monochrome,
+    #[strum(serialize = "RGB", message = "Red, green, blue")]
+    Rgb,
+    #[strum(serialize = "RGB/LUT", message = "Mapped color")]
+    RgbLut,
+    #[strum(serialize = "MULTI", message = "Multiband")]
+    Multiband,
+    #[strum(serialize = "NODISPLY", message = "Not intended for display")]
+    NoDisplay,
+    #[strum(serialize = "NVECTOR", message = "Cartesian coordinates")]
+    CartesianVector,
+    #[strum(serialize = "POLAR", message = "Polar coordinates")]
+    PolarVector,
+    #[strum(serialize = "VPH", message = "SAR video phase history")]
+    VideoPhaseHistory,
+    #[strum(serialize = "YCbCr601", message = "ITU-R BT.601 YCbCr")]
+    YCbCr601,
+}
+
+/// Image category (ICAT)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum ImageCategory {
+    #[strum(serialize = "VIS", message = "Visible imagery")]
+    Visible,
+    #[strum(serialize = "SL", message = "Side-looking radar")]
+    SideLookingRadar,
+    #[strum(serialize = "TI", message = "Thermal infrared")]
+    ThermalInfrared,
+    #[strum(serialize = "FL", message = "Forward-looking infrared")]
+    ForwardLookingInfrared,
+    #[strum(serialize = "RD", message = "Radar")]
+    Radar,
+    #[strum(serialize = "EO", message = "Electro-optical")]
+    ElectroOptical,
+    #[strum(serialize = "OP", message = "Optical")]
+    Optical,
+    #[strum(serialize = "HR", message = "High resolution radar")]
+    HighResolutionRadar,
+    #[strum(serialize = "HS", message = "Hyperspectral")]
+    Hyperspectral,
+    #[strum(serialize = "CP", message = "Color frame photography")]
+    ColorPhotography,
+    #[strum(serialize = "BP", message = "Black/white frame photography")]
+    BlackWhitePhotography,
+    #[strum(serialize = "SAR", message = "Synthetic aperture radar")]
+    Sar,
+    #[strum(serialize = "SARIQ", message = "SAR radio hologram")]
+    SarRadioHologram,
+    #[strum(serialize = "IR", message = "Infrared")]
+    Infrared,
+    #[strum(serialize = "MAP", message = "Map")]
+    Map,
+    #[strum(serialize = "MS", message = "Multispectral")]
+    Multispectral,
+    #[strum(serialize = "FP", message = "Fingerprints")]
+    Fingerprint,
+    #[strum(serialize = "MRI", message = "Magnetic resonance imagery")]
+    MagneticResonance,
+    #[strum(serialize = "XRAY", message = "X-rays")]
+    XRay,
+    #[strum(serialize = "CAT", message = "CAT scans")]
+    CatScan,
+    #[strum(serialize = "VD", message = "Video")]
+    Video,
+    #[strum(serialize = "PAT", message = "Patterns")]
+    Pattern,
+    #[strum(serialize = "LEG", message = "Legends")]
+    Legend,
+    #[strum(serialize = "DTEM", message = "Elevation model")]
+    ElevationModel,
+    #[strum(serialize = "MATR", message = "Matrix data")]
+    Matrix,
+    #[strum(serialize = "LOCG", message = "Location grid")]
+    LocationGrid,
+    #[strum(serialize = "BARO", message = "Barometric pressure")]
+    BarometricPressure,
+    #[strum(serialize = "CURRENT", message = "Water current")]
+    WaterCurrent,
+    #[strum(serialize = "DEPTH", message = "Water depth")]
+    WaterDepth,
+    #[strum(serialize = "WIND", message = "Air wind")]
+    AirWind,
+}
+
+/// Image coordinate representation (ICORDS)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, AsRefStr, EnumIter, EnumMessage,
+)]
+pub enum CoordinateRepresentation {
+    #[strum(serialize = "", message = "No coordinates")]
+    None,
+    #[strum(serialize = "U", message = "UTM expressed in MGRS")]
+    Mgrs,
+    #[strum(serialize = "N", message = "UTM, northern hemisphere")]
+    UtmNorth,
+    #[strum(serialize = "S", message = "UTM, southern hemisphere")]
+    UtmSouth,
+    #[strum(serialize = "G", message = "Geographic")]
+    Geographic,
+    #[strum(serialize = "D", message = "Decimal degrees")]
+    DecimalDegrees,
+}
+
+impl Code for Compression {}
+impl Code for ImageMode {}
+impl Code for PixelValueType {}
+impl Code for Classification {}
+impl Code for DeclassificationType {}
+impl Code for Downgrade {}
+impl Code for AuthorityType {}
+impl Code for ClassificationReason {}
+impl Code for ImageRepresentation {}
+impl Code for ImageCategory {}
+impl Code for CoordinateRepresentation {}
@@ -0,0 +1,265 @@
+//! Encoders for the compressed image formats written into image segments.
+
+use super::error::NsifError;
+use jpeg_encoder::{ColorType, Encoder};
+use openjpeg_sys as opj;
+use std::ffi::c_void;
+
+/// Settings for compressing image segments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionOptions {
+    /// The JPEG quality from 1 to 100 used for C3
+    pub quality: u8,
+    /// The target bit rate of C8 in bits per pixel per band, `None` for numerically lossless
+    pub rate: Option<f32>,
+    /// The edge length of the blocks (C3) or tiles (C8) the image is divided into
+    pub block_size: u32,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        CompressionOptions {
+            quality: 90,
+            rate: None,
+            block_size: 1024,
+        }
+    }
+}
+
+/// Encodes 8-bit pixels in band interleaved by pixel order as a baseline JPEG stream (C3)
+pub fn encode_jpeg(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    bands: usize,
+    quality: u8,
+) -> Result<Vec<u8>, NsifError> {
+    let color_type = match bands {
+        1 => ColorType::Luma,
+        3 => ColorType::Rgb,
+        _ => return Err(NsifError::IcNotSupported),
+    };
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => return Err(NsifError::InvalidDimensions),
+    };
+    let mut bytes = Vec::new();
+    Encoder::new(&mut bytes, quality)
+        .encode(pixels, width, height, color_type)
+        .map_err(|_| NsifError::IcNotSupported)?;
+    Ok(bytes)
+}
+
+/// Encodes 8-bit pixels in band interleaved by pixel order as a JPEG 2000 codestream (C8),
+/// following the conventions of the NPJE profile: square tiles, LRCP progression and
+/// quality layers at increasing bit rates. Without a rate, the reversible 5-3 wavelet is
+/// used and the result is numerically lossless. A rate is given in bits per pixel per band
+/// and selects the irreversible 9-7 wavelet.
+pub fn encode_jpeg2000(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    bands: usize,
+    rate: Option<f32>,
+    tile_size: u32,
+) -> Result<Vec<u8>, NsifError> {
+    let color_space = match bands {
+        1 => opj::COLOR_SPACE::OPJ_CLRSPC_GRAY,
+        3 => opj::COLOR_SPACE::OPJ_CLRSPC_SRGB,
+        _ => return Err(NsifError::IcNotSupported),
+    };
+    let mut component_parameters = vec![
+        opj::opj_image_cmptparm_t {
+            dx: 1,
+            dy: 1,
+            w: width,
+            h: height,
+            x0: 0,
+            y0: 0,
+            prec: 8,
+            bpp: 8,
+            sgnd: 0,
+        };
+        bands
+    ];
+
+    let mut output = Output::default();
+    // SAFETY: every pointer handed to OpenJPEG is checked for NULL after creation and
+    // released exactly once below. The component buffers are allocated by
+    // opj_image_create with width * height samples each, which is what we write.
+    let encoded = unsafe {
+        let image =
+            opj::opj_image_create(bands as u32, component_parameters.as_mut_ptr(), color_space);
+        if image.is_null() {
+            return Err(NsifError::InvalidDimensions);
+        }
+        (*image).x1 = width;
+        (*image).y1 = height;
+        let components = std::slice::from_raw_parts_mut((*image).comps, bands);
+        for (band, component) in components.iter_mut().enumerate() {
+            let samples =
+                std::slice::from_raw_parts_mut(component.data, width as usize * height as usize);
+            for (sample, pixel) in samples
+                .iter_mut()
+                .zip(pixels.iter().skip(band).step_by(bands))
+            {
+                *sample = i32::from(*pixel);
+            }
+        }
+
+        let mut parameters = std::mem::zeroed::<opj::opj_cparameters_t>();
+        opj::opj_set_default_encoder_parameters(&mut parameters);
+        parameters.tile_size_on = 1;
+        parameters.cp_tdx = tile_size.min(width) as i32;
+        parameters.cp_tdy = tile_size.min(height) as i32;
+        parameters.prog_order = opj::PROG_ORDER::OPJ_LRCP;
+        parameters.cp_disto_alloc = 1;
+        parameters.tcp_mct = u8::from(bands == 3) as _;
+        // OpenJPEG expects the compression ratio of each layer relative to the uncompressed
+        // size, the last layer of a lossless codestream being denoted by 0
+        let final_ratio = match rate {
+            Some(rate) => {
+                parameters.irreversible = 1;
+                8.0 / rate
+            }
+            None => 0.0,
+        };
+        let layers = [64.0, 32.0, 16.0, 8.0, 4.0, 2.0]
+            .into_iter()
+            .filter(|&ratio| final_ratio == 0.0 || ratio > final_ratio)
+            .chain([final_ratio])
+            .collect::<Vec<f32>>();
+        parameters.tcp_numlayers = layers.len() as i32;
+        parameters.tcp_rates[..layers.len()].copy_from_slice(&layers);
+        let smallest_tile = parameters.cp_tdx.min(parameters.cp_tdy).max(1);
+        parameters.numresolution = (smallest_tile.ilog2() as i32).clamp(1, 6);
+
+        let codec = opj::opj_create_compress(opj::CODEC_FORMAT::OPJ_CODEC_J2K);
+        let stream = opj::opj_stream_create(1 << 16, 0);
+        let encoded = !codec.is_null()
+            && !stream.is_null()
+            && {
+                opj::opj_stream_set_user_data(
+                    stream,
+                    &mut output as *mut Output as *mut c_void,
+                    None,
+                );
+                opj::opj_stream_set_write_function(stream, Some(write_to_output));
+                opj::opj_stream_set_skip_function(stream, Some(skip_in_output));
+                opj::opj_stream_set_seek_function(stream, Some(seek_in_output));
+                opj::opj_setup_encoder(codec, &mut parameters, image) != 0
+            }
+            && opj::opj_start_compress(codec, image, stream) != 0
+            && opj::opj_encode(codec, stream) != 0
+            && opj::opj_end_compress(codec, stream) != 0;
+
+        if !stream.is_null() {
+            opj::opj_stream_destroy(stream);
+        }
+        if !codec.is_null() {
+            opj::opj_destroy_codec(codec);
+        }
+        opj::opj_image_destroy(image);
+        encoded
+    };
+    if !encoded {
+        return Err(NsifError::IcNotSupported);
+    }
+    Ok(output.bytes)
+}
+
+/// The destination of the codestream. OpenJPEG writes mostly sequentially but seeks back
+/// to patch marker segments, so the write position is tracked separately.
+#[derive(Default)]
+struct Output {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+unsafe extern "C" fn write_to_output(
+    buffer: *mut c_void,
+    length: usize,
+    output: *mut c_void,
+) -> usize {
+    let output = &mut *(output as *mut Output);
+    let buffer = std::slice::from_raw_parts(buffer as *const u8, length);
+    let end = output.position + length;
+    if output.bytes.len() < end {
+        output.bytes.resize(end, 0);
+    }
+    output.bytes[output.position..end].copy_from_slice(buffer);
+    output.position = end;
+    length
+}
+
+unsafe extern "C" fn skip_in_output(length: i64, output: *mut c_void) -> i64 {
+    let output = &mut *(output as *mut Output);
+    match output.position.checked_add_signed(length as isize) {
+        Some(position) => {
+            output.position = position;
+            length
+        }
+        None => -1,
+    }
+}
+
+unsafe extern "C" fn seek_in_output(position: i64, output: *mut c_void) -> i32 {
+    let output = &mut *(output as *mut Output);
+    output.position = position as usize;
+    1
+}
+
+/// Copies a block out of an image, replicating the last row and column of the image
+/// where the block extends beyond it, so that all blocks have the same size
+pub(crate) fn extract_block(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    bands: usize,
+    (left, top): (u32, u32),
+    (block_width, block_height): (u32, u32),
+) -> Vec<u8> {
+    let mut block = Vec::with_capacity(block_width as usize * block_height as usize * bands);
+    for y in top..top + block_height {
+        let row = y.min(height - 1) as usize * width as usize;
+        for x in left..left + block_width {
+            let start = (row + x.min(width - 1) as usize) * bands;
+            block.extend_from_slice(&pixels[start..start + bands]);
+        }
+    }
+    block
+}
+
+/// Splits the concatenated JPEG streams of a blocked C3 image segment into one stream per
+/// block by following the marker segments of each stream up to its EOI marker
+pub(crate) fn split_jpeg_streams(data: &[u8]) -> Vec<&[u8]> {
+    let mut streams = Vec::new();
+    let mut start = 0;
+    let mut position = 2;
+    while position + 1 < data.len() {
+        if data[position] != 0xFF {
+            position += 1;
+            continue;
+        }
+        match data[position + 1] {
+            // Fill bytes, stuffed 0xFF bytes and restart markers inside entropy-coded data
+            0xFF => position += 1,
+            0x00 | 0xD0..=0xD7 => position += 2,
+            0xD9 => {
+                position += 2;
+                streams.push(&data[start..position]);
+                start = position;
+                position += 2;
+            }
+            _ if position + 3 < data.len() => {
+                let length = u16::from_be_bytes([data[position + 2], data[position + 3]]);
+                position += 2 + length as usize;
+            }
+            _ => break,
+        }
+    }
+    if start < data.len() {
+        streams.push(&data[start..]);
+    }
+    streams
+}
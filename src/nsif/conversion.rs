@@ -0,0 +1,73 @@
+//! Conversion between NSIF 1.0 and NITF 2.1. Both profiles share the same header layout, so a
+//! file is converted by rewriting FHDR and FVER and, as NATO and national consumers mark their
+//! products in different classification systems, the xxCLSY field of every header.
+
+use super::error::NsifError;
+use super::fileheader::Profile;
+use super::rawsegment::RawSegment;
+use super::security::{EXTENSION_SECURITY_OFFSET, GRAPHIC_SECURITY_OFFSET, TEXT_SECURITY_OFFSET};
+use super::{HeaderFields, NSIF};
+
+/// The offset of the classification system within the security fields
+const SYSTEM_OFFSET: usize = 1;
+
+/// Rewrites the file for the given profile. If a classification system (e.g. NS for NATO or a
+/// country code) is given, it replaces the system of every header that is classified or names
+/// one; unclassified headers without a system are left blank.
+pub fn convert(
+    nsif: &mut NSIF,
+    profile: Profile,
+    classification_system: Option<&str>,
+) -> Result<(), NsifError> {
+    nsif.file_header.fhdr.set(profile.fhdr())?;
+    nsif.file_header.fver.set(profile.fver())?;
+    let Some(system) = classification_system else {
+        return Ok(());
+    };
+    if system.len() != 2 || !system.is_ascii() {
+        return Err(NsifError::InvalidFieldValue {
+            mnemonic: "FSCLSY",
+            value: system.to_owned(),
+        });
+    }
+
+    let file_header = &mut nsif.file_header;
+    if is_marked(&file_header.fsclas.bytes(), &file_header.fsclsy.bytes()) {
+        file_header.set_field("FSCLSY", system)?;
+    }
+    for image_segment in &mut nsif.image_segments {
+        let sub_header = &mut image_segment.sub_header;
+        if is_marked(&sub_header.isclas.bytes(), &sub_header.isclsy.bytes()) {
+            sub_header.set_field("ISCLSY", system)?;
+        }
+    }
+    for (segments, offset) in [
+        (&mut nsif.graphic_segments, GRAPHIC_SECURITY_OFFSET),
+        (&mut nsif.text_segments, TEXT_SECURITY_OFFSET),
+        (&mut nsif.data_extension_segments, EXTENSION_SECURITY_OFFSET),
+        (
+            &mut nsif.reserved_extension_segments,
+            EXTENSION_SECURITY_OFFSET,
+        ),
+    ] {
+        for segment in segments.iter_mut() {
+            set_system(segment, offset, system.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Whether a header with the given xxCLAS and xxCLSY carries a classification system
+fn is_marked(classification: &[u8], system: &[u8]) -> bool {
+    classification != b"U" || system.iter().any(|&byte| byte != b' ')
+}
+
+fn set_system(segment: &mut RawSegment, offset: usize, system: &[u8]) {
+    let start = offset + SYSTEM_OFFSET;
+    if let Some(fields) = segment.sub_header.get_mut(offset..start + system.len()) {
+        let (classification, field) = fields.split_at_mut(SYSTEM_OFFSET);
+        if is_marked(classification, field) {
+            field.copy_from_slice(system);
+        }
+    }
+}
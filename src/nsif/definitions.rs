@@ -0,0 +1,317 @@
+//! Field definitions for the NSIF file header and segment subheaders.
+//!
+//! Every field is described by its mnemonic, human-readable name, length and character set
+//! as given by STANAG 4545 / MIL-STD-2500C. Parsing, writing and validation are driven by
+//! these tables rather than by hard-coded buffer sizes.
+
+use crate::nsif::field::{FieldDefinition, FieldKind};
+
+const fn bcs_a(mnemonic: &'static str, name: &'static str, length: usize) -> FieldDefinition {
+    FieldDefinition::new(mnemonic, name, Some(length), FieldKind::BcsA, None)
+}
+
+const fn ecs_a(mnemonic: &'static str, name: &'static str, length: usize) -> FieldDefinition {
+    FieldDefinition::new(mnemonic, name, Some(length), FieldKind::EcsA, None)
+}
+
+const fn bcs_n(mnemonic: &'static str, name: &'static str, length: usize) -> FieldDefinition {
+    FieldDefinition::new(mnemonic, name, Some(length), FieldKind::BcsN, None)
+}
+
+const fn bcs_n_range(
+    mnemonic: &'static str,
+    name: &'static str,
+    length: usize,
+    min: u64,
+    max: u64,
+) -> FieldDefinition {
+    FieldDefinition::new(
+        mnemonic,
+        name,
+        Some(length),
+        FieldKind::BcsN,
+        Some((min, max)),
+    )
+}
+
+const fn binary(
+    mnemonic: &'static str,
+    name: &'static str,
+    length: Option<usize>,
+) -> FieldDefinition {
+    FieldDefinition::new(mnemonic, name, length, FieldKind::Binary, None)
+}
+
+pub mod file_header {
+    use super::*;
+
+    pub const FHDR: FieldDefinition = bcs_a("FHDR", "File Profile Name", 4);
+    pub const FVER: FieldDefinition = bcs_a("FVER", "File Version", 5);
+    pub const CLEVEL: FieldDefinition = bcs_n_range("CLEVEL", "Complexity level", 2, 1, 99);
+    pub const STYPE: FieldDefinition = bcs_a("STYPE", "Standard Type", 4);
+    pub const OSTAID: FieldDefinition = bcs_a("OSTAID", "Originating Station Identifier", 10);
+    pub const FDT: FieldDefinition = bcs_n("FDT", "File Date and Time", 14);
+    pub const FTITLE: FieldDefinition = ecs_a("FTITLE", "File Title", 80);
+    pub const FSCLAS: FieldDefinition = bcs_a("FSCLAS", "File Security Classification", 1);
+    pub const FSCLSY: FieldDefinition = bcs_a("FSCLSY", "File Security Classification System", 2);
+    pub const FSCODE: FieldDefinition = bcs_a("FSCODE", "File Codewords", 11);
+    pub const FSCTLH: FieldDefinition = bcs_a("FSCTLH", "File Control and Handling", 2);
+    pub const FSREL: FieldDefinition = bcs_a("FSREL", "File Releasing Instructions", 20);
+    pub const FSDCTP: FieldDefinition = bcs_a("FSDCTP", "File Declassification Type", 2);
+    pub const FSDCDT: FieldDefinition = bcs_a("FSDCDT", "File Declassification Date", 8);
+    pub const FSDCXM: FieldDefinition = bcs_a("FSDCXM", "File Declassification Exemption", 4);
+    pub const FSDG: FieldDefinition = bcs_a("FSDG", "File Downgrade", 1);
+    pub const FSDGDT: FieldDefinition = bcs_a("FSDGDT", "File Downgrade Date", 8);
+    pub const FSCLTX: FieldDefinition = ecs_a("FSCLTX", "File Classification Text", 43);
+    pub const FSCATP: FieldDefinition = bcs_a("FSCATP", "File Classification Authority Type", 1);
+    pub const FSCAUT: FieldDefinition = ecs_a("FSCAUT", "File Classification Authority", 40);
+    pub const FSCRSN: FieldDefinition = bcs_a("FSCRSN", "File Classification Reason", 1);
+    pub const FSSRDT: FieldDefinition = bcs_a("FSSRDT", "File Security Source Date", 8);
+    pub const FSCTLN: FieldDefinition = bcs_a("FSCTLN", "File Security Control Number", 15);
+    pub const FSCOP: FieldDefinition = bcs_n_range("FSCOP", "File Copy Number", 5, 0, 99999);
+    pub const FSCPYS: FieldDefinition = bcs_n_range("FSCPYS", "File Number of Copies", 5, 0, 99999);
+    pub const ENCRYP: FieldDefinition = bcs_n_range("ENCRYP", "Encryption", 1, 0, 0);
+    pub const FBKGC: FieldDefinition = binary("FBKGC", "File Background Color", Some(3));
+    pub const ONAME: FieldDefinition = ecs_a("ONAME", "Originator's Name", 24);
+    pub const OPHONE: FieldDefinition = ecs_a("OPHONE", "Originator's Phone Number", 18);
+    pub const FL: FieldDefinition = bcs_n_range("FL", "File Length", 12, 388, 999_999_999_998);
+    pub const HL: FieldDefinition = bcs_n_range("HL", "NSIF File Header Length", 6, 388, 999_999);
+    pub const NUMI: FieldDefinition = bcs_n_range("NUMI", "Number of Image Segments", 3, 0, 999);
+    pub const LISH: FieldDefinition =
+        bcs_n_range("LISH", "Length of Image Subheader", 6, 439, 999_999);
+    pub const LI: FieldDefinition =
+        bcs_n_range("LI", "Length of Image Segment", 10, 1, 9_999_999_999);
+    pub const NUMS: FieldDefinition = bcs_n_range("NUMS", "Number of Graphic Segments", 3, 0, 999);
+    pub const LSSH: FieldDefinition =
+        bcs_n_range("LSSH", "Length of Graphic Subheader", 4, 258, 9999);
+    pub const LS: FieldDefinition = bcs_n_range("LS", "Length of Graphic Segment", 6, 1, 999_999);
+    pub const NUMX: FieldDefinition = bcs_n_range("NUMX", "Reserved for Future Use", 3, 0, 0);
+    pub const NUMT: FieldDefinition = bcs_n_range("NUMT", "Number of Text Segments", 3, 0, 999);
+    pub const LTSH: FieldDefinition = bcs_n_range("LTSH", "Length of Text Subheader", 4, 282, 9999);
+    pub const LT: FieldDefinition = bcs_n_range("LT", "Length of Text Segment", 5, 1, 99999);
+    pub const NUMDES: FieldDefinition =
+        bcs_n_range("NUMDES", "Number of Data Extension Segments", 3, 0, 999);
+    pub const LDSH: FieldDefinition = bcs_n_range(
+        "LDSH",
+        "Length of Data Extension Segment Subheader",
+        4,
+        200,
+        9999,
+    );
+    pub const LD: FieldDefinition =
+        bcs_n_range("LD", "Length of Data Extension Segment", 9, 1, 999_999_999);
+    pub const NUMRES: FieldDefinition =
+        bcs_n_range("NUMRES", "Number of Reserved Extension Segments", 3, 0, 999);
+    pub const LRESH: FieldDefinition = bcs_n_range(
+        "LRESH",
+        "Length of Reserved Extension Segment Subheader",
+        4,
+        200,
+        9999,
+    );
+    pub const LRE: FieldDefinition = bcs_n_range(
+        "LRE",
+        "Length of Reserved Extension Segment",
+        7,
+        1,
+        9_999_999,
+    );
+    pub const UDHDL: FieldDefinition =
+        bcs_n_range("UDHDL", "User-Defined Header Data Length", 5, 0, 99999);
+    pub const UDHOFL: FieldDefinition =
+        bcs_n_range("UDHOFL", "User-Defined Header Overflow", 3, 0, 999);
+    pub const UDHD: FieldDefinition = binary("UDHD", "User-Defined Header Data", None);
+    pub const XHDL: FieldDefinition =
+        bcs_n_range("XHDL", "Extended Header Data Length", 5, 0, 99999);
+    pub const XHDLOFL: FieldDefinition =
+        bcs_n_range("XHDLOFL", "Extended Header Data Overflow", 3, 0, 999);
+    pub const XHD: FieldDefinition = binary("XHD", "Extended Header Data", None);
+
+    /// Fields derived from the segments and the header itself, recomputed whenever a file is written
+    pub const COMPUTED: &[&str] = &[
+        "FL", "HL", "NUMI", "LISH", "LI", "NUMS", "LSSH", "LS", "NUMX", "NUMT", "LTSH", "LT",
+        "NUMDES", "LDSH", "LD", "NUMRES", "LRESH", "LRE", "UDHDL", "UDHOFL", "XHDL", "XHDLOFL",
+    ];
+}
+
+pub mod image_subheader {
+    use super::*;
+
+    pub const IM: FieldDefinition = bcs_a("IM", "File Part Type", 2);
+    pub const IID1: FieldDefinition = bcs_a("IID1", "Image Identifier 1", 10);
+    pub const IDATIM: FieldDefinition = bcs_n("IDATIM", "Image Date and Time", 14);
+    pub const TGTID: FieldDefinition = bcs_a("TGTID", "Target Identifier", 17);
+    pub const IID2: FieldDefinition = ecs_a("IID2", "Image Identifier 2", 80);
+    pub const ISCLAS: FieldDefinition = bcs_a("ISCLAS", "Image Security Classification", 1);
+    pub const ISCLSY: FieldDefinition = bcs_a("ISCLSY", "Image Security Classification System", 2);
+    pub const ISCODE: FieldDefinition = bcs_a("ISCODE", "Image Codewords", 11);
+    pub const ISCTLH: FieldDefinition = bcs_a("ISCTLH", "Image Control and Handling", 2);
+    pub const ISREL: FieldDefinition = bcs_a("ISREL", "Image Releasing Instructions", 20);
+    pub const ISDCTP: FieldDefinition = bcs_a("ISDCTP", "Image Declassification Type", 2);
+    pub const ISDCDT: FieldDefinition = bcs_a("ISDCDT", "Image Declassification Date", 8);
+    pub const ISDCXM: FieldDefinition = bcs_a("ISDCXM", "Image Declassification Exemption", 4);
+    pub const ISDG: FieldDefinition = bcs_a("ISDG", "Image Downgrade", 1);
+    pub const ISDGDT: FieldDefinition = bcs_a("ISDGDT", "Image Downgrade Date", 8);
+    pub const ISCLTX: FieldDefinition = ecs_a("ISCLTX", "Image Classification Text", 43);
+    pub const ISCATP: FieldDefinition = bcs_a("ISCATP", "Image Classification Authority Type", 1);
+    pub const ISCAUT: FieldDefinition = ecs_a("ISCAUT", "Image Classification Authority", 40);
+    pub const ISCRSN: FieldDefinition = bcs_a("ISCRSN", "Image Classification Reason", 1);
+    pub const ISSRDT: FieldDefinition = bcs_a("ISSRDT", "Image Security Source Date", 8);
+    pub const ISCTLN: FieldDefinition = bcs_a("ISCTLN", "Image Security Control Number", 15);
+    pub const ENCRYP: FieldDefinition = bcs_n_range("ENCRYP", "Encryption", 1, 0, 0);
+    pub const ISORCE: FieldDefinition = ecs_a("ISORCE", "Image Source", 42);
+    pub const NROWS: FieldDefinition = bcs_n_range(
+        "NROWS",
+        "Number of Significant Rows in Image",
+        8,
+        1,
+        99_999_999,
+    );
+    pub const NCOLS: FieldDefinition = bcs_n_range(
+        "NCOLS",
+        "Number of Significant Columns in Image",
+        8,
+        1,
+        99_999_999,
+    );
+    pub const PVTYPE: FieldDefinition = bcs_a("PVTYPE", "Pixel Value Type", 3);
+    pub const IREP: FieldDefinition = bcs_a("IREP", "Image Representation", 8);
+    pub const ICAT: FieldDefinition = bcs_a("ICAT", "Image Category", 8);
+    pub const ABPP: FieldDefinition =
+        bcs_n_range("ABPP", "Actual Bits-per-Pixel per Band", 2, 1, 96);
+    pub const PJUST: FieldDefinition = bcs_a("PJUST", "Pixel Justification", 1);
+    pub const ICORDS: FieldDefinition = bcs_a("ICORDS", "Image Coordinate Representation", 1);
+    pub const IGEOLO: FieldDefinition = bcs_a("IGEOLO", "Image Geographic Location", 60);
+    pub const NICOM: FieldDefinition = bcs_n_range("NICOM", "Number of Image Comments", 1, 0, 9);
+    pub const ICOM: FieldDefinition = ecs_a("ICOM", "Image comments", 80);
+    pub const IC: FieldDefinition = bcs_a("IC", "Image compression", 2);
+    pub const COMRAT: FieldDefinition = bcs_a("COMRAT", "Compression Rate Code", 4);
+    pub const NBANDS: FieldDefinition = bcs_n_range("NBANDS", "Number of Bands", 1, 0, 9);
+    pub const XBANDS: FieldDefinition =
+        bcs_n_range("XBANDS", "Number of Multispectral Bands", 5, 10, 99999);
+    pub const IREPBAND: FieldDefinition = bcs_a("IREPBAND", "Band Representations", 2);
+    pub const ISUBCAT: FieldDefinition = bcs_a("ISUBCAT", "Band Subcategories", 6);
+    pub const IFC: FieldDefinition = bcs_a("IFC", "Band Image Filter Condition", 1);
+    pub const IMFLT: FieldDefinition = bcs_a("IMFLT", "Band Standard Image Code", 3);
+    pub const NLUTS: FieldDefinition = bcs_n_range("NLUTS", "Number of LUTs", 1, 0, 4);
+    pub const NELUT: FieldDefinition = bcs_n_range("NELUT", "Number of LUT entries", 5, 1, 65536);
+    pub const LUTD: FieldDefinition = binary("LUTD", "LUTs", None);
+    pub const ISYNC: FieldDefinition = bcs_n_range("ISYNC", "Image Sync Code", 1, 0, 0);
+    pub const IMODE: FieldDefinition = bcs_a("IMODE", "Image Mode", 1);
+    pub const NBPR: FieldDefinition = bcs_n_range("NBPR", "Number of Blocks per Row", 4, 1, 9999);
+    pub const NBPC: FieldDefinition =
+        bcs_n_range("NBPC", "Number of Blocks per Columns", 4, 1, 9999);
+    pub const NPPBH: FieldDefinition =
+        bcs_n_range("NPPBH", "Number of Pixels per Block Horizontal", 4, 0, 8192);
+    pub const NPPBV: FieldDefinition =
+        bcs_n_range("NPPBV", "Number of Pixels per Block Vertical", 4, 0, 8192);
+    pub const NBPP: FieldDefinition =
+        bcs_n_range("NBPP", "Number of Bits per Pixel per Band", 2, 1, 96);
+    pub const IDLVL: FieldDefinition = bcs_n_range("IDLVL", "Image Display Level", 3, 1, 999);
+    pub const IALVL: FieldDefinition = bcs_n_range("IALVL", "Image Attachment Level", 3, 0, 998);
+    pub const ILOC: FieldDefinition = bcs_n("ILOC", "Image Location", 10);
+    pub const IMAG: FieldDefinition = bcs_a("IMAG", "Image Magnification", 4);
+    pub const UDIDL: FieldDefinition =
+        bcs_n_range("UDIDL", "User-Defined Image Data Length", 5, 0, 99999);
+    pub const UDOFL: FieldDefinition = bcs_n_range("UDOFL", "User-Defined Overflow", 3, 0, 999);
+    pub const UDID: FieldDefinition = binary("UDID", "User-Defined Image Data", None);
+    pub const IXSHDL: FieldDefinition =
+        bcs_n_range("IXSHDL", "Image Extended Subheader Length", 5, 0, 99999);
+    pub const IXSOFL: FieldDefinition =
+        bcs_n_range("IXSOFL", "Image Extended Subheader Overflow", 3, 0, 999);
+    pub const IXSHD: FieldDefinition = binary("IXSHD", "Image Extended Subheader Data", None);
+
+    /// Fields derived from the rest of the sub header, recomputed whenever a file is written.
+    /// NBANDS and XBANDS are included because they determine the layout of the band fields.
+    pub const COMPUTED: &[&str] = &[
+        "NICOM", "NBANDS", "XBANDS", "UDIDL", "UDOFL", "IXSHDL", "IXSOFL",
+    ];
+}
+
+/// The fixed fields of the graphic, text, data extension and reserved extension sub headers,
+/// which are kept as raw bytes. `BEFORE_SECURITY` and `AFTER_SECURITY` follow one another
+/// with the security fields in between, up to the length of the variable part.
+pub mod graphic_subheader {
+    use super::*;
+
+    pub const BEFORE_SECURITY: &[FieldDefinition] = &[
+        bcs_a("SY", "File Part Type", 2),
+        bcs_a("SID", "Graphic Identifier", 10),
+        ecs_a("SNAME", "Graphic Name", 20),
+    ];
+    pub const AFTER_SECURITY: &[FieldDefinition] = &[
+        bcs_n_range("ENCRYP", "Encryption", 1, 0, 0),
+        bcs_a("SFMT", "Graphic Type", 1),
+        bcs_n_range("SSTRUCT", "Reserved for Future Use", 13, 0, 0),
+        bcs_n_range("SDLVL", "Graphic Display Level", 3, 1, 999),
+        bcs_n_range("SALVL", "Graphic Attachment Level", 3, 0, 998),
+        bcs_n("SLOC", "Graphic Location", 10),
+        bcs_n("SBND1", "First Graphic Bound Location", 10),
+        bcs_a("SCOLOR", "Graphic Color", 1),
+        bcs_n("SBND2", "Second Graphic Bound Location", 10),
+        bcs_n_range("SRES2", "Reserved for Future Use", 2, 0, 0),
+        bcs_n_range(
+            "SXSHDL",
+            "Graphic Extended Subheader Data Length",
+            5,
+            0,
+            9741,
+        ),
+    ];
+}
+
+pub mod text_subheader {
+    use super::*;
+
+    pub const BEFORE_SECURITY: &[FieldDefinition] = &[
+        bcs_a("TE", "File Part Type", 2),
+        bcs_a("TEXTID", "Text Identifier", 7),
+        bcs_n_range("TXTALVL", "Text Attachment Level", 3, 0, 998),
+        bcs_n("TXTDT", "Text Date and Time", 14),
+        ecs_a("TXTITL", "Text Title", 80),
+    ];
+    pub const AFTER_SECURITY: &[FieldDefinition] = &[
+        bcs_n_range("ENCRYP", "Encryption", 1, 0, 0),
+        bcs_a("TXTFMT", "Text Format", 3),
+        bcs_n_range("TXSHDL", "Text Extended Subheader Data Length", 5, 0, 9717),
+    ];
+}
+
+pub mod data_extension_subheader {
+    use super::*;
+
+    pub const BEFORE_SECURITY: &[FieldDefinition] = &[
+        bcs_a("DE", "File Part Type", 2),
+        bcs_a("DESID", "Unique DES Type Identifier", 25),
+        bcs_n_range("DESVER", "Version of the Data Definition", 2, 1, 99),
+    ];
+    /// Only present in TRE_OVERFLOW segments
+    pub const OVERFLOW: &[FieldDefinition] = &[
+        bcs_a("DESOFLW", "DES Overflowed Header Type", 6),
+        bcs_n_range("DESITEM", "DES Data Item Overflowed", 3, 0, 999),
+    ];
+    pub const AFTER_SECURITY: &[FieldDefinition] = &[bcs_n_range(
+        "DESSHL",
+        "Length of DES-Defined Subheader Fields",
+        4,
+        0,
+        9999,
+    )];
+}
+
+pub mod reserved_extension_subheader {
+    use super::*;
+
+    pub const BEFORE_SECURITY: &[FieldDefinition] = &[
+        bcs_a("RE", "File Part Type", 2),
+        bcs_a("RESID", "Unique RES Type Identifier", 25),
+        bcs_n_range("RESVER", "Version of the Data Definition", 2, 1, 99),
+    ];
+    pub const AFTER_SECURITY: &[FieldDefinition] = &[bcs_n_range(
+        "RESSHL",
+        "Length of RES-Defined Subheader Fields",
+        4,
+        0,
+        9999,
+    )];
+}
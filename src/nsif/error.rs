@@ -1,3 +1,5 @@
+use super::field::FieldKind;
+use super::validation::Location;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,4 +14,55 @@ pub enum NsifError {
     ImageSegmentSubHeaderMalformed,
     #[error("The given image segment has invalid dimensions")]
     InvalidDimensions,
+    #[error("{field} declares a length of {declared} bytes but {consumed} bytes were parsed")]
+    LengthMismatch {
+        field: String,
+        declared: u64,
+        consumed: u64,
+    },
+    #[error(
+        "The file is truncated: its segments end at byte {expected} but it is {actual} bytes long"
+    )]
+    TruncatedFile { expected: u64, actual: u64 },
+    #[error("The tagged record extension at byte {0} of its field is malformed")]
+    MalformedTre(usize),
+    #[error("The character '{0}' cannot be represented in ISO 8859-1")]
+    UnencodableCharacter(char),
+    #[error("The value '{value}' of field {mnemonic} is invalid")]
+    InvalidFieldValue {
+        mnemonic: &'static str,
+        value: String,
+    },
+    #[error("The value '{value}' of security field {field} is invalid")]
+    InvalidSecurityField { field: String, value: String },
+    #[error("The given date and time is invalid")]
+    InvalidDateTime,
+    #[error("The file has no {0}")]
+    MissingSegment(Location),
+    #[error("There is no field {0}")]
+    UnknownField(String),
+    #[error("Field {0} is computed when the file is written and cannot be set")]
+    ComputedField(&'static str),
+    #[error("Field {0} holds more than one value and cannot be set as a whole")]
+    MultiValuedField(&'static str),
+    #[error(
+        "The value of field {mnemonic} is {actual} bytes long but the field holds {length} bytes"
+    )]
+    InvalidFieldLength {
+        mnemonic: &'static str,
+        length: usize,
+        actual: usize,
+    },
+    #[error("The value of field {mnemonic} contains characters outside of {kind}")]
+    InvalidCharacterSet {
+        mnemonic: &'static str,
+        kind: FieldKind,
+    },
+    #[error("The value {value} of field {mnemonic} is outside of the range {min} to {max}")]
+    ValueOutOfRange {
+        mnemonic: &'static str,
+        value: u64,
+        min: u64,
+        max: u64,
+    },
 }
@@ -1,18 +1,87 @@
 use super::imagesegment::ImageSegment;
+use super::security::Banner;
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
 use jpeg_encoder::{ColorType, Encoder};
 use std::path::PathBuf;
 
+/// Exports the image segment as JPEG, with classification banners above and below the
+/// image if `banner` is given
 pub fn export_to_jpeg(
     image_segment: &ImageSegment,
     path: PathBuf,
+    banner: Option<&Banner>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let data = image_segment.as_rgb()?;
+    let mut data = image_segment.as_rgb()?;
     let mut output_path = path.clone();
     output_path.set_extension("jpg");
 
     let encoder = Encoder::new_file(output_path, 100)?;
     let (height, width) = image_segment.dimensions()?;
+    let (width, mut height) = (width as u32, height as u32);
+    if let Some(banner) = banner {
+        (data, height) = add_banners(&data, width, height, banner)?;
+    }
     encoder
         .encode(&data, width as _, height as _, ColorType::Rgb)
         .map_err(Into::into)
 }
+
+/// Adds classification banners above and below RGB pixels, returning the pixels and their
+/// new height
+pub fn add_banners(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    banner: &Banner,
+) -> Result<(Vec<u8>, u32), Box<dyn std::error::Error>> {
+    let font = FontRef::try_from_slice(epaint_default_fonts::HACK_REGULAR)?;
+    let banner_height = (height / 20).clamp(16, 64);
+    let text_width = |scale: PxScale| {
+        let font = font.as_scaled(scale);
+        banner
+            .text
+            .chars()
+            .map(|c| font.h_advance(font.glyph_id(c)))
+            .sum::<f32>()
+    };
+    let mut scale = PxScale::from(banner_height as f32 * 0.75);
+    let available_width = width as f32 * 0.95;
+    if text_width(scale) > available_width {
+        scale = PxScale::from(scale.y * available_width / text_width(scale));
+    }
+
+    let [r, g, b] = banner.color();
+    let mut strip = [r, g, b].repeat((width * banner_height) as usize);
+    let scaled_font = font.as_scaled(scale);
+    let mut x = (width as f32 - text_width(scale)) / 2.0;
+    let baseline = (banner_height as f32 + scaled_font.ascent() + scaled_font.descent()) / 2.0;
+    let text_color = banner.text_color();
+    for c in banner.text.chars() {
+        let mut glyph = scaled_font.scaled_glyph(c);
+        glyph.position = point(x, baseline);
+        x += scaled_font.h_advance(glyph.id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|glyph_x, glyph_y, coverage| {
+            let pixel_x = bounds.min.x as i64 + glyph_x as i64;
+            let pixel_y = bounds.min.y as i64 + glyph_y as i64;
+            if !(0..width as i64).contains(&pixel_x)
+                || !(0..banner_height as i64).contains(&pixel_y)
+            {
+                return;
+            }
+            let offset = ((pixel_y * width as i64 + pixel_x) * 3) as usize;
+            for (channel, text) in strip[offset..offset + 3].iter_mut().zip(text_color) {
+                *channel = (*channel as f32 * (1.0 - coverage) + text as f32 * coverage) as u8;
+            }
+        });
+    }
+
+    let mut result = Vec::with_capacity(pixels.len() + strip.len() * 2);
+    result.extend_from_slice(&strip);
+    result.extend_from_slice(pixels);
+    result.extend_from_slice(&strip);
+    Ok((result, height + 2 * banner_height))
+}
@@ -1,11 +1,66 @@
+use super::codes::describe;
+use super::error::NsifError;
+use super::{
+    encode_string_to_bytes, is_bcs, is_bcs_n, is_ecs, parse_string_from_bytes,
+    parse_unsigned_integers_from_byte,
+};
 use bevy_reflect::Reflect;
 use enum_as_inner::EnumAsInner;
 use std::fmt::Display;
+use std::io::{self, Read, Seek};
+use std::str::FromStr;
 
-#[derive(Debug, Reflect)]
+#[derive(Debug, Clone, Reflect)]
 pub struct Field {
-    pub name: String,
     pub value: Value,
+    definition: FieldDefinition,
+    offset: u64,
+}
+
+/// The character set a field is drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, strum::Display)]
+pub enum FieldKind {
+    /// Basic Character Set, alphanumeric
+    #[strum(serialize = "BCS-A")]
+    BcsA,
+    /// Basic Character Set, numeric
+    #[strum(serialize = "BCS-N")]
+    BcsN,
+    /// Extended Character Set, alphanumeric
+    #[strum(serialize = "ECS-A")]
+    EcsA,
+    /// Unformatted binary data
+    Binary,
+}
+
+/// Describes a field as specified by the standard, see [`crate::nsif::definitions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct FieldDefinition {
+    pub mnemonic: &'static str,
+    pub name: &'static str,
+    /// The length in bytes, `None` if the length is given by another field
+    pub length: Option<usize>,
+    pub kind: FieldKind,
+    /// The inclusive range of allowed values of a BCS-N field
+    pub range: Option<(u64, u64)>,
+}
+
+impl FieldDefinition {
+    pub const fn new(
+        mnemonic: &'static str,
+        name: &'static str,
+        length: Option<usize>,
+        kind: FieldKind,
+        range: Option<(u64, u64)>,
+    ) -> Self {
+        FieldDefinition {
+            mnemonic,
+            name,
+            length,
+            kind,
+            range,
+        }
+    }
 }
 
 pub trait IsEmpty {
@@ -18,9 +73,9 @@ impl IsEmpty for Field {
     }
 }
 
-#[derive(Debug, Reflect, EnumAsInner)]
+#[derive(Debug, Clone, Reflect, EnumAsInner)]
 pub enum Value {
-    SingleAlphanumeric(AlphanumericValue), // BCS vs ECS?
+    SingleAlphanumeric(AlphanumericValue),
     MultipleAlphanumeric(Vec<AlphanumericValue>),
     NestedAlphaNumeric(Vec<Vec<AlphanumericValue>>),
     SingleNumeric(NumericValue), // We use String because of leading zeros, datetime-format etc. We will see about TryFrom trait implementations ...
@@ -28,90 +83,504 @@ pub enum Value {
     NestedNumeric(Vec<Vec<NumericValue>>),
 }
 
-#[derive(Debug, Reflect)]
+#[derive(Debug, Clone, Reflect)]
 pub struct AlphanumericValue {
     pub value: String,
+    /// The bytes as they were read from the file, kept for round-tripping
+    pub bytes: Vec<u8>,
 }
 
-impl From<String> for AlphanumericValue {
+impl From<Vec<u8>> for AlphanumericValue {
     // TODO will probably have to be discarded if range info
     // is required for editing functionality
-    fn from(value: String) -> Self {
-        Self { value }
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            value: parse_string_from_bytes(&bytes),
+            bytes,
+        }
     }
 }
 
-#[derive(Debug, Reflect)]
+#[derive(Debug, Clone, Reflect)]
 pub struct NumericValue {
     pub value: String,
+    /// The bytes as they were read from the file, kept for round-tripping
+    pub bytes: Vec<u8>,
 }
 
-impl From<String> for NumericValue {
-    fn from(value: String) -> Self {
-        Self { value }
+impl AlphanumericValue {
+    /// Binary values are shown as hex bytes unless they consist of printable characters only
+    fn with_kind(kind: FieldKind, bytes: Vec<u8>) -> Self {
+        if kind == FieldKind::Binary && !is_ecs(&bytes) {
+            AlphanumericValue {
+                value: parse_unsigned_integers_from_byte(&bytes),
+                bytes,
+            }
+        } else {
+            bytes.into()
+        }
+    }
+}
+
+impl From<Vec<u8>> for NumericValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            value: parse_string_from_bytes(&bytes),
+            bytes,
+        }
     }
 }
 
 impl Field {
-    pub fn from_alphanumeric(name: &str, value: String) -> Self {
+    pub fn single(definition: FieldDefinition, offset: u64, value: Vec<u8>) -> Self {
+        let value = match definition.kind {
+            FieldKind::BcsN => Value::SingleNumeric(value.into()),
+            kind => Value::SingleAlphanumeric(AlphanumericValue::with_kind(kind, value)),
+        };
         Field {
-            name: name.to_owned(),
-            value: Value::SingleAlphanumeric(value.into()),
+            value,
+            definition,
+            offset,
         }
     }
 
-    pub fn from_multiple_alphanumeric(name: &str, value: Vec<String>) -> Self {
+    pub fn multiple(definition: FieldDefinition, offset: u64, value: Vec<Vec<u8>>) -> Self {
+        let value = match definition.kind {
+            FieldKind::BcsN => Value::MultipleNumeric(value.into_iter().map(Into::into).collect()),
+            kind => Value::MultipleAlphanumeric(
+                value
+                    .into_iter()
+                    .map(|v| AlphanumericValue::with_kind(kind, v))
+                    .collect(),
+            ),
+        };
         Field {
-            name: name.to_owned(),
-            value: Value::MultipleAlphanumeric(value.into_iter().map(Into::into).collect()),
+            value,
+            definition,
+            offset,
         }
     }
 
-    pub fn from_numeric(name: &str, value: String) -> Self {
+    pub fn nested(definition: FieldDefinition, offset: u64, value: Vec<Vec<Vec<u8>>>) -> Self {
+        let value = match definition.kind {
+            FieldKind::BcsN => Value::NestedNumeric(
+                value
+                    .into_iter()
+                    .map(|v| v.into_iter().map(Into::into).collect())
+                    .collect(),
+            ),
+            kind => Value::NestedAlphaNumeric(
+                value
+                    .into_iter()
+                    .map(|v| {
+                        v.into_iter()
+                            .map(|v| AlphanumericValue::with_kind(kind, v))
+                            .collect()
+                    })
+                    .collect(),
+            ),
+        };
         Field {
-            name: name.to_owned(),
-            value: Value::SingleNumeric(value.into()),
+            value,
+            definition,
+            offset,
         }
     }
 
-    pub fn from_multiple_numeric(name: &str, value: Vec<String>) -> Self {
-        Field {
-            name: name.to_owned(),
-            value: Value::MultipleNumeric(value.into_iter().map(Into::into).collect()),
+    /// A field holding the default value of its definition: zeros for BCS-N and binary fields,
+    /// spaces otherwise. Fields of variable length are left empty.
+    pub fn blank(definition: FieldDefinition) -> Self {
+        let filler = match definition.kind {
+            FieldKind::BcsN => b'0',
+            FieldKind::Binary => 0,
+            FieldKind::BcsA | FieldKind::EcsA => b' ',
+        };
+        Field::single(definition, 0, vec![filler; definition.length.unwrap_or(0)])
+    }
+
+    /// A conditional field that is not present
+    pub fn absent(definition: FieldDefinition) -> Self {
+        Field::single(definition, 0, Vec::new())
+    }
+
+    pub fn definition(&self) -> &FieldDefinition {
+        &self.definition
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.definition.name
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.definition.mnemonic
+    }
+
+    /// The length in bytes of a single value of this field
+    pub fn length(&self) -> usize {
+        self.definition.length.unwrap_or_else(|| self.bytes().len())
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        self.definition.kind
+    }
+
+    /// The offset from the start of the file at which this field was read
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The human-readable meaning of the value of a coded field, e.g. "JPEG 2000" for IC C8
+    pub fn description(&self) -> Option<&'static str> {
+        self.value
+            .as_single_alphanumeric()
+            .and_then(|value| describe(self.definition.mnemonic, &value.value))
+    }
+
+    /// Parses the value of a single-valued field into a typed value, e.g. a number or a code
+    pub fn parse_value<T: FromStr>(&self) -> Result<T, NsifError> {
+        let value = match &self.value {
+            Value::SingleAlphanumeric(value) => &value.value,
+            Value::SingleNumeric(value) => &value.value,
+            _ => return Err(self.invalid_value(String::new())),
+        };
+        self.parse_str(value)
+    }
+
+    /// Parses every value of a multi-valued field, e.g. the LIn lengths of the file header
+    pub fn parse_values<T: FromStr>(&self) -> Result<Vec<T>, NsifError> {
+        match &self.value {
+            Value::MultipleAlphanumeric(values) => {
+                values.iter().map(|v| self.parse_str(&v.value)).collect()
+            }
+            Value::MultipleNumeric(values) => {
+                values.iter().map(|v| self.parse_str(&v.value)).collect()
+            }
+            _ => Err(self.invalid_value(String::new())),
         }
     }
 
-    pub fn from_nested_numeric(name: &str, value: Vec<Vec<String>>) -> Self {
-        Field {
-            name: name.to_owned(),
-            value: Value::NestedNumeric(
-                value
-                    .into_iter()
-                    .map(|v| v.into_iter().map(Into::into).collect())
+    fn parse_str<T: FromStr>(&self, value: &str) -> Result<T, NsifError> {
+        value
+            .trim_end()
+            .parse()
+            .map_err(|_| self.invalid_value(value.to_owned()))
+    }
+
+    fn invalid_value(&self, value: String) -> NsifError {
+        NsifError::InvalidFieldValue {
+            mnemonic: self.definition.mnemonic,
+            value,
+        }
+    }
+
+    /// Replaces the value of a single-valued field after validating it against the definition.
+    /// BCS-A and ECS-A values are padded with trailing spaces, BCS-N integers with leading zeros.
+    pub fn set(&mut self, value: &str) -> Result<(), NsifError> {
+        self.set_bytes(&encode_string_to_bytes(value)?)
+    }
+
+    /// Like [`Field::set`], but takes the raw bytes, e.g. for binary fields
+    pub fn set_bytes(&mut self, bytes: &[u8]) -> Result<(), NsifError> {
+        let FieldDefinition {
+            mnemonic,
+            length,
+            kind,
+            range,
+            ..
+        } = self.definition;
+        if !matches!(
+            self.value,
+            Value::SingleAlphanumeric(_) | Value::SingleNumeric(_)
+        ) {
+            return Err(NsifError::MultiValuedField(mnemonic));
+        }
+        let valid = match kind {
+            FieldKind::BcsA => is_bcs(bytes),
+            FieldKind::BcsN => is_bcs_n(bytes),
+            FieldKind::EcsA => is_ecs(bytes),
+            FieldKind::Binary => true,
+        };
+        if !valid {
+            return Err(NsifError::InvalidCharacterSet { mnemonic, kind });
+        }
+
+        let mut bytes = bytes.to_vec();
+        if let Some(length) = length {
+            let is_integer = !bytes.is_empty() && bytes.iter().all(u8::is_ascii_digit);
+            match kind {
+                _ if bytes.len() > length => {}
+                FieldKind::BcsA | FieldKind::EcsA => bytes.resize(length, b' '),
+                FieldKind::BcsN if is_integer => {
+                    bytes.splice(0..0, std::iter::repeat_n(b'0', length - bytes.len()));
+                }
+                _ => {}
+            }
+            if bytes.len() != length {
+                return Err(NsifError::InvalidFieldLength {
+                    mnemonic,
+                    length,
+                    actual: bytes.len(),
+                });
+            }
+        }
+        if let Some((min, max)) = range {
+            let value = parse_string_from_bytes(&bytes)
+                .parse::<u64>()
+                .map_err(|_| self.invalid_value(parse_string_from_bytes(&bytes)))?;
+            if !(min..=max).contains(&value) {
+                return Err(NsifError::ValueOutOfRange {
+                    mnemonic,
+                    value,
+                    min,
+                    max,
+                });
+            }
+        }
+
+        *self = Field::single(self.definition, self.offset, bytes);
+        Ok(())
+    }
+
+    /// Adds or removes a conditional field. A field that becomes present is filled with spaces
+    /// until it is set, one that becomes absent loses its value.
+    pub(crate) fn set_present(&mut self, present: bool) {
+        let bytes = match (present, self.bytes().is_empty()) {
+            (true, true) => vec![b' '; self.definition.length.unwrap_or(0)],
+            (false, false) => Vec::new(),
+            _ => return,
+        };
+        *self = Field::single(self.definition, self.offset, bytes);
+    }
+
+    /// Replaces every value with the filler of [`Field::blank`], keeping its length
+    pub(crate) fn blank_values(&mut self) {
+        let filler = match self.definition.kind {
+            FieldKind::BcsN => b'0',
+            FieldKind::Binary => 0,
+            FieldKind::BcsA | FieldKind::EcsA => b' ',
+        };
+        let blank = |bytes: &Vec<u8>| vec![filler; bytes.len()];
+        *self = match &self.value {
+            Value::SingleAlphanumeric(value) => {
+                Field::single(self.definition, self.offset, blank(&value.bytes))
+            }
+            Value::SingleNumeric(value) => {
+                Field::single(self.definition, self.offset, blank(&value.bytes))
+            }
+            Value::MultipleAlphanumeric(_) | Value::MultipleNumeric(_) => Field::multiple(
+                self.definition,
+                self.offset,
+                self.value_bytes().iter().map(blank).collect(),
+            ),
+            Value::NestedAlphaNumeric(values) => Field::nested(
+                self.definition,
+                self.offset,
+                values
+                    .iter()
+                    .map(|values| values.iter().map(|value| blank(&value.bytes)).collect())
                     .collect(),
             ),
+            Value::NestedNumeric(values) => Field::nested(
+                self.definition,
+                self.offset,
+                values
+                    .iter()
+                    .map(|values| values.iter().map(|value| blank(&value.bytes)).collect())
+                    .collect(),
+            ),
+        };
+    }
+
+    /// Removes every value of a multi-valued or variable-length field. Fields of fixed
+    /// length are blanked instead. Returns whether the values were removed.
+    pub(crate) fn clear(&mut self) -> bool {
+        *self = match &self.value {
+            Value::SingleAlphanumeric(_) | Value::SingleNumeric(_)
+                if self.definition.length.is_some() =>
+            {
+                self.blank_values();
+                return false;
+            }
+            Value::SingleAlphanumeric(_) | Value::SingleNumeric(_) => {
+                Field::single(self.definition, self.offset, Vec::new())
+            }
+            Value::MultipleAlphanumeric(_) | Value::MultipleNumeric(_) => {
+                Field::multiple(self.definition, self.offset, Vec::new())
+            }
+            Value::NestedAlphaNumeric(_) | Value::NestedNumeric(_) => {
+                Field::nested(self.definition, self.offset, Vec::new())
+            }
+        };
+        true
+    }
+
+    /// Replaces the value of a single-valued BCS-N field, zero-padded to the field length
+    pub(crate) fn set_number(&mut self, value: u64) -> Result<(), NsifError> {
+        let bytes = self.format_number(value)?;
+        self.value = Value::SingleNumeric(bytes.into());
+        Ok(())
+    }
+
+    /// Replaces the values of a multi-valued BCS-N field such as the LIn lengths
+    pub(crate) fn set_numbers(&mut self, values: &[u64]) -> Result<(), NsifError> {
+        let values = values
+            .iter()
+            .map(|&value| self.format_number(value).map(Into::into))
+            .collect::<Result<_, _>>()?;
+        self.value = Value::MultipleNumeric(values);
+        Ok(())
+    }
+
+    fn format_number(&self, value: u64) -> Result<Vec<u8>, NsifError> {
+        let length = self.definition.length.unwrap_or(0);
+        let formatted = format!("{:0length$}", value);
+        if formatted.len() > length {
+            return Err(self.invalid_value(formatted));
+        }
+        Ok(formatted.into_bytes())
+    }
+
+    /// The bytes of each value of a multi-valued field. For nested values,
+    /// all inner values belonging to the same outer value are concatenated.
+    pub fn value_bytes(&self) -> Vec<Vec<u8>> {
+        match &self.value {
+            Value::SingleAlphanumeric(value) => vec![value.bytes.clone()],
+            Value::SingleNumeric(value) => vec![value.bytes.clone()],
+            Value::MultipleAlphanumeric(values) => values.iter().map(|v| v.bytes.clone()).collect(),
+            Value::MultipleNumeric(values) => values.iter().map(|v| v.bytes.clone()).collect(),
+            Value::NestedAlphaNumeric(values) => values
+                .iter()
+                .map(|v| v.iter().flat_map(|v| v.bytes.clone()).collect())
+                .collect(),
+            Value::NestedNumeric(values) => values
+                .iter()
+                .map(|v| v.iter().flat_map(|v| v.bytes.clone()).collect())
+                .collect(),
         }
     }
+
+    /// The bytes of all values of this field in file order
+    pub fn bytes(&self) -> Vec<u8> {
+        self.value_bytes().concat()
+    }
+}
+
+/// Writes the values of a group of multi-valued fields in the interleaved order they have
+/// in the file, the inverse of [`FieldReader::read_interleaved`]
+pub(crate) fn write_interleaved(bytes: &mut Vec<u8>, fields: &[&Field]) {
+    let values = fields
+        .iter()
+        .map(|field| field.value_bytes())
+        .collect::<Vec<_>>();
+    let count = values.iter().map(Vec::len).max().unwrap_or(0);
+    for i in 0..count {
+        for field_values in &values {
+            if let Some(value) = field_values.get(i) {
+                bytes.extend_from_slice(value);
+            }
+        }
+    }
+}
+
+/// Reads fields one after another according to their definitions,
+/// recording the offset at which each field starts
+pub(crate) struct FieldReader<'a, R> {
+    reader: &'a mut R,
+}
+
+impl<'a, R: Read + Seek> FieldReader<'a, R> {
+    pub fn new(reader: &'a mut R) -> Self {
+        FieldReader { reader }
+    }
+
+    pub fn position(&mut self) -> io::Result<u64> {
+        self.reader.stream_position()
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; length];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads the bytes of a single value without wrapping them in a field
+    pub fn read_value(&mut self, definition: FieldDefinition) -> io::Result<Vec<u8>> {
+        self.read_bytes(definition.length.unwrap_or(0))
+    }
+
+    pub fn read(&mut self, definition: FieldDefinition) -> io::Result<Field> {
+        self.read_with_length(definition, definition.length.unwrap_or(0))
+    }
+
+    /// Reads a conditional field, which is left empty if it is not present in the file
+    pub fn read_if(&mut self, definition: FieldDefinition, present: bool) -> io::Result<Field> {
+        let length = if present {
+            definition.length.unwrap_or(0)
+        } else {
+            0
+        };
+        self.read_with_length(definition, length)
+    }
+
+    pub fn read_with_length(
+        &mut self,
+        definition: FieldDefinition,
+        length: usize,
+    ) -> io::Result<Field> {
+        let offset = self.position()?;
+        let bytes = self.read_bytes(length)?;
+        Ok(Field::single(definition, offset, bytes))
+    }
+
+    /// Reads `count` repetitions of a group of fields, such as the LISHn/LIn pairs
+    /// of the file header, into one multi-valued field per definition
+    pub fn read_interleaved<const N: usize>(
+        &mut self,
+        definitions: [FieldDefinition; N],
+        count: usize,
+    ) -> io::Result<[Field; N]> {
+        let mut offset = self.position()?;
+        let mut values: [Vec<Vec<u8>>; N] = std::array::from_fn(|_| Vec::with_capacity(count));
+        for _ in 0..count {
+            for (definition, values) in definitions.iter().zip(values.iter_mut()) {
+                values.push(self.read_value(*definition)?);
+            }
+        }
+        Ok(std::array::from_fn(|i| {
+            let field = Field::multiple(definitions[i], offset, std::mem::take(&mut values[i]));
+            offset += definitions[i].length.unwrap_or(0) as u64;
+            field
+        }))
+    }
 }
 
 impl Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.value {
-            Value::SingleAlphanumeric(AlphanumericValue { value }) => {
-                write!(f, "{}: {}", self.name, value)
+            Value::SingleAlphanumeric(AlphanumericValue { value, .. }) => {
+                match self.description() {
+                    Some(description) => write!(
+                        f,
+                        "{}: {} ({})",
+                        self.definition.name,
+                        value.trim_end(),
+                        description
+                    ),
+                    None => write!(f, "{}: {}", self.definition.name, value),
+                }
             }
-            Value::SingleNumeric(NumericValue { value }) => {
-                write!(f, "{}: {}", self.name, value)
+            Value::SingleNumeric(NumericValue { value, .. }) => {
+                write!(f, "{}: {}", self.definition.name, value)
             }
             Value::MultipleAlphanumeric(values) => {
                 for value in values {
-                    write!(f, "    {}: {}", self.name, value.value)?;
+                    write!(f, "    {}: {}", self.definition.name, value.value)?;
                 }
                 Ok(())
             }
             Value::MultipleNumeric(values) => {
                 for value in values {
-                    write!(f, "    {}: {}", self.name, value.value)?;
+                    write!(f, "    {}: {}", self.definition.name, value.value)?;
                 }
                 Ok(())
             }
@@ -121,7 +590,7 @@ impl Display for Field {
                     for inner_value in outer_value {
                         outer_s.push_str(&inner_value.value);
                     }
-                    write!(f, "    {}: {}", self.name, outer_s)?;
+                    write!(f, "    {}: {}", self.definition.name, outer_s)?;
                 }
                 Ok(())
             }
@@ -131,7 +600,7 @@ impl Display for Field {
                     for inner_value in outer_value {
                         outer_s.push_str(&inner_value.value);
                     }
-                    write!(f, "    {}: {}", self.name, outer_s)?;
+                    write!(f, "    {}: {}", self.definition.name, outer_s)?;
                 }
                 Ok(())
             }
@@ -143,23 +612,19 @@ impl IsEmpty for Value {
     fn is_empty(&self) -> bool {
         match self {
             Value::SingleAlphanumeric(value) => is_empty_or_null(value.value.as_str()),
-            Value::MultipleAlphanumeric(values) => values
-                .into_iter()
-                .all(|a| is_empty_or_null(a.value.as_str())),
+            Value::MultipleAlphanumeric(values) => {
+                values.iter().all(|a| is_empty_or_null(a.value.as_str()))
+            }
             Value::SingleNumeric(value) => is_empty_or_null(value.value.as_str()),
-            Value::MultipleNumeric(values) => values
-                .into_iter()
-                .all(|a| is_empty_or_null(a.value.as_str())),
-            Value::NestedNumeric(outer_values) => outer_values.into_iter().all(|values| {
-                values
-                    .into_iter()
-                    .all(|a| is_empty_or_null(a.value.as_str()))
-            }),
-            Value::NestedAlphaNumeric(outer_values) => outer_values.into_iter().all(|values| {
-                values
-                    .into_iter()
-                    .all(|a| is_empty_or_null(a.value.as_str()))
-            }),
+            Value::MultipleNumeric(values) => {
+                values.iter().all(|a| is_empty_or_null(a.value.as_str()))
+            }
+            Value::NestedNumeric(outer_values) => outer_values
+                .iter()
+                .all(|values| values.iter().all(|a| is_empty_or_null(a.value.as_str()))),
+            Value::NestedAlphaNumeric(outer_values) => outer_values
+                .iter()
+                .all(|values| values.iter().all(|a| is_empty_or_null(a.value.as_str()))),
         }
     }
 }
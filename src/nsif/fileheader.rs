@@ -1,15 +1,16 @@
+use super::codes::Classification;
+use super::datetime::DateTime;
+use super::definitions::file_header::*;
 use super::error::NsifError;
-use super::{
-    parse_number_from_bytes, parse_string_from_bytes, parse_unsigned_integers_from_byte,
-    PrettyPrint,
-};
-use crate::nsif::field::Field;
+use super::security::SecurityMetadata;
+use super::tre::{parse_tres, Tre};
+use super::{parse_number_from_bytes, HeaderFields, PrettyPrint};
+use crate::nsif::field::{write_interleaved, Field, FieldDefinition, FieldReader};
 use bevy_reflect::Reflect;
 use std::cmp::max;
-use std::vec;
-use std::{fs::File, io::Read};
+use std::io::{Read, Seek};
 
-#[derive(Debug, Reflect)]
+#[derive(Debug, Clone, Reflect)]
 pub struct FileHeader {
     pub fhdr: Field,
     pub fver: Field,
@@ -66,367 +67,502 @@ pub struct FileHeader {
     pub xhd: Field,
 }
 
+/// The standard a file conforms to, given by FHDR and FVER
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display)]
+pub enum Profile {
+    #[default]
+    #[strum(serialize = "NSIF 1.0")]
+    Nsif10,
+    #[strum(serialize = "NITF 2.1")]
+    Nitf21,
+}
+
+impl Profile {
+    pub fn fhdr(&self) -> &'static str {
+        match self {
+            Profile::Nsif10 => "NSIF",
+            Profile::Nitf21 => "NITF",
+        }
+    }
+
+    pub fn fver(&self) -> &'static str {
+        match self {
+            Profile::Nsif10 => "01.00",
+            Profile::Nitf21 => "02.10",
+        }
+    }
+}
+
 impl FileHeader {
-    pub fn parse(mut file: &File) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut fhdr = vec![0; 4];
-        let mut fver = vec![0; 5];
-        let mut clevel = vec![0; 2];
-        let mut stype = vec![0; 4];
-        let mut ostaid = vec![0; 10];
-        let mut fdt = vec![0; 14];
-        let mut ftitle = vec![0; 80];
-        let mut fsclas = vec![0; 1];
-        let mut fsclsy = vec![0; 2];
-        let mut fscode = vec![0; 11];
-        let mut fsctlh = vec![0; 2];
-        let mut fsrel = vec![0; 20];
-        let mut fsdctp = vec![0; 2];
-        let mut fsdcdt = vec![0; 8];
-        let mut fsdcxm = vec![0; 4];
-        let mut fsdg = vec![0; 1];
-        let mut fsdgdt = vec![0; 8];
-        let mut fscltx = vec![0; 43];
-        let mut fscatp = vec![0; 1];
-        let mut fscaut = vec![0; 40];
-        let mut fscrsn = vec![0; 1];
-        let mut fssrdt = vec![0; 8];
-        let mut fsctln = vec![0; 15];
-        let mut fscop = vec![0; 5];
-        let mut fscpys = vec![0; 5];
-        let mut encryp = vec![0; 1];
-        let mut fbkgc = vec![0; 3];
-        let mut oname = vec![0; 24];
-        let mut ophone = vec![0; 18];
-        let mut fl = vec![0; 12];
-        let mut hl = vec![0; 6];
-        let mut numi = vec![0; 3];
-        let mut lishs = Vec::new();
-        let mut lis = Vec::new();
-        let mut nums = vec![0; 3];
-        let mut lsshs = Vec::new();
-        let mut lss = Vec::new();
-        let mut numx = vec![0; 3];
-        let mut numt = vec![0; 3];
-        let mut ltshs = Vec::new();
-        let mut lts = Vec::new();
-        let mut numdes = vec![0; 3];
-        let mut ldshs = Vec::new();
-        let mut lds = Vec::new();
-        let mut numres = vec![0; 3];
-        let mut lreshs = Vec::new();
-        let mut lres = Vec::new();
-        let mut udhdl = vec![0; 5];
-        let mut udhofl = vec![0; 3];
-        // udhd is dynamically sized
-        let mut xhdl = vec![0; 5];
-        let mut xhdlofl = vec![0; 3];
-        // xhd is dynamically sized
-
-        file.read_exact(&mut fhdr)?;
-        let fhdr_value = parse_string_from_bytes(&fhdr)?;
-        if !matches!(fhdr_value.as_str(), "NITF" | "NSIF") {
-            return Err(Box::new(NsifError::FileMismatch));
+    /// A file header for a file without segments, dated now and unclassified.
+    /// The length fields are filled in when the file is written.
+    pub fn new(profile: Profile) -> Self {
+        let value = |definition: FieldDefinition, value: &str| {
+            let length = definition.length.unwrap_or(0);
+            Field::single(definition, 0, format!("{value:<length$}").into())
+        };
+        let table = |definition| Field::multiple(definition, 0, Vec::new());
+        FileHeader {
+            fhdr: value(FHDR, profile.fhdr()),
+            fver: value(FVER, profile.fver()),
+            clevel: value(CLEVEL, "03"),
+            stype: value(STYPE, "BF01"),
+            ostaid: Field::blank(OSTAID),
+            fdt: value(FDT, &DateTime::now().to_nsif_string()),
+            ftitle: Field::blank(FTITLE),
+            fsclas: value(FSCLAS, "U"),
+            fsclsy: Field::blank(FSCLSY),
+            fscode: Field::blank(FSCODE),
+            fsctlh: Field::blank(FSCTLH),
+            fsrel: Field::blank(FSREL),
+            fsdctp: Field::blank(FSDCTP),
+            fsdcdt: Field::blank(FSDCDT),
+            fsdcxm: Field::blank(FSDCXM),
+            fsdg: Field::blank(FSDG),
+            fsdgdt: Field::blank(FSDGDT),
+            fscltx: Field::blank(FSCLTX),
+            fscatp: Field::blank(FSCATP),
+            fscaut: Field::blank(FSCAUT),
+            fscrsn: Field::blank(FSCRSN),
+            fssrdt: Field::blank(FSSRDT),
+            fsctln: Field::blank(FSCTLN),
+            fscop: Field::blank(FSCOP),
+            fscpys: Field::blank(FSCPYS),
+            encryp: Field::blank(ENCRYP),
+            fbkgc: Field::blank(FBKGC),
+            oname: Field::blank(ONAME),
+            ophone: Field::blank(OPHONE),
+            fl: Field::blank(FL),
+            hl: Field::blank(HL),
+            numi: Field::blank(NUMI),
+            lishs: table(LISH),
+            lis: table(LI),
+            nums: Field::blank(NUMS),
+            lsshs: table(LSSH),
+            lss: table(LS),
+            numx: Field::blank(NUMX),
+            numt: Field::blank(NUMT),
+            ltshs: table(LTSH),
+            lts: table(LT),
+            numdes: Field::blank(NUMDES),
+            ldshs: table(LDSH),
+            lds: table(LD),
+            numres: Field::blank(NUMRES),
+            lreshs: table(LRESH),
+            lres: table(LRE),
+            udhdl: Field::blank(UDHDL),
+            udhofl: Field::absent(UDHOFL),
+            udhd: Field::absent(UDHD),
+            xhdl: Field::blank(XHDL),
+            xhdlofl: Field::absent(XHDLOFL),
+            xhd: Field::absent(XHD),
         }
+    }
 
-        file.read_exact(&mut fver)?;
-        file.read_exact(&mut clevel)?;
-        file.read_exact(&mut stype)?;
-        file.read_exact(&mut ostaid)?;
-        file.read_exact(&mut fdt)?;
-        file.read_exact(&mut ftitle)?;
-        file.read_exact(&mut fsclas)?;
-        file.read_exact(&mut fsclsy)?;
-        file.read_exact(&mut fscode)?;
-        file.read_exact(&mut fsctlh)?;
-        file.read_exact(&mut fsrel)?;
-        file.read_exact(&mut fsdctp)?;
-        file.read_exact(&mut fsdcdt)?;
-        file.read_exact(&mut fsdcxm)?;
-        file.read_exact(&mut fsdg)?;
-        file.read_exact(&mut fsdgdt)?;
-        file.read_exact(&mut fscltx)?;
-        file.read_exact(&mut fscatp)?;
-        file.read_exact(&mut fscaut)?;
-        file.read_exact(&mut fscrsn)?;
-        file.read_exact(&mut fssrdt)?;
-        file.read_exact(&mut fsctln)?;
-        file.read_exact(&mut fscop)?;
-        file.read_exact(&mut fscpys)?;
-        file.read_exact(&mut encryp)?;
-        file.read_exact(&mut fbkgc)?;
-        file.read_exact(&mut oname)?;
-        file.read_exact(&mut ophone)?;
-        file.read_exact(&mut fl)?;
-        file.read_exact(&mut hl)?;
-
-        file.read_exact(&mut numi)?;
-        let number_of_image_segments = parse_number_from_bytes(&numi).unwrap_or(0);
-
-        for _ in 0..number_of_image_segments {
-            let mut lish = vec![0; 6];
-            let mut li = vec![0; 10];
-            file.read_exact(&mut lish)?;
-            file.read_exact(&mut li)?;
-            lishs.push(lish);
-            lis.push(li);
+    /// Parses the file header and checks the number of bytes consumed against HL.
+    pub fn parse<R: Read + Seek>(file: &mut R) -> Result<Self, Box<dyn std::error::Error>> {
+        let start = file.stream_position()?;
+        let file_header = Self::parse_unchecked(file)?;
+        let header_length = file_header.header_length()?;
+        let consumed = file.stream_position()? - start;
+        if consumed != header_length {
+            return Err(Box::new(NsifError::LengthMismatch {
+                field: String::from("HL"),
+                declared: header_length,
+                consumed,
+            }));
         }
+        Ok(file_header)
+    }
 
-        file.read_exact(&mut nums)?;
-        let number_of_graphic_segments = parse_number_from_bytes(&nums).unwrap_or(0);
+    /// Parses the file header without checking HL, leaving the reader after its last field
+    pub(crate) fn parse_unchecked<R: Read + Seek>(
+        file: &mut R,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = FieldReader::new(file);
 
-        for _ in 0..number_of_graphic_segments {
-            let mut lssh = vec![0; 4];
-            let mut ls = vec![0; 6];
-            file.read_exact(&mut lssh)?;
-            file.read_exact(&mut ls)?;
-            lsshs.push(lssh);
-            lss.push(ls);
+        let fhdr = reader.read(FHDR)?;
+        if !matches!(fhdr.bytes().as_slice(), b"NITF" | b"NSIF") {
+            return Err(Box::new(NsifError::FileMismatch));
         }
 
-        file.read_exact(&mut numx)?;
+        let fver = reader.read(FVER)?;
+        let clevel = reader.read(CLEVEL)?;
+        let stype = reader.read(STYPE)?;
+        let ostaid = reader.read(OSTAID)?;
+        let fdt = reader.read(FDT)?;
+        let ftitle = reader.read(FTITLE)?;
+        let fsclas = reader.read(FSCLAS)?;
+        let fsclsy = reader.read(FSCLSY)?;
+        let fscode = reader.read(FSCODE)?;
+        let fsctlh = reader.read(FSCTLH)?;
+        let fsrel = reader.read(FSREL)?;
+        let fsdctp = reader.read(FSDCTP)?;
+        let fsdcdt = reader.read(FSDCDT)?;
+        let fsdcxm = reader.read(FSDCXM)?;
+        let fsdg = reader.read(FSDG)?;
+        let fsdgdt = reader.read(FSDGDT)?;
+        let fscltx = reader.read(FSCLTX)?;
+        let fscatp = reader.read(FSCATP)?;
+        let fscaut = reader.read(FSCAUT)?;
+        let fscrsn = reader.read(FSCRSN)?;
+        let fssrdt = reader.read(FSSRDT)?;
+        let fsctln = reader.read(FSCTLN)?;
+        let fscop = reader.read(FSCOP)?;
+        let fscpys = reader.read(FSCPYS)?;
+        let encryp = reader.read(ENCRYP)?;
+        let fbkgc = reader.read(FBKGC)?;
+        let oname = reader.read(ONAME)?;
+        let ophone = reader.read(OPHONE)?;
+        let fl = reader.read(FL)?;
+        let hl = reader.read(HL)?;
 
-        file.read_exact(&mut numt)?;
-        let number_of_text_segments = parse_number_from_bytes(&numt).unwrap_or(0);
+        let numi = reader.read(NUMI)?;
+        let number_of_image_segments = parse_number_from_bytes(&numi.bytes()).unwrap_or(0);
+        let [lishs, lis] =
+            reader.read_interleaved([LISH, LI], number_of_image_segments as usize)?;
 
-        for _ in 0..number_of_text_segments {
-            let mut ltsh = vec![0; 4];
-            let mut lt = vec![0; 5];
-            file.read_exact(&mut ltsh)?;
-            file.read_exact(&mut lt)?;
-            ltshs.push(ltsh);
-            lts.push(lt);
-        }
+        let nums = reader.read(NUMS)?;
+        let number_of_graphic_segments = parse_number_from_bytes(&nums.bytes()).unwrap_or(0);
+        let [lsshs, lss] =
+            reader.read_interleaved([LSSH, LS], number_of_graphic_segments as usize)?;
 
-        file.read_exact(&mut numdes)?;
-        let number_of_data_extension_segments = parse_number_from_bytes(&numdes).unwrap_or(0);
+        let numx = reader.read(NUMX)?;
 
-        for _ in 0..number_of_data_extension_segments {
-            let mut ldsh = vec![0; 4];
-            let mut ld = vec![0; 9];
-            file.read_exact(&mut ldsh)?;
-            file.read_exact(&mut ld)?;
-            ldshs.push(ldsh);
-            lds.push(ld);
-        }
+        let numt = reader.read(NUMT)?;
+        let number_of_text_segments = parse_number_from_bytes(&numt.bytes()).unwrap_or(0);
+        let [ltshs, lts] = reader.read_interleaved([LTSH, LT], number_of_text_segments as usize)?;
 
-        file.read_exact(&mut numres)?;
-        let number_of_reserved_extension_segments = parse_number_from_bytes(&numres).unwrap_or(0);
+        let numdes = reader.read(NUMDES)?;
+        let number_of_data_extension_segments =
+            parse_number_from_bytes(&numdes.bytes()).unwrap_or(0);
+        let [ldshs, lds] =
+            reader.read_interleaved([LDSH, LD], number_of_data_extension_segments as usize)?;
 
-        for _ in 0..number_of_reserved_extension_segments {
-            let mut lresh = vec![0; 4];
-            let mut lre = vec![0; 7];
-            file.read_exact(&mut lresh)?;
-            file.read_exact(&mut lre)?;
-            lreshs.push(lresh);
-            lres.push(lre);
-        }
+        let numres = reader.read(NUMRES)?;
+        let number_of_reserved_extension_segments =
+            parse_number_from_bytes(&numres.bytes()).unwrap_or(0);
+        let [lreshs, lres] = reader
+            .read_interleaved([LRESH, LRE], number_of_reserved_extension_segments as usize)?;
 
-        file.read_exact(&mut udhdl)?;
-        let udhd_length = max(parse_number_from_bytes(&udhdl).unwrap_or(3) - 3, 0);
+        let udhdl = reader.read(UDHDL)?;
+        let udhdl_value = parse_number_from_bytes(&udhdl.bytes()).unwrap_or(0);
+        let udhofl = reader.read_if(UDHOFL, udhdl_value != 0)?;
+        let udhd = reader.read_with_length(UDHD, max(udhdl_value - 3, 0) as usize)?;
 
-        if udhd_length != 0 {
-            file.read_exact(&mut udhofl)?;
+        let xhdl = reader.read(XHDL)?;
+        let xhdl_value = parse_number_from_bytes(&xhdl.bytes()).unwrap_or(0);
+        let xhdlofl = reader.read_if(XHDLOFL, xhdl_value != 0)?;
+        let xhd = reader.read_with_length(XHD, max(xhdl_value - 3, 0) as usize)?;
+
+        Ok(FileHeader {
+            fhdr,
+            fver,
+            clevel,
+            stype,
+            ostaid,
+            fdt,
+            ftitle,
+            fsclas,
+            fsclsy,
+            fscode,
+            fsctlh,
+            fsrel,
+            fsdctp,
+            fsdcdt,
+            fsdcxm,
+            fsdg,
+            fsdgdt,
+            fscltx,
+            fscatp,
+            fscaut,
+            fscrsn,
+            fssrdt,
+            fsctln,
+            fscop,
+            fscpys,
+            encryp,
+            fbkgc,
+            oname,
+            ophone,
+            fl,
+            hl,
+            numi,
+            lishs,
+            lis,
+            nums,
+            lsshs,
+            lss,
+            numx,
+            numt,
+            ltshs,
+            lts,
+            numdes,
+            ldshs,
+            lds,
+            numres,
+            lreshs,
+            lres,
+            udhdl,
+            udhofl,
+            udhd,
+            xhdl,
+            xhdlofl,
+            xhd,
+        })
+    }
+
+    pub fn profile(&self) -> Result<Profile, NsifError> {
+        match (self.fhdr.bytes().as_slice(), self.fver.bytes().as_slice()) {
+            (b"NSIF", b"01.00") => Ok(Profile::Nsif10),
+            (b"NITF", b"02.10") => Ok(Profile::Nitf21),
+            _ => Err(NsifError::FileMismatch),
         }
+    }
+
+    pub fn complexity_level(&self) -> Result<u8, NsifError> {
+        self.clevel.parse_value()
+    }
+
+    pub fn file_datetime(&self) -> Result<DateTime, NsifError> {
+        self.fdt.parse_value()
+    }
+
+    pub fn classification(&self) -> Result<Classification, NsifError> {
+        self.fsclas.parse_value()
+    }
+
+    /// The TREs of the user-defined header data followed by those of the extended header data
+    pub fn tres(&self) -> Result<Vec<Tre>, NsifError> {
+        Ok([parse_tres(&self.udhd)?, parse_tres(&self.xhd)?].concat())
+    }
+
+    pub fn security(&self) -> Result<SecurityMetadata, NsifError> {
+        let fields = [
+            &self.fsclas,
+            &self.fsclsy,
+            &self.fscode,
+            &self.fsctlh,
+            &self.fsrel,
+            &self.fsdctp,
+            &self.fsdcdt,
+            &self.fsdcxm,
+            &self.fsdg,
+            &self.fsdgdt,
+            &self.fscltx,
+            &self.fscatp,
+            &self.fscaut,
+            &self.fscrsn,
+            &self.fssrdt,
+            &self.fsctln,
+        ];
+        SecurityMetadata::parse("FS", &fields.map(Field::bytes).concat())
+    }
+
+    pub fn file_length(&self) -> Result<u64, NsifError> {
+        self.fl.parse_value()
+    }
+
+    pub fn header_length(&self) -> Result<u64, NsifError> {
+        self.hl.parse_value()
+    }
+
+    pub fn num_image_segments(&self) -> Result<u16, NsifError> {
+        self.numi.parse_value()
+    }
+
+    pub fn image_subheader_lengths(&self) -> Result<Vec<u64>, NsifError> {
+        self.lishs.parse_values()
+    }
+
+    pub fn image_segment_lengths(&self) -> Result<Vec<u64>, NsifError> {
+        self.lis.parse_values()
+    }
+
+    /// The (LSSHn, LSn) pairs of all graphic segments
+    pub fn graphic_segment_lengths(&self) -> Result<Vec<(u64, u64)>, NsifError> {
+        segment_lengths(&self.lsshs, &self.lss)
+    }
 
-        let mut udhd = vec![0; udhd_length as usize];
-        file.read_exact(&mut udhd)?;
+    /// The (LTSHn, LTn) pairs of all text segments
+    pub fn text_segment_lengths(&self) -> Result<Vec<(u64, u64)>, NsifError> {
+        segment_lengths(&self.ltshs, &self.lts)
+    }
 
-        file.read_exact(&mut xhdl)?;
-        let xhd_length = max(parse_number_from_bytes(&xhdl).unwrap_or(3) - 3, 0);
+    /// The (LDSHn, LDn) pairs of all data extension segments
+    pub fn data_extension_segment_lengths(&self) -> Result<Vec<(u64, u64)>, NsifError> {
+        segment_lengths(&self.ldshs, &self.lds)
+    }
 
-        if xhd_length != 0 {
-            file.read_exact(&mut xhdlofl)?;
+    /// The (LRESHn, LREn) pairs of all reserved extension segments
+    pub fn reserved_extension_segment_lengths(&self) -> Result<Vec<(u64, u64)>, NsifError> {
+        segment_lengths(&self.lreshs, &self.lres)
+    }
+
+    /// Recomputes the segment counts, the segment length tables, UDHDL, XHDL, HL and FL
+    /// from the given (sub header length, data length) pairs of every segment
+    pub(crate) fn update_lengths(
+        &mut self,
+        image_segments: &[(u64, u64)],
+        graphic_segments: &[(u64, u64)],
+        text_segments: &[(u64, u64)],
+        data_extension_segments: &[(u64, u64)],
+        reserved_extension_segments: &[(u64, u64)],
+    ) -> Result<(), NsifError> {
+        set_segment_lengths(
+            &mut self.numi,
+            &mut self.lishs,
+            &mut self.lis,
+            image_segments,
+        )?;
+        set_segment_lengths(
+            &mut self.nums,
+            &mut self.lsshs,
+            &mut self.lss,
+            graphic_segments,
+        )?;
+        set_segment_lengths(
+            &mut self.numt,
+            &mut self.ltshs,
+            &mut self.lts,
+            text_segments,
+        )?;
+        set_segment_lengths(
+            &mut self.numdes,
+            &mut self.ldshs,
+            &mut self.lds,
+            data_extension_segments,
+        )?;
+        set_segment_lengths(
+            &mut self.numres,
+            &mut self.lreshs,
+            &mut self.lres,
+            reserved_extension_segments,
+        )?;
+        set_data_length(&mut self.udhdl, &mut self.udhofl, &self.udhd)?;
+        set_data_length(&mut self.xhdl, &mut self.xhdlofl, &self.xhd)?;
+
+        let header_length = self.to_bytes().len() as u64;
+        self.hl.set_number(header_length)?;
+        let file_length = header_length
+            + [
+                image_segments,
+                graphic_segments,
+                text_segments,
+                data_extension_segments,
+                reserved_extension_segments,
+            ]
+            .iter()
+            .flat_map(|segments| segments.iter())
+            .map(|(subheader_length, data_length)| subheader_length + data_length)
+            .sum::<u64>();
+        self.fl.set_number(file_length)
+    }
+
+    /// Serializes the header the way it is stored in the file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for field in [
+            &self.fhdr,
+            &self.fver,
+            &self.clevel,
+            &self.stype,
+            &self.ostaid,
+            &self.fdt,
+            &self.ftitle,
+            &self.fsclas,
+            &self.fsclsy,
+            &self.fscode,
+            &self.fsctlh,
+            &self.fsrel,
+            &self.fsdctp,
+            &self.fsdcdt,
+            &self.fsdcxm,
+            &self.fsdg,
+            &self.fsdgdt,
+            &self.fscltx,
+            &self.fscatp,
+            &self.fscaut,
+            &self.fscrsn,
+            &self.fssrdt,
+            &self.fsctln,
+            &self.fscop,
+            &self.fscpys,
+            &self.encryp,
+            &self.fbkgc,
+            &self.oname,
+            &self.ophone,
+            &self.fl,
+            &self.hl,
+            &self.numi,
+        ] {
+            bytes.extend(field.bytes());
         }
+        write_interleaved(&mut bytes, &[&self.lishs, &self.lis]);
+        bytes.extend(self.nums.bytes());
+        write_interleaved(&mut bytes, &[&self.lsshs, &self.lss]);
+        bytes.extend(self.numx.bytes());
+        bytes.extend(self.numt.bytes());
+        write_interleaved(&mut bytes, &[&self.ltshs, &self.lts]);
+        bytes.extend(self.numdes.bytes());
+        write_interleaved(&mut bytes, &[&self.ldshs, &self.lds]);
+        bytes.extend(self.numres.bytes());
+        write_interleaved(&mut bytes, &[&self.lreshs, &self.lres]);
+        for field in [
+            &self.udhdl,
+            &self.udhofl,
+            &self.udhd,
+            &self.xhdl,
+            &self.xhdlofl,
+            &self.xhd,
+        ] {
+            bytes.extend(field.bytes());
+        }
+        bytes
+    }
+}
 
-        let mut xhd = vec![0; xhd_length as usize];
-        file.read_exact(&mut xhd)?;
+fn segment_lengths(
+    subheader_lengths: &Field,
+    segment_lengths: &Field,
+) -> Result<Vec<(u64, u64)>, NsifError> {
+    Ok(subheader_lengths
+        .parse_values()?
+        .into_iter()
+        .zip(segment_lengths.parse_values()?)
+        .collect())
+}
 
-        Ok(FileHeader {
-            fhdr: Field::from_alphanumeric("File Profile Name", parse_string_from_bytes(&fhdr)?),
-            fver: Field::from_alphanumeric("File Version", parse_string_from_bytes(&fver)?),
-            clevel: Field::from_numeric("Complexity level", parse_string_from_bytes(&clevel)?),
-            stype: Field::from_alphanumeric("Standard Type", parse_string_from_bytes(&stype)?),
-            ostaid: Field::from_alphanumeric(
-                "Originating Station Identifier",
-                parse_string_from_bytes(&ostaid)?,
-            ),
-            fdt: Field::from_numeric("File Date and Time", parse_string_from_bytes(&fdt)?),
-            ftitle: Field::from_alphanumeric("File Title", parse_string_from_bytes(&ftitle)?),
-            fsclas: Field::from_alphanumeric(
-                "File Security Classification",
-                parse_string_from_bytes(&fsclas)?,
-            ),
-            fsclsy: Field::from_alphanumeric(
-                "File Security Classification System",
-                parse_string_from_bytes(&fsclsy)?,
-            ),
-            fscode: Field::from_alphanumeric("File Codewords", parse_string_from_bytes(&fscode)?),
-            fsctlh: Field::from_alphanumeric(
-                "File Control and Handling",
-                parse_string_from_bytes(&fsctlh)?,
-            ),
-            fsrel: Field::from_alphanumeric(
-                "File Releasing Instructions",
-                parse_string_from_bytes(&fsrel)?,
-            ),
-            fsdctp: Field::from_alphanumeric(
-                "File Declassification Type",
-                parse_string_from_bytes(&fsdctp)?,
-            ),
-            fsdcdt: Field::from_alphanumeric(
-                "File Declassification Date",
-                parse_string_from_bytes(&fsdcdt)?,
-            ),
-            fsdcxm: Field::from_alphanumeric(
-                "File Declassification Exemption",
-                parse_string_from_bytes(&fsdcxm)?,
-            ),
-            fsdg: Field::from_alphanumeric("File Downgrade", parse_string_from_bytes(&fsdg)?),
-            fsdgdt: Field::from_alphanumeric(
-                "File Downgrade Date",
-                parse_string_from_bytes(&fsdgdt)?,
-            ),
-            fscltx: Field::from_alphanumeric(
-                "File Classification Text",
-                parse_string_from_bytes(&fscltx)?,
-            ),
-            fscatp: Field::from_alphanumeric(
-                "File Classification Authority Type",
-                parse_string_from_bytes(&fscatp)?,
-            ),
-            fscaut: Field::from_alphanumeric(
-                "File Classification Authority",
-                parse_string_from_bytes(&fscaut)?,
-            ),
-            fscrsn: Field::from_alphanumeric(
-                "File Classification Reason",
-                parse_string_from_bytes(&fscrsn)?,
-            ),
-            fssrdt: Field::from_alphanumeric(
-                "File Security Source Date",
-                parse_string_from_bytes(&fssrdt)?,
-            ),
-            fsctln: Field::from_alphanumeric(
-                "File Security Control Number",
-                parse_string_from_bytes(&fsctln)?,
-            ),
-            fscop: Field::from_numeric("File Copy Number", parse_string_from_bytes(&fscop)?),
-            fscpys: Field::from_numeric("File Number of Copies", parse_string_from_bytes(&fscpys)?),
-            encryp: Field::from_numeric("Encryption", parse_string_from_bytes(&encryp)?),
-            fbkgc: Field::from_alphanumeric(
-                "File Background Color",
-                parse_unsigned_integers_from_byte(&fbkgc),
-            ),
-            oname: Field::from_alphanumeric("Originator's Name", parse_string_from_bytes(&oname)?),
-            ophone: Field::from_alphanumeric(
-                "Originator's Phone Number",
-                parse_string_from_bytes(&ophone)?,
-            ),
-            fl: Field::from_numeric("File Length", parse_string_from_bytes(&fl)?),
-            hl: Field::from_numeric("NSIF File Header Length", parse_string_from_bytes(&hl)?),
-            numi: Field::from_numeric("Number of Image Segments", parse_string_from_bytes(&numi)?),
-            lishs: Field::from_multiple_numeric(
-                "Length of Image Subheader",
-                lishs
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            lis: Field::from_multiple_numeric(
-                "Length of Image Segment",
-                lis.iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            nums: Field::from_numeric(
-                "Number of Graphic Segments",
-                parse_string_from_bytes(&nums)?,
-            ),
-            lsshs: Field::from_multiple_numeric(
-                "Length of Graphic Subheader",
-                lsshs
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            lss: Field::from_multiple_numeric(
-                "Length of Graphic Segment",
-                lss.iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            numx: Field::from_numeric("Reserved for Future Use", parse_string_from_bytes(&numx)?),
-            numt: Field::from_numeric("Number of Text Segments", parse_string_from_bytes(&numt)?),
-            ltshs: Field::from_multiple_numeric(
-                "Length of Text Subheader",
-                ltshs
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            lts: Field::from_multiple_numeric(
-                "Length of Text Segment",
-                lts.iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            numdes: Field::from_numeric(
-                "Number of Data Extension Segments",
-                parse_string_from_bytes(&numdes)?,
-            ),
-            ldshs: Field::from_multiple_numeric(
-                "Length of Data Extension Segment Subheader",
-                ldshs
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            lds: Field::from_multiple_numeric(
-                "Length of Data Extension Segment",
-                lds.iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            numres: Field::from_numeric(
-                "Number of Reserved Extension Segments",
-                parse_string_from_bytes(&numres)?,
-            ),
-            lreshs: Field::from_multiple_numeric(
-                "Length of Reserved Extension Segment Subheader",
-                lreshs
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            lres: Field::from_multiple_numeric(
-                "Length of Reserved Extension Segment",
-                lres.iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            udhdl: Field::from_numeric(
-                "User-Defined Header Data Length",
-                parse_string_from_bytes(&udhdl)?,
-            ),
-            udhofl: Field::from_numeric(
-                "User-Defined Header Overflow",
-                parse_string_from_bytes(&udhofl)?,
-            ),
-            udhd: Field::from_alphanumeric(
-                "User-Defined Header Data",
-                parse_string_from_bytes(&udhd)?,
-            ),
-            xhdl: Field::from_numeric(
-                "Extended Header Data Length",
-                parse_string_from_bytes(&xhdl)?,
-            ),
-            xhdlofl: Field::from_numeric(
-                "Extended Header Data Overflow",
-                parse_string_from_bytes(&xhdlofl)?,
-            ),
-            xhd: Field::from_alphanumeric("Extended Header Data", parse_string_from_bytes(&xhd)?),
-        })
+fn set_segment_lengths(
+    count: &mut Field,
+    subheader_lengths: &mut Field,
+    segment_lengths: &mut Field,
+    lengths: &[(u64, u64)],
+) -> Result<(), NsifError> {
+    count.set_number(lengths.len() as u64)?;
+    subheader_lengths.set_numbers(&lengths.iter().map(|(s, _)| *s).collect::<Vec<_>>())?;
+    segment_lengths.set_numbers(&lengths.iter().map(|(_, d)| *d).collect::<Vec<_>>())
+}
+
+/// Sets a user-defined or extended data length field, which counts the 3-byte overflow
+/// field along with the data and is 0 only if neither is present
+pub(crate) fn set_data_length(
+    length: &mut Field,
+    overflow: &mut Field,
+    data: &Field,
+) -> Result<(), NsifError> {
+    let data_length = data.bytes().len() as u64;
+    if data_length == 0 && overflow.bytes().is_empty() {
+        return length.set_number(0);
     }
+    if overflow.bytes().is_empty() {
+        overflow.set_number(0)?;
+    }
+    length.set_number(data_length + 3)
 }
 
-impl PrettyPrint for FileHeader {}
\ No newline at end of file
+impl PrettyPrint for FileHeader {}
+
+impl HeaderFields for FileHeader {
+    const COMPUTED_FIELDS: &'static [&'static str] = COMPUTED;
+}
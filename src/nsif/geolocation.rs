@@ -0,0 +1,104 @@
+//! Conversion between image coordinates and the corner coordinates (IGEOLO) of an image
+//! segment. The corners are taken to be the centres of the corner pixels and the image is
+//! assumed to be a projective transformation of the ground, as for a flat area.
+
+use super::error::NsifError;
+use super::imagesegment::ImageSubheader;
+
+/// A projective transformation of the plane, determined by four points and their images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projective {
+    /// The first source point, subtracted from every point to keep the system well conditioned
+    origin: (f64, f64),
+    coefficients: [f64; 8],
+}
+
+impl Projective {
+    /// The transformation taking each of the points in `from` to the one in `to`, or `None` if
+    /// three of the points lie on a line
+    pub fn from_points(from: [(f64, f64); 4], to: [(f64, f64); 4]) -> Option<Self> {
+        let origin = from[0];
+        let mut system = [[0.0; 9]; 8];
+        for (i, (&(x, y), &(u, v))) in from.iter().zip(&to).enumerate() {
+            let (x, y) = (x - origin.0, y - origin.1);
+            system[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u, u];
+            system[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v, v];
+        }
+        Some(Projective {
+            origin,
+            coefficients: solve(system)?,
+        })
+    }
+
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let h = &self.coefficients;
+        let (x, y) = (x - self.origin.0, y - self.origin.1);
+        let w = h[6] * x + h[7] * y + 1.0;
+        (
+            (h[0] * x + h[1] * y + h[2]) / w,
+            (h[3] * x + h[4] * y + h[5]) / w,
+        )
+    }
+}
+
+/// The (row, column) centres of the corner pixels in the order of the corners in IGEOLO
+pub fn corner_pixels(rows: u32, columns: u32) -> [(f64, f64); 4] {
+    let (rows, columns) = (rows as f64, columns as f64);
+    [
+        (0.5, 0.5),
+        (0.5, columns - 0.5),
+        (rows - 0.5, columns - 0.5),
+        (rows - 0.5, 0.5),
+    ]
+}
+
+/// The transformation of (row, column) image coordinates to (latitude, longitude)
+pub fn image_to_ground(sub_header: &ImageSubheader) -> Result<Projective, NsifError> {
+    let corners = sub_header.corners()?;
+    let pixels = corner_pixels(sub_header.num_rows()?, sub_header.num_cols()?);
+    Projective::from_points(pixels, corners).ok_or_else(|| degenerate(&corners))
+}
+
+/// The transformation of (latitude, longitude) to (row, column) image coordinates
+pub fn ground_to_image(sub_header: &ImageSubheader) -> Result<Projective, NsifError> {
+    let corners = sub_header.corners()?;
+    let pixels = corner_pixels(sub_header.num_rows()?, sub_header.num_cols()?);
+    Projective::from_points(corners, pixels).ok_or_else(|| degenerate(&corners))
+}
+
+fn degenerate(corners: &[(f64, f64); 4]) -> NsifError {
+    NsifError::InvalidFieldValue {
+        mnemonic: "IGEOLO",
+        value: corners
+            .iter()
+            .map(|(latitude, longitude)| format!("{latitude},{longitude}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Solves a linear system of eight equations given as augmented rows by Gaussian elimination
+fn solve(mut system: [[f64; 9]; 8]) -> Option<[f64; 8]> {
+    for column in 0..8 {
+        let pivot = (column..8)
+            .max_by(|&a, &b| system[a][column].abs().total_cmp(&system[b][column].abs()))?;
+        if system[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        system.swap(column, pivot);
+        let pivot_row = system[column];
+        for (i, row) in system.iter_mut().enumerate() {
+            if i != column {
+                let factor = row[column] / pivot_row[column];
+                for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    let mut solution = [0.0; 8];
+    for (i, value) in solution.iter_mut().enumerate() {
+        *value = system[i][8] / system[i][i];
+    }
+    Some(solution)
+}
@@ -1,87 +1,561 @@
-use super::{parse_number_from_bytes, parse_string_from_bytes, PrettyPrint};
+use super::chip::Ichipb;
+use super::codes::{
+    Classification, Code, Compression, CoordinateRepresentation, ImageCategory, ImageMode,
+    ImageRepresentation, PixelValueType,
+};
+use super::compression::{
+    encode_jpeg, encode_jpeg2000, extract_block, split_jpeg_streams, CompressionOptions,
+};
+use super::datetime::DateTime;
+use super::definitions::image_subheader::*;
+use super::security::SecurityMetadata;
+use super::tre::{parse_tres, Tre};
+use super::{is_bcs, parse_number_from_bytes, parse_string_from_bytes, HeaderFields, PrettyPrint};
 use crate::nsif::error::NsifError;
-use crate::nsif::field::{Field, Value};
-use crate::nsif::parse_number_from_string;
+use crate::nsif::field::{write_interleaved, Field, FieldDefinition, FieldReader};
+use crate::nsif::fileheader::set_data_length;
 use bevy_reflect::Reflect;
+use image::DynamicImage;
 use jpeg2k::ImagePixelData;
 use std::cmp::max;
-use std::vec;
-use std::{fs::File, io::Read};
-use zune_jpeg::errors::DecodeErrors;
+use std::io::{Read, Seek, SeekFrom};
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
 use zune_jpeg::JpegDecoder;
 
-#[derive(Debug, Reflect)]
+#[derive(Debug, Clone, Reflect)]
 pub struct ImageSegment {
     pub sub_header: ImageSubheader,
     pub data: Vec<u8>,
 }
 impl ImageSegment {
-    pub fn parse(
-        mut file: &File,
-        _subheader_length: i32,
-        segment_length: i32,
+    /// Parses the image segment at `offset`. A LISH that disagrees with the bytes consumed by
+    /// the sub header is added to `diagnostics` and the data is read from offset + LISH.
+    pub fn parse<R: Read + Seek>(
+        reader: &mut R,
+        position: usize,
+        offset: u64,
+        subheader_length: u64,
+        segment_length: u64,
+        diagnostics: &mut Vec<NsifError>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let sub_header = ImageSubheader::parse(file)?;
+        reader.seek(SeekFrom::Start(offset))?;
+        let sub_header = ImageSubheader::parse(reader)?;
+        let consumed = reader.stream_position()? - offset;
+        if consumed != subheader_length {
+            diagnostics.push(NsifError::LengthMismatch {
+                field: format!("LISH{:03}", position),
+                declared: subheader_length,
+                consumed,
+            });
+            reader.seek(SeekFrom::Start(offset + subheader_length))?;
+        }
         let mut data = vec![0; segment_length as usize];
-        file.read_exact(&mut data)?;
+        reader.read_exact(&mut data)?;
         Ok(ImageSegment { sub_header, data })
     }
 
+    /// An uncompressed image segment from 8-bit pixels in band interleaved by pixel order,
+    /// with the IREPBAND of each band in `bands`
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        bands: &[&str],
+        pixels: Vec<u8>,
+    ) -> Result<Self, NsifError> {
+        if width == 0
+            || height == 0
+            || bands.is_empty()
+            || pixels.len() != width as usize * height as usize * bands.len()
+        {
+            return Err(NsifError::InvalidDimensions);
+        }
+        Ok(ImageSegment {
+            sub_header: ImageSubheader::new(height, width, bands)?,
+            data: pixels,
+        })
+    }
+
+    /// An uncompressed RGB or monochrome image segment from an image of the `image` crate
+    pub fn from_image(image: &DynamicImage) -> Result<Self, NsifError> {
+        if image.color().has_color() {
+            let rgb = image.to_rgb8();
+            Self::from_pixels(rgb.width(), rgb.height(), &["R", "G", "B"], rgb.into_raw())
+        } else {
+            let luma = image.to_luma8();
+            Self::from_pixels(luma.width(), luma.height(), &["M"], luma.into_raw())
+        }
+    }
+
+    /// Maps (row, column) coordinates to the full image the image was cut from (ICHIPB)
+    pub fn chip_to_full_image(&self, point: (f64, f64)) -> Result<(f64, f64), NsifError> {
+        match self.sub_header.ichipb()? {
+            Some(ichipb) => Ok(ichipb.chip_to_full_image()?.apply(point)),
+            None => Ok(point),
+        }
+    }
+
     pub fn dimensions(&self) -> Result<(i32, i32), Box<dyn std::error::Error>> {
-        if let (Value::SingleNumeric(height), Value::SingleNumeric(width)) =
-            (&self.sub_header.nrows.value, &self.sub_header.ncols.value)
+        Ok((
+            self.sub_header.num_rows()? as i32,
+            self.sub_header.num_cols()? as i32,
+        ))
+    }
+
+    pub fn compress(&mut self, compression: Compression) -> Result<(), NsifError> {
+        self.compress_with(compression, &CompressionOptions::default())
+    }
+
+    /// Compresses an uncompressed 8-bit image with one or three bands stored in a single block
+    pub fn compress_with(
+        &mut self,
+        compression: Compression,
+        options: &CompressionOptions,
+    ) -> Result<(), NsifError> {
+        let sub_header = &self.sub_header;
+        if sub_header.compression()? != Compression::NotCompressed
+            || sub_header.bits_per_pixel()? != 8
+        {
+            return Err(NsifError::IcNotSupported);
+        }
+        let (width, height) = (sub_header.num_cols()?, sub_header.num_rows()?);
+        let bands = sub_header.num_bands()? as usize;
+        if (bands > 1 && sub_header.image_mode()? != ImageMode::BandInterleavedByPixel)
+            || sub_header.blocks_per_row()? != 1
+            || sub_header.blocks_per_column()? != 1
         {
-            return Ok((
-                parse_number_from_string(&height.value)?,
-                parse_number_from_string(&width.value)?,
-            ));
+            return Err(NsifError::ImodeNotSupported);
         }
-        Err(Box::new(NsifError::InvalidDimensions))
+        if !(1..=8192).contains(&options.block_size)
+            || options.rate.is_some_and(|rate| !(rate > 0.0 && rate < 8.0))
+        {
+            return Err(NsifError::InvalidDimensions);
+        }
+        let block_width = options.block_size.min(width);
+        let block_height = options.block_size.min(height);
+        let blocks_per_row = width.div_ceil(block_width);
+        let blocks_per_column = height.div_ceil(block_height);
+
+        let (data, comrat, imode) = match compression {
+            Compression::NotCompressed => return Ok(()),
+            Compression::Jpeg => {
+                let mut data = Vec::new();
+                for block_row in 0..blocks_per_column {
+                    for block_column in 0..blocks_per_row {
+                        let block = extract_block(
+                            &self.data,
+                            width,
+                            height,
+                            bands,
+                            (block_column * block_width, block_row * block_height),
+                            (block_width, block_height),
+                        );
+                        data.extend(encode_jpeg(
+                            &block,
+                            block_width,
+                            block_height,
+                            bands,
+                            options.quality,
+                        )?);
+                    }
+                }
+                // 00.0 denotes quantization tables embedded in the stream
+                let imode = if bands == 1 {
+                    ImageMode::BandInterleavedByBlock
+                } else {
+                    ImageMode::BandInterleavedByPixel
+                };
+                (data, String::from("00.0"), imode)
+            }
+            Compression::Jpeg2000 => {
+                let data = encode_jpeg2000(
+                    &self.data,
+                    width,
+                    height,
+                    bands,
+                    options.rate,
+                    options.block_size,
+                )?;
+                // The bit rate in bits per pixel per band, prefixed by N if numerically lossless
+                let comrat = match options.rate {
+                    Some(rate) => format!("{rate:04.1}"),
+                    None => {
+                        let rate = data.len() as f64 * 8.0 / self.data.len() as f64;
+                        format!("N{:03}", (rate * 10.0).round().min(999.0) as u32)
+                    }
+                };
+                (data, comrat, ImageMode::BandInterleavedByBlock)
+            }
+            _ => return Err(NsifError::IcNotSupported),
+        };
+        self.data = data;
+        let sub_header = &mut self.sub_header;
+        sub_header.ic.set(compression.code())?;
+        sub_header.comrat.set(&comrat)?;
+        sub_header.imode.set(imode.code())?;
+        sub_header.nbpr.set_number(blocks_per_row as u64)?;
+        sub_header.nbpc.set_number(blocks_per_column as u64)?;
+        sub_header.nppbh.set_number(block_width as u64)?;
+        sub_header.nppbv.set_number(block_height as u64)
     }
 
     pub fn as_rgb(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        if let Value::SingleAlphanumeric(ic) = &self.sub_header.ic.value {
-            return match ic.value.as_str() {
-                "NC" => self.handle_nc().map_err(Into::into),
-                "C3" => self.handle_c3().map_err(Into::into),
-                "C8" => self.handle_c8().map_err(Into::into),
+        let compression = self
+            .sub_header
+            .compression()
+            .map_err(|_| NsifError::ImageSegmentSubHeaderMalformed)?;
+        match compression {
+            Compression::NotCompressed => self.handle_nc().map_err(Into::into),
+            Compression::Jpeg => self.handle_c3(),
+            Compression::Jpeg2000 => self.handle_c8().map_err(Into::into),
+            _ => Err(Box::new(NsifError::IcNotSupported)),
+        }
+    }
+
+    /// The samples of all bands in band interleaved by pixel order, each taking NBPP / 8 bytes
+    pub fn pixels(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let sub_header = &self.sub_header;
+        let bands = sub_header.num_bands()? as usize;
+        match sub_header.compression()? {
+            Compression::NotCompressed => {
+                let layout = SampleLayout::new(sub_header, self.data.len())?;
+                let mut pixels = Vec::with_capacity(layout.image_length());
+                for row in 0..layout.rows {
+                    for column in 0..layout.columns {
+                        for band in 0..layout.bands {
+                            let offset = layout.offset(row, column, band);
+                            pixels.extend_from_slice(&self.data[offset..offset + layout.sample]);
+                        }
+                    }
+                }
+                Ok(pixels)
+            }
+            Compression::Jpeg if sub_header.bits_per_pixel()? == 8 => match bands {
+                1 => self.decode_jpeg_blocks(ColorSpace::Luma),
+                3 => self.decode_jpeg_blocks(ColorSpace::RGB),
                 _ => Err(Box::new(NsifError::IcNotSupported)),
-            };
+            },
+            Compression::Jpeg2000 if sub_header.bits_per_pixel()? == 8 => {
+                let pixels = self.handle_c8()?;
+                if pixels.len()
+                    != self.sub_header.num_rows()? as usize
+                        * self.sub_header.num_cols()? as usize
+                        * bands
+                {
+                    return Err(Box::new(NsifError::InvalidDimensions));
+                }
+                Ok(pixels)
+            }
+            _ => Err(Box::new(NsifError::IcNotSupported)),
         }
-        Err(Box::new(NsifError::ImageSegmentSubHeaderMalformed))
     }
 
-    fn handle_nc(&self) -> Result<Vec<u8>, NsifError> {
-        if let Value::SingleAlphanumeric(imode) = &self.sub_header.imode.value {
-            if imode.value.as_str() == "P" {
-                return Ok(self.data.clone());
+    /// Replaces the samples of all bands, keeping the layout or compression of the image
+    pub fn set_pixels(&mut self, pixels: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let compression = self.sub_header.compression()?;
+        if compression == Compression::NotCompressed {
+            let layout = SampleLayout::new(&self.sub_header, self.data.len())?;
+            if pixels.len() != layout.image_length() {
+                return Err(Box::new(NsifError::InvalidDimensions));
             }
+            let mut samples = pixels.chunks_exact(layout.sample);
+            for row in 0..layout.rows {
+                for column in 0..layout.columns {
+                    for band in 0..layout.bands {
+                        let offset = layout.offset(row, column, band);
+                        self.data[offset..offset + layout.sample]
+                            .copy_from_slice(samples.next().unwrap_or_default());
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let (width, height) = (self.sub_header.num_cols()?, self.sub_header.num_rows()?);
+        self.encode(width, height, pixels)
+    }
+
+    /// A copy of the segment holding only the given window, encoded like [`Self::set_pixels`]
+    pub fn crop(
+        &self,
+        row: u32,
+        column: u32,
+        height: u32,
+        width: u32,
+    ) -> Result<ImageSegment, Box<dyn std::error::Error>> {
+        let sub_header = &self.sub_header;
+        let (rows, columns) = (sub_header.num_rows()?, sub_header.num_cols()?);
+        if height == 0
+            || width == 0
+            || row.checked_add(height).is_none_or(|end| end > rows)
+            || column.checked_add(width).is_none_or(|end| end > columns)
+        {
+            return Err(Box::new(NsifError::InvalidDimensions));
         }
-        Err(NsifError::ImodeNotSupported)
+        let pixels = self.pixels()?;
+        let pixel_length = pixels.len() / (rows as usize * columns as usize);
+        let window = (row..row + height)
+            .flat_map(|y| {
+                let start = (y as usize * columns as usize + column as usize) * pixel_length;
+                &pixels[start..start + width as usize * pixel_length]
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        let mut cropped = self.clone();
+        cropped.encode(width, height, &window)?;
+        Ok(cropped)
+    }
+
+    /// Decodes the image and encodes it again with the given compression and options
+    pub fn recompress(
+        &mut self,
+        compression: Compression,
+        options: &CompressionOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let pixels = self.pixels()?;
+        let (width, height) = (self.sub_header.num_cols()?, self.sub_header.num_rows()?);
+        self.encode_with(width, height, &pixels, compression, options)
     }
 
-    fn handle_c3(&self) -> Result<Vec<u8>, DecodeErrors> {
-        JpegDecoder::new(&self.data).decode()
+    /// Replaces the image with pixels of the given size, keeping its compression
+    fn encode(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let sub_header = &self.sub_header;
+        let compression = sub_header.compression()?;
+        let block_size = match max(
+            sub_header.pixels_per_block_horizontal()?,
+            sub_header.pixels_per_block_vertical()?,
+        ) {
+            0 => CompressionOptions::default().block_size,
+            size => size,
+        };
+        // A numeric COMRAT is the bit rate, N followed by digits denotes numerically lossless
+        let rate = match compression {
+            Compression::Jpeg2000 => parse_string_from_bytes(&sub_header.comrat.bytes())
+                .trim()
+                .parse::<f32>()
+                .ok(),
+            _ => None,
+        };
+        let options = CompressionOptions {
+            rate,
+            block_size,
+            ..CompressionOptions::default()
+        };
+        self.encode_with(width, height, pixels, compression, &options)
+    }
+
+    fn encode_with(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        compression: Compression,
+        options: &CompressionOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let sub_header = &self.sub_header;
+        let bands = sub_header.num_bands()?;
+        let sample = sub_header.bits_per_pixel()?.div_ceil(8) as usize;
+        if !matches!(
+            compression,
+            Compression::NotCompressed | Compression::Jpeg | Compression::Jpeg2000
+        ) {
+            return Err(Box::new(NsifError::IcNotSupported));
+        }
+        if pixels.len() != width as usize * height as usize * bands as usize * sample {
+            return Err(Box::new(NsifError::InvalidDimensions));
+        }
+
+        // Encode from a single uncompressed block, as produced by `from_pixels`
+        let mut uncompressed = self.clone();
+        let sub_header = &mut uncompressed.sub_header;
+        sub_header.nrows.set_number(height as u64)?;
+        sub_header.ncols.set_number(width as u64)?;
+        sub_header.ic.set(Compression::NotCompressed.code())?;
+        sub_header.imode.set(if bands == 1 {
+            ImageMode::BandInterleavedByBlock.code()
+        } else {
+            ImageMode::BandInterleavedByPixel.code()
+        })?;
+        sub_header.nbpr.set_number(1)?;
+        sub_header.nbpc.set_number(1)?;
+        sub_header
+            .nppbh
+            .set_number(if width > 8192 { 0 } else { width as u64 })?;
+        sub_header
+            .nppbv
+            .set_number(if height > 8192 { 0 } else { height as u64 })?;
+        uncompressed.data = pixels.to_vec();
+        if compression != Compression::NotCompressed {
+            uncompressed.compress_with(compression, options)?;
+        }
+        *self = uncompressed;
+        Ok(())
+    }
+
+    fn handle_nc(&self) -> Result<Vec<u8>, NsifError> {
+        if matches!(self.sub_header.num_bands(), Ok(1)) {
+            // For a single band, all image modes store the pixels in the same order
+            return Ok(self.data.iter().flat_map(|&value| [value; 3]).collect());
+        }
+        match self.sub_header.image_mode() {
+            Ok(ImageMode::BandInterleavedByPixel) => Ok(self.data.clone()),
+            _ => Err(NsifError::ImodeNotSupported),
+        }
+    }
+
+    fn handle_c3(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        self.decode_jpeg_blocks(ColorSpace::RGB)
+    }
+
+    /// Decodes and assembles the blocks of a C3 image
+    fn decode_jpeg_blocks(
+        &self,
+        color_space: ColorSpace,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let sub_header = &self.sub_header;
+        let (width, height) = (
+            sub_header.num_cols()? as usize,
+            sub_header.num_rows()? as usize,
+        );
+        let blocks_per_row = match sub_header.blocks_per_row()? {
+            0 => {
+                return Err(Box::new(NsifError::InvalidFieldValue {
+                    mnemonic: "NBPR",
+                    value: parse_string_from_bytes(&sub_header.nbpr.bytes()),
+                }))
+            }
+            blocks => blocks as usize,
+        };
+        let block_width = match sub_header.pixels_per_block_horizontal()? {
+            0 => width,
+            pixels => pixels as usize,
+        };
+        let block_height = match sub_header.pixels_per_block_vertical()? {
+            0 => height,
+            pixels => pixels as usize,
+        };
+
+        let channels = color_space.num_components();
+        let options = DecoderOptions::default().jpeg_set_out_colorspace(color_space);
+        let mut pixels = vec![0; width * height * channels];
+        for (i, stream) in split_jpeg_streams(&self.data).into_iter().enumerate() {
+            let block = JpegDecoder::new_with_options(stream, options).decode()?;
+            if block.len() < block_width * block_height * channels {
+                return Err(Box::new(NsifError::InvalidDimensions));
+            }
+            let (left, top) = (
+                i % blocks_per_row * block_width,
+                i / blocks_per_row * block_height,
+            );
+            let visible_width = block_width.min(width.saturating_sub(left));
+            for y in 0..block_height.min(height.saturating_sub(top)) {
+                let source = y * block_width * channels;
+                let target = ((top + y) * width + left) * channels;
+                pixels[target..target + visible_width * channels]
+                    .copy_from_slice(&block[source..source + visible_width * channels]);
+            }
+        }
+        Ok(pixels)
     }
 
     fn handle_c8(&self) -> Result<Vec<u8>, jpeg2k::error::Error> {
         jpeg2k::Image::from_bytes(self.data.as_slice())
             .and_then(|image| image.get_pixels(None))
-            .and_then(|image_data| {
-                return match image_data.data {
-                    ImagePixelData::L8(data)
-                    | ImagePixelData::La8(data)
-                    | ImagePixelData::Rgb8(data)
-                    | ImagePixelData::Rgba8(data) => Ok(data),
-                    _ => Err(jpeg2k::error::Error::UnknownFormatError(String::from(
-                        "unsupported pixel format encountered",
-                    ))),
-                };
+            .and_then(|image_data| match image_data.data {
+                ImagePixelData::L8(data)
+                | ImagePixelData::La8(data)
+                | ImagePixelData::Rgb8(data)
+                | ImagePixelData::Rgba8(data) => Ok(data),
+                _ => Err(jpeg2k::error::Error::UnknownFormatError(String::from(
+                    "unsupported pixel format encountered",
+                ))),
             })
     }
 }
 
-#[derive(Debug, Reflect)]
+/// Where the samples of an uncompressed image are stored in its data for each image mode
+struct SampleLayout {
+    mode: ImageMode,
+    rows: usize,
+    columns: usize,
+    bands: usize,
+    sample: usize,
+    blocks_per_row: usize,
+    blocks: usize,
+    block_width: usize,
+    block_height: usize,
+}
+
+impl SampleLayout {
+    /// The layout described by the sub header, checked against the length of the data
+    fn new(sub_header: &ImageSubheader, data_length: usize) -> Result<Self, NsifError> {
+        let bits_per_pixel = sub_header.bits_per_pixel()? as usize;
+        if bits_per_pixel == 0 || !bits_per_pixel.is_multiple_of(8) {
+            return Err(NsifError::IcNotSupported);
+        }
+        let (rows, columns) = (
+            sub_header.num_rows()? as usize,
+            sub_header.num_cols()? as usize,
+        );
+        let blocks_per_row = sub_header.blocks_per_row()? as usize;
+        let blocks = blocks_per_row * sub_header.blocks_per_column()? as usize;
+        let layout = SampleLayout {
+            mode: sub_header.image_mode()?,
+            rows,
+            columns,
+            bands: sub_header.num_bands()? as usize,
+            sample: bits_per_pixel / 8,
+            blocks_per_row,
+            blocks,
+            block_width: match sub_header.pixels_per_block_horizontal()? {
+                0 => columns,
+                pixels => pixels as usize,
+            },
+            block_height: match sub_header.pixels_per_block_vertical()? {
+                0 => rows,
+                pixels => pixels as usize,
+            },
+        };
+        if layout.blocks * layout.block_length() * layout.bands > data_length {
+            return Err(NsifError::InvalidDimensions);
+        }
+        Ok(layout)
+    }
+
+    fn block_length(&self) -> usize {
+        self.block_width * self.block_height * self.sample
+    }
+
+    fn image_length(&self) -> usize {
+        self.rows * self.columns * self.bands * self.sample
+    }
+
+    fn offset(&self, row: usize, column: usize, band: usize) -> usize {
+        let block = row / self.block_height * self.blocks_per_row + column / self.block_width;
+        let (y, x) = (row % self.block_height, column % self.block_width);
+        let block_length = self.block_length();
+        let pixel = (y * self.block_width + x) * self.sample;
+        match self.mode {
+            ImageMode::BandInterleavedByBlock => (block * self.bands + band) * block_length + pixel,
+            ImageMode::BandInterleavedByPixel => {
+                block * block_length * self.bands + (pixel * self.bands) + band * self.sample
+            }
+            ImageMode::BandInterleavedByRow => {
+                block * block_length * self.bands
+                    + (y * self.bands + band) * self.block_width * self.sample
+                    + x * self.sample
+            }
+            ImageMode::BandSequential => (band * self.blocks + block) * block_length + pixel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Reflect)]
 pub struct ImageSubheader {
     pub im: Field,
     pub iid1: Field,
@@ -147,45 +621,163 @@ pub struct ImageSubheader {
     pub ixshd: Field,
 }
 impl ImageSubheader {
-    fn parse(mut file: &File) -> Result<ImageSubheader, Box<dyn std::error::Error>> {
-        let mut im = vec![0; 2];
-        let mut iid1 = vec![0; 10];
-        let mut idatim = vec![0; 14];
-        let mut tgtid = vec![0; 17];
-        let mut iid2 = vec![0; 80];
-        let mut isclas = vec![0; 1];
-        let mut isclsy = vec![0; 2];
-        let mut iscode = vec![0; 11];
-        let mut isctlh = vec![0; 2];
-        let mut isrel = vec![0; 20];
-        let mut isdctp = vec![0; 2];
-        let mut isdcdt = vec![0; 8];
-        let mut isdcxm = vec![0; 4];
-        let mut isdg = vec![0; 1];
-        let mut isdgt = vec![0; 8];
-        let mut iscltx = vec![0; 43];
-        let mut iscatp = vec![0; 1];
-        let mut iscaut = vec![0; 40];
-        let mut iscrsn = vec![0; 1];
-        let mut issrdt = vec![0; 8];
-        let mut isctln = vec![0; 15];
-        let mut encryp = vec![0; 1];
-        let mut isorce = vec![0; 42];
-        let mut nrows = vec![0; 8];
-        let mut ncols = vec![0; 8];
-        let mut pvtype = vec![0; 3];
-        let mut irep = vec![0; 8];
-        let mut icat = vec![0; 8];
-        let mut abpp = vec![0; 2];
-        let mut pjust = vec![0; 1];
-        let mut icords = vec![0; 1];
-        let mut igeolo = vec![0; 60];
-        let mut nicom = vec![0; 1];
-        let mut icoms = Vec::new();
-        let mut ic = vec![0; 2];
-        let mut comrat = vec![0; 4];
-        let mut nbands = vec![0; 1];
-        let mut xbands = vec![0; 5];
+    /// A sub header for an uncompressed, unclassified 8-bit image stored in a single block
+    pub fn new(rows: u32, cols: u32, bands: &[&str]) -> Result<Self, NsifError> {
+        let value = |definition: FieldDefinition, value: &str| {
+            let length = definition.length.unwrap_or(0);
+            Field::single(definition, 0, format!("{value:<length$}").into())
+        };
+        let table = |definition, value: &str| {
+            Field::multiple(definition, 0, vec![value.into(); bands.len()])
+        };
+        let irep = match bands {
+            ["M"] => ImageRepresentation::Monochrome,
+            ["R", "G", "B"] => ImageRepresentation::Rgb,
+            _ => ImageRepresentation::Multiband,
+        };
+        let imode = if bands.len() == 1 {
+            ImageMode::BandInterleavedByBlock
+        } else {
+            ImageMode::BandInterleavedByPixel
+        };
+        let irepbands = bands
+            .iter()
+            .map(|band| {
+                let mut irepband = Field::blank(IREPBAND);
+                irepband.set(band)?;
+                Ok(irepband.bytes())
+            })
+            .collect::<Result<_, NsifError>>()?;
+        // A block size of 0 denotes a single block spanning more than 8192 pixels
+        let block_size = |pixels: u32| if pixels > 8192 { 0 } else { pixels as u64 };
+
+        let mut sub_header = ImageSubheader {
+            im: value(IM, "IM"),
+            iid1: Field::blank(IID1),
+            idatim: value(IDATIM, &DateTime::now().to_nsif_string()),
+            tgtid: Field::blank(TGTID),
+            iid2: Field::blank(IID2),
+            isclas: value(ISCLAS, "U"),
+            isclsy: Field::blank(ISCLSY),
+            iscode: Field::blank(ISCODE),
+            isctlh: Field::blank(ISCTLH),
+            isrel: Field::blank(ISREL),
+            isdctp: Field::blank(ISDCTP),
+            isdcdt: Field::blank(ISDCDT),
+            isdcxm: Field::blank(ISDCXM),
+            isdg: Field::blank(ISDG),
+            isdgdt: Field::blank(ISDGDT),
+            iscltx: Field::blank(ISCLTX),
+            iscatp: Field::blank(ISCATP),
+            iscaut: Field::blank(ISCAUT),
+            iscrsn: Field::blank(ISCRSN),
+            issrdt: Field::blank(ISSRDT),
+            isctln: Field::blank(ISCTLN),
+            encryp: Field::blank(ENCRYP),
+            isorce: Field::blank(ISORCE),
+            nrows: Field::blank(NROWS),
+            ncols: Field::blank(NCOLS),
+            pvtype: value(PVTYPE, PixelValueType::Integer.code()),
+            irep: Field::blank(IREP),
+            icat: value(ICAT, ImageCategory::Visible.code()),
+            abpp: value(ABPP, "08"),
+            pjust: value(PJUST, "R"),
+            icords: Field::blank(ICORDS),
+            igeolo: Field::absent(IGEOLO),
+            nicom: Field::blank(NICOM),
+            icoms: Field::multiple(ICOM, 0, Vec::new()),
+            ic: value(IC, Compression::NotCompressed.code()),
+            comrat: Field::absent(COMRAT),
+            nbands: Field::blank(NBANDS),
+            xbands: Field::absent(XBANDS),
+            irepbands: Field::multiple(IREPBAND, 0, irepbands),
+            isubcats: table(ISUBCAT, "      "),
+            ifcs: table(IFC, "N"),
+            imflts: table(IMFLT, "   "),
+            nlutss: table(NLUTS, "0"),
+            neluts: table(NELUT, ""),
+            lutdss: Field::nested(LUTD, 0, vec![Vec::new(); bands.len()]),
+            isync: Field::blank(ISYNC),
+            imode: value(IMODE, imode.code()),
+            nbpr: value(NBPR, "0001"),
+            nbpc: value(NBPC, "0001"),
+            nppbh: Field::blank(NPPBH),
+            nppbv: Field::blank(NPPBV),
+            nbpp: value(NBPP, "08"),
+            idlvl: value(IDLVL, "001"),
+            ialvl: Field::blank(IALVL),
+            iloc: Field::blank(ILOC),
+            imag: value(IMAG, "1.0 "),
+            udidl: Field::blank(UDIDL),
+            udofl: Field::absent(UDOFL),
+            udid: Field::absent(UDID),
+            ixshdl: Field::blank(IXSHDL),
+            ixsofl: Field::absent(IXSOFL),
+            ixshd: Field::absent(IXSHD),
+        };
+        sub_header.nrows.set_number(rows as u64)?;
+        sub_header.ncols.set_number(cols as u64)?;
+        sub_header.nppbh.set_number(block_size(cols))?;
+        sub_header.nppbv.set_number(block_size(rows))?;
+        sub_header.irep.set(irep.code())?;
+        sub_header.update_lengths()?;
+        Ok(sub_header)
+    }
+
+    pub(crate) fn parse<R: Read + Seek>(
+        file: &mut R,
+    ) -> Result<ImageSubheader, Box<dyn std::error::Error>> {
+        let mut reader = FieldReader::new(file);
+        let im = reader.read(IM)?;
+        let iid1 = reader.read(IID1)?;
+        let idatim = reader.read(IDATIM)?;
+        let tgtid = reader.read(TGTID)?;
+        let iid2 = reader.read(IID2)?;
+        let isclas = reader.read(ISCLAS)?;
+        let isclsy = reader.read(ISCLSY)?;
+        let iscode = reader.read(ISCODE)?;
+        let isctlh = reader.read(ISCTLH)?;
+        let isrel = reader.read(ISREL)?;
+        let isdctp = reader.read(ISDCTP)?;
+        let isdcdt = reader.read(ISDCDT)?;
+        let isdcxm = reader.read(ISDCXM)?;
+        let isdg = reader.read(ISDG)?;
+        let isdgdt = reader.read(ISDGDT)?;
+        let iscltx = reader.read(ISCLTX)?;
+        let iscatp = reader.read(ISCATP)?;
+        let iscaut = reader.read(ISCAUT)?;
+        let iscrsn = reader.read(ISCRSN)?;
+        let issrdt = reader.read(ISSRDT)?;
+        let isctln = reader.read(ISCTLN)?;
+        let encryp = reader.read(ENCRYP)?;
+        let isorce = reader.read(ISORCE)?;
+        let nrows = reader.read(NROWS)?;
+        let ncols = reader.read(NCOLS)?;
+        let pvtype = reader.read(PVTYPE)?;
+        let irep = reader.read(IREP)?;
+        let icat = reader.read(ICAT)?;
+        let abpp = reader.read(ABPP)?;
+        let pjust = reader.read(PJUST)?;
+        let icords = reader.read(ICORDS)?;
+        let igeolo = reader.read_if(IGEOLO, icords.bytes() != b" ")?;
+
+        let nicom = reader.read(NICOM)?;
+        let number_of_image_comments = parse_number_from_bytes(&nicom.bytes()).unwrap_or(0);
+        let [icoms] = reader.read_interleaved([ICOM], number_of_image_comments as usize)?;
+
+        let ic = reader.read(IC)?;
+        let comrat = reader.read_if(COMRAT, !matches!(ic.bytes().as_slice(), b"NC" | b"NM"))?;
+
+        let nbands = reader.read(NBANDS)?;
+        let nbands_value = parse_number_from_bytes(&nbands.bytes()).unwrap_or(0);
+        let xbands = reader.read_if(XBANDS, nbands_value == 0)?;
+        let number_of_bands = if nbands_value > 0 {
+            nbands_value
+        } else {
+            parse_number_from_bytes(&xbands.bytes()).unwrap_or(0)
+        };
+
+        let bands_offset = reader.position()?;
         let mut irepbands = Vec::new();
         let mut isubcats = Vec::new();
         let mut ifcs = Vec::new();
@@ -193,356 +785,425 @@ impl ImageSubheader {
         let mut nlutss = Vec::new();
         let mut neluts = Vec::new();
         let mut lutdss = Vec::new();
-        let mut isync = vec![0; 1];
-        let mut imode = vec![0; 1];
-        let mut nbpr = vec![0; 4];
-        let mut nbpc = vec![0; 4];
-        let mut nppbh = vec![0; 4];
-        let mut nppbv = vec![0; 4];
-        let mut nbpp = vec![0; 2];
-        let mut idlvl = vec![0; 3];
-        let mut ialvl = vec![0; 3];
-        let mut iloc = vec![0; 10];
-        let mut imag = vec![0; 4];
-        let mut udidl = vec![0; 5];
-        let mut udofl = vec![0; 3];
-        // udid is dynamically sized
-        let mut ixshdl = vec![0; 5];
-        let mut ixsofl = vec![0; 3];
-        // ixshd is dynamically sized
-
-        file.read_exact(&mut im)?;
-        file.read_exact(&mut iid1)?;
-        file.read_exact(&mut idatim)?;
-        file.read_exact(&mut tgtid)?;
-        file.read_exact(&mut iid2)?;
-        file.read_exact(&mut isclas)?;
-        file.read_exact(&mut isclsy)?;
-        file.read_exact(&mut iscode)?;
-        file.read_exact(&mut isctlh)?;
-        file.read_exact(&mut isrel)?;
-        file.read_exact(&mut isdctp)?;
-        file.read_exact(&mut isdcdt)?;
-        file.read_exact(&mut isdcxm)?;
-        file.read_exact(&mut isdg)?;
-        file.read_exact(&mut isdgt)?;
-        file.read_exact(&mut iscltx)?;
-        file.read_exact(&mut iscatp)?;
-        file.read_exact(&mut iscaut)?;
-        file.read_exact(&mut iscrsn)?;
-        file.read_exact(&mut issrdt)?;
-        file.read_exact(&mut isctln)?;
-        file.read_exact(&mut encryp)?;
-        file.read_exact(&mut isorce)?;
-        file.read_exact(&mut nrows)?;
-        file.read_exact(&mut ncols)?;
-        file.read_exact(&mut pvtype)?;
-        file.read_exact(&mut irep)?;
-        file.read_exact(&mut icat)?;
-        file.read_exact(&mut abpp)?;
-        file.read_exact(&mut pjust)?;
-        file.read_exact(&mut icords)?;
-        file.read_exact(&mut igeolo)?;
-
-        file.read_exact(&mut nicom)?;
-        let number_of_image_comments = parse_number_from_bytes(&nicom).unwrap_or(0);
-        for _ in 0..number_of_image_comments {
-            let mut icom = vec![0; 80];
-            file.read_exact(&mut icom)?;
-            icoms.push(icom);
+        for _ in 0..number_of_bands {
+            irepbands.push(reader.read_value(IREPBAND)?);
+            isubcats.push(reader.read_value(ISUBCAT)?);
+            ifcs.push(reader.read_value(IFC)?);
+            imflts.push(reader.read_value(IMFLT)?);
+            let nluts = reader.read_value(NLUTS)?;
+            let number_of_luts = parse_number_from_bytes(&nluts).unwrap_or(0);
+            let nelut = if number_of_luts != 0 {
+                reader.read_value(NELUT)?
+            } else {
+                Vec::new()
+            };
+            let lut_entry_size = parse_number_from_bytes(&nelut).unwrap_or(0);
+            let mut lutds = Vec::new();
+            for _ in 0..number_of_luts {
+                lutds.push(reader.read_bytes(lut_entry_size as usize)?);
+            }
+            nlutss.push(nluts);
+            neluts.push(nelut);
+            lutdss.push(lutds);
         }
+        let irepbands = Field::multiple(IREPBAND, bands_offset, irepbands);
+        let isubcats = Field::multiple(ISUBCAT, bands_offset, isubcats);
+        let ifcs = Field::multiple(IFC, bands_offset, ifcs);
+        let imflts = Field::multiple(IMFLT, bands_offset, imflts);
+        let nlutss = Field::multiple(NLUTS, bands_offset, nlutss);
+        let neluts = Field::multiple(NELUT, bands_offset, neluts);
+        let lutdss = Field::nested(LUTD, bands_offset, lutdss);
+
+        let isync = reader.read(ISYNC)?;
+        let imode = reader.read(IMODE)?;
+        let nbpr = reader.read(NBPR)?;
+        let nbpc = reader.read(NBPC)?;
+        let nppbh = reader.read(NPPBH)?;
+        let nppbv = reader.read(NPPBV)?;
+        let nbpp = reader.read(NBPP)?;
+        let idlvl = reader.read(IDLVL)?;
+        let ialvl = reader.read(IALVL)?;
+        let iloc = reader.read(ILOC)?;
+        let imag = reader.read(IMAG)?;
+        let udidl = reader.read(UDIDL)?;
+        let udidl_value = parse_number_from_bytes(&udidl.bytes()).unwrap_or(0);
+        let udofl = reader.read_if(UDOFL, udidl_value != 0)?;
+        let udid = reader.read_with_length(UDID, max(udidl_value - 3, 0) as usize)?;
+
+        let ixshdl = reader.read(IXSHDL)?;
+        let ixshdl_value = parse_number_from_bytes(&ixshdl.bytes()).unwrap_or(0);
+        let ixsofl = reader.read_if(IXSOFL, ixshdl_value != 0)?;
+        let ixshd = reader.read_with_length(IXSHD, max(ixshdl_value - 3, 0) as usize)?;
+
+        Ok(ImageSubheader {
+            im,
+            iid1,
+            idatim,
+            tgtid,
+            iid2,
+            isclas,
+            isclsy,
+            iscode,
+            isctlh,
+            isrel,
+            isdctp,
+            isdcdt,
+            isdcxm,
+            isdg,
+            isdgdt,
+            iscltx,
+            iscatp,
+            iscaut,
+            iscrsn,
+            issrdt,
+            isctln,
+            encryp,
+            isorce,
+            nrows,
+            ncols,
+            pvtype,
+            irep,
+            icat,
+            abpp,
+            pjust,
+            icords,
+            igeolo,
+            nicom,
+            icoms,
+            ic,
+            comrat,
+            nbands,
+            xbands,
+            irepbands,
+            isubcats,
+            ifcs,
+            imflts,
+            nlutss,
+            neluts,
+            lutdss,
+            isync,
+            imode,
+            nbpr,
+            nbpc,
+            nppbh,
+            nppbv,
+            nbpp,
+            idlvl,
+            ialvl,
+            iloc,
+            imag,
+            udidl,
+            udofl,
+            udid,
+            ixshdl,
+            ixsofl,
+            ixshd,
+        })
+    }
+
+    pub fn num_rows(&self) -> Result<u32, NsifError> {
+        self.nrows.parse_value()
+    }
+
+    pub fn num_cols(&self) -> Result<u32, NsifError> {
+        self.ncols.parse_value()
+    }
 
-        file.read_exact(&mut ic)?;
-        let ic_value = String::from_utf8(ic.clone())?;
-        if ic_value != "NC" && ic_value != "NM" {
-            file.read_exact(&mut comrat)?;
+    pub fn num_bands(&self) -> Result<u32, NsifError> {
+        match self.nbands.parse_value()? {
+            0 => self.xbands.parse_value(),
+            nbands => Ok(nbands),
         }
+    }
+
+    pub fn compression(&self) -> Result<Compression, NsifError> {
+        self.ic.parse_value()
+    }
+
+    pub fn image_mode(&self) -> Result<ImageMode, NsifError> {
+        self.imode.parse_value()
+    }
+
+    pub fn image_representation(&self) -> Result<ImageRepresentation, NsifError> {
+        self.irep.parse_value()
+    }
+
+    pub fn image_category(&self) -> Result<ImageCategory, NsifError> {
+        self.icat.parse_value()
+    }
 
-        file.read_exact(&mut nbands)?;
-        let nbands_value = parse_number_from_bytes(&nbands).unwrap_or(0);
-        if nbands_value == 0 {
-            file.read_exact(&mut xbands)?;
+    pub fn coordinate_representation(&self) -> Result<CoordinateRepresentation, NsifError> {
+        self.icords.parse_value()
+    }
+
+    /// Sets IGEOLO to the given (latitude, longitude) corners in decimal degrees, in the order
+    /// upper left, upper right, lower right, lower left (first row/column first)
+    pub fn set_corners(&mut self, corners: [(f64, f64); 4]) -> Result<(), NsifError> {
+        let igeolo = corners
+            .iter()
+            .map(|&(latitude, longitude)| {
+                if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                    return Err(NsifError::InvalidFieldValue {
+                        mnemonic: "IGEOLO",
+                        value: format!("{latitude},{longitude}"),
+                    });
+                }
+                Ok(format!("{latitude:+07.3}{longitude:+08.3}"))
+            })
+            .collect::<Result<String, _>>()?;
+        self.icords
+            .set(CoordinateRepresentation::DecimalDegrees.code())?;
+        self.igeolo.set(&igeolo)
+    }
+
+    /// The corners in the order of [`Self::set_corners`], for ICORDS D and G
+    pub fn corners(&self) -> Result<[(f64, f64); 4], NsifError> {
+        let representation = self.coordinate_representation()?;
+        if !matches!(
+            representation,
+            CoordinateRepresentation::DecimalDegrees | CoordinateRepresentation::Geographic
+        ) {
+            return Err(NsifError::InvalidFieldValue {
+                mnemonic: "ICORDS",
+                value: parse_string_from_bytes(&self.icords.bytes()),
+            });
         }
-        let number_of_bands = if nbands_value > 0 {
-            nbands_value
-        } else {
-            parse_number_from_bytes(&xbands).unwrap_or(0)
+        let bytes = self.igeolo.bytes();
+        let invalid = || NsifError::InvalidFieldValue {
+            mnemonic: "IGEOLO",
+            value: parse_string_from_bytes(&bytes),
+        };
+        // BCS-A is ASCII, so that the coordinates can be sliced by byte
+        if bytes.len() != 60 || !is_bcs(&bytes) {
+            return Err(invalid());
+        }
+        let number = |s: &str| s.parse::<f64>().map_err(|_| invalid());
+        // ddmmss or dddmmss followed by the hemisphere
+        let sexagesimal = |s: &str, negative: char| {
+            let (value, hemisphere) = s.split_at(s.len() - 1);
+            let (degrees, rest) = value.split_at(value.len() - 4);
+            let decimal =
+                number(degrees)? + number(&rest[..2])? / 60.0 + number(&rest[2..])? / 3600.0;
+            Ok(if hemisphere == negative.to_string() {
+                -decimal
+            } else {
+                decimal
+            })
         };
+        let mut corners = [(0.0, 0.0); 4];
+        for (corner, s) in corners.iter_mut().zip(bytes.chunks(15)) {
+            let s = std::str::from_utf8(s).map_err(|_| invalid())?;
+            *corner = match representation {
+                CoordinateRepresentation::DecimalDegrees => (number(&s[..7])?, number(&s[7..])?),
+                _ => (sexagesimal(&s[..7], 'S')?, sexagesimal(&s[7..], 'W')?),
+            };
+        }
+        Ok(corners)
+    }
 
-        for _ in 0..number_of_bands {
-            let mut irepband = vec![0; 2];
-            let mut isubcat = vec![0; 6];
-            let mut ifc = vec![0; 1];
-            let mut imflt = vec![0; 3];
-            let mut nluts = vec![0; 1];
-            let mut nelut = vec![0; 5];
-            let mut lutds = Vec::new();
+    pub fn pixel_type(&self) -> Result<PixelValueType, NsifError> {
+        self.pvtype.parse_value()
+    }
 
-            file.read_exact(&mut irepband)?;
-            file.read_exact(&mut isubcat)?;
-            file.read_exact(&mut ifc)?;
-            file.read_exact(&mut imflt)?;
-            file.read_exact(&mut nluts)?;
-            let number_of_lut_entries = parse_number_from_bytes(&nluts).unwrap_or(0);
-            if number_of_lut_entries != 0 {
-                file.read_exact(&mut nelut)?;
-            }
-            let lut_entry_size = parse_number_from_bytes(&nelut).unwrap_or(0);
-            for _ in 0..number_of_lut_entries {
-                let mut lutd = vec![0; lut_entry_size as usize];
-                file.read_exact(&mut lutd)?;
-                lutds.push(lutd);
-            }
+    pub fn classification(&self) -> Result<Classification, NsifError> {
+        self.isclas.parse_value()
+    }
 
-            irepbands.push(irepband);
-            isubcats.push(isubcat);
-            ifcs.push(ifc);
-            imflts.push(imflt);
-            nlutss.push(nluts);
-            neluts.push(nelut);
-            lutdss.push(lutds);
-        }
+    pub fn tres(&self) -> Result<Vec<Tre>, NsifError> {
+        Ok([parse_tres(&self.udid)?, parse_tres(&self.ixshd)?].concat())
+    }
+
+    /// The chipping TRE relating the image to the full image it was cut from, if any
+    pub fn ichipb(&self) -> Result<Option<Ichipb>, NsifError> {
+        self.tres()?
+            .iter()
+            .find(|tre| tre.tag == Ichipb::TAG)
+            .map(Ichipb::parse)
+            .transpose()
+    }
+
+    pub fn security(&self) -> Result<SecurityMetadata, NsifError> {
+        let fields = [
+            &self.isclas,
+            &self.isclsy,
+            &self.iscode,
+            &self.isctlh,
+            &self.isrel,
+            &self.isdctp,
+            &self.isdcdt,
+            &self.isdcxm,
+            &self.isdg,
+            &self.isdgdt,
+            &self.iscltx,
+            &self.iscatp,
+            &self.iscaut,
+            &self.iscrsn,
+            &self.issrdt,
+            &self.isctln,
+        ];
+        SecurityMetadata::parse("IS", &fields.map(Field::bytes).concat())
+    }
+
+    pub fn image_datetime(&self) -> Result<DateTime, NsifError> {
+        self.idatim.parse_value()
+    }
+
+    pub fn bits_per_pixel(&self) -> Result<u8, NsifError> {
+        self.nbpp.parse_value()
+    }
+
+    pub fn actual_bits_per_pixel(&self) -> Result<u8, NsifError> {
+        self.abpp.parse_value()
+    }
 
-        file.read_exact(&mut isync)?;
-        file.read_exact(&mut imode)?;
-        file.read_exact(&mut nbpr)?;
-        file.read_exact(&mut nbpc)?;
-        file.read_exact(&mut nppbh)?;
-        file.read_exact(&mut nppbv)?;
-        file.read_exact(&mut nbpp)?;
-        file.read_exact(&mut idlvl)?;
-        file.read_exact(&mut ialvl)?;
-        file.read_exact(&mut iloc)?;
-        file.read_exact(&mut imag)?;
-        file.read_exact(&mut udidl)?;
-        let udid_length = max(parse_number_from_bytes(&udidl).unwrap_or(3) - 3, 0);
-        let mut udid = vec![0; udid_length as usize];
-        if udid_length != 0 {
-            file.read_exact(&mut udofl)?;
-            file.read_exact(&mut udid)?;
+    pub fn blocks_per_row(&self) -> Result<u32, NsifError> {
+        self.nbpr.parse_value()
+    }
+
+    pub fn blocks_per_column(&self) -> Result<u32, NsifError> {
+        self.nbpc.parse_value()
+    }
+
+    pub fn pixels_per_block_horizontal(&self) -> Result<u32, NsifError> {
+        self.nppbh.parse_value()
+    }
+
+    pub fn pixels_per_block_vertical(&self) -> Result<u32, NsifError> {
+        self.nppbv.parse_value()
+    }
+
+    pub fn display_level(&self) -> Result<u16, NsifError> {
+        self.idlvl.parse_value()
+    }
+
+    pub fn attachment_level(&self) -> Result<u16, NsifError> {
+        self.ialvl.parse_value()
+    }
+
+    /// The (row, column) of the first pixel relative to the item the image is attached to (ILOC)
+    pub fn location(&self) -> Result<(i32, i32), NsifError> {
+        let iloc = self.iloc.bytes();
+        let invalid = || NsifError::InvalidFieldValue {
+            mnemonic: "ILOC",
+            value: parse_string_from_bytes(&iloc),
+        };
+        if iloc.len() != 10 {
+            return Err(invalid());
         }
-        file.read_exact(&mut ixshdl)?;
-        file.read_exact(&mut ixsofl)?;
-        let ixshdl_length = max(parse_number_from_bytes(&ixshdl).unwrap_or(0) - 3, 0);
-        let mut ixshd = vec![0; ixshdl_length as usize];
-        if ixshdl_length != 0 {
-            file.read_exact(&mut ixshd)?;
+        let parse = |bytes: &[u8]| {
+            parse_string_from_bytes(bytes)
+                .parse::<i32>()
+                .map_err(|_| invalid())
+        };
+        Ok((parse(&iloc[..5])?, parse(&iloc[5..])?))
+    }
+
+    /// Recomputes the count and length fields and adds or removes IGEOLO and COMRAT
+    pub(crate) fn update_lengths(&mut self) -> Result<(), NsifError> {
+        let number_of_bands = self.irepbands.value_bytes().len() as u64;
+        if number_of_bands > 9 {
+            self.nbands.set_number(0)?;
+            self.xbands.set_number(number_of_bands)?;
+        } else {
+            self.nbands.set_number(number_of_bands)?;
+            self.xbands.set_present(false);
         }
+        self.igeolo.set_present(self.icords.bytes() != b" ");
+        self.comrat
+            .set_present(!matches!(self.ic.bytes().as_slice(), b"NC" | b"NM"));
+        self.nicom
+            .set_number(self.icoms.value_bytes().len() as u64)?;
+        set_data_length(&mut self.udidl, &mut self.udofl, &self.udid)?;
+        set_data_length(&mut self.ixshdl, &mut self.ixsofl, &self.ixshd)
+    }
 
-        Ok(ImageSubheader {
-            im: Field::from_alphanumeric("File Part Type", parse_string_from_bytes(&im)?),
-            iid1: Field::from_alphanumeric("Image Identifier 1", parse_string_from_bytes(&iid1)?),
-            idatim: Field::from_numeric("Image Date and Time", parse_string_from_bytes(&idatim)?),
-            tgtid: Field::from_alphanumeric("Target Identifier", parse_string_from_bytes(&tgtid)?),
-            iid2: Field::from_alphanumeric("Image Identifier 2", parse_string_from_bytes(&iid2)?),
-            isclas: Field::from_alphanumeric(
-                "Image Security Classification",
-                parse_string_from_bytes(&isclas)?,
-            ),
-            isclsy: Field::from_alphanumeric(
-                "Image Security Classification System",
-                parse_string_from_bytes(&isclsy)?,
-            ),
-            iscode: Field::from_alphanumeric("Image Codewords", parse_string_from_bytes(&iscode)?),
-            isctlh: Field::from_alphanumeric(
-                "Image Control and Handling",
-                parse_string_from_bytes(&isctlh)?,
-            ),
-            isrel: Field::from_alphanumeric(
-                "Image Releasing Instructions",
-                parse_string_from_bytes(&isrel)?,
-            ),
-            isdctp: Field::from_alphanumeric(
-                "Image Declassification Type",
-                parse_string_from_bytes(&isdctp)?,
-            ),
-            isdcdt: Field::from_alphanumeric(
-                "Image Declassification Date",
-                parse_string_from_bytes(&isdcdt)?,
-            ),
-            isdcxm: Field::from_alphanumeric(
-                "Image Declassification Exemption",
-                parse_string_from_bytes(&isdcxm)?,
-            ),
-            isdg: Field::from_alphanumeric("Image Downgrade", parse_string_from_bytes(&isdg)?),
-            isdgdt: Field::from_alphanumeric(
-                "Image Downgrade Date",
-                parse_string_from_bytes(&isdgt)?,
-            ),
-            iscltx: Field::from_alphanumeric(
-                "Image Classification Text",
-                parse_string_from_bytes(&iscltx)?,
-            ),
-            iscatp: Field::from_alphanumeric(
-                "Image Classification Authority Type",
-                parse_string_from_bytes(&iscatp)?,
-            ),
-            iscaut: Field::from_alphanumeric(
-                "Image Classification Authority",
-                parse_string_from_bytes(&iscaut)?,
-            ),
-            iscrsn: Field::from_alphanumeric(
-                "Image Classification Reason",
-                parse_string_from_bytes(&iscrsn)?,
-            ),
-            issrdt: Field::from_alphanumeric(
-                "Image Security Source Date",
-                parse_string_from_bytes(&issrdt)?,
-            ),
-            isctln: Field::from_alphanumeric(
-                "Image Security Control Number",
-                parse_string_from_bytes(&isctln)?,
-            ),
-            encryp: Field::from_numeric("Encryption", parse_string_from_bytes(&encryp)?),
-            isorce: Field::from_alphanumeric("Image Source", parse_string_from_bytes(&isorce)?),
-            nrows: Field::from_numeric(
-                "Number of Significant Rows in Image",
-                parse_string_from_bytes(&nrows)?,
-            ),
-            ncols: Field::from_numeric(
-                "Number of Significant Columns in Image",
-                parse_string_from_bytes(&ncols)?,
-            ),
-            pvtype: Field::from_alphanumeric("Pixel Value Type", parse_string_from_bytes(&pvtype)?),
-            irep: Field::from_alphanumeric("Image Representation", parse_string_from_bytes(&irep)?),
-            icat: Field::from_alphanumeric("Image Category", parse_string_from_bytes(&icat)?),
-            abpp: Field::from_numeric(
-                "Actual Bits-per-Pixel per Band",
-                parse_string_from_bytes(&abpp)?,
-            ),
-            pjust: Field::from_alphanumeric(
-                "Pixel Justification",
-                parse_string_from_bytes(&pjust)?,
-            ),
-            icords: Field::from_alphanumeric(
-                "Image Coordinate Representation",
-                parse_string_from_bytes(&icords)?,
-            ),
-            igeolo: Field::from_alphanumeric(
-                "Image Geographic Location",
-                parse_string_from_bytes(&igeolo)?,
-            ),
-            nicom: Field::from_numeric(
-                "Number of Image Comments",
-                parse_string_from_bytes(&nicom)?,
-            ),
-            icoms: Field::from_multiple_alphanumeric(
-                "Image comments",
-                icoms
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            ic: Field::from_alphanumeric("Image compression", parse_string_from_bytes(&ic)?),
-            comrat: Field::from_alphanumeric(
-                "Compression Rate Code",
-                parse_string_from_bytes(&comrat)?,
-            ),
-            nbands: Field::from_numeric("Number of Bands", parse_string_from_bytes(&nbands)?),
-            xbands: Field::from_numeric(
-                "Number of Multispectral Bands",
-                parse_string_from_bytes(&xbands)?,
-            ),
-            irepbands: Field::from_multiple_alphanumeric(
-                "Band Representations",
-                irepbands
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            isubcats: Field::from_multiple_alphanumeric(
-                "Band Subcategories",
-                isubcats
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            ifcs: Field::from_multiple_alphanumeric(
-                "Band Image Filter Condition",
-                ifcs.iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            imflts: Field::from_multiple_alphanumeric(
-                "Band Standard Image Code",
-                imflts
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            nlutss: Field::from_multiple_numeric(
-                "Number of LUTs",
-                nlutss
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            neluts: Field::from_multiple_numeric(
-                "Number of LUT entries",
-                neluts
-                    .iter()
-                    .map(parse_string_from_bytes)
-                    .collect::<Result<Vec<String>, _>>()?,
-            ),
-            lutdss: Field::from_nested_numeric(
-                "LUTs",
-                lutdss
-                    .iter()
-                    .filter_map(|l| l.iter().map(|l| parse_string_from_bytes(l).ok()).collect())
-                    .collect(),
-            ),
-
-            isync: Field::from_numeric("Image Sync Code", parse_string_from_bytes(&isync)?),
-            imode: Field::from_alphanumeric("Image Mode", parse_string_from_bytes(&imode)?),
-            nbpr: Field::from_numeric("Number of Blocks per Row", parse_string_from_bytes(&nbpr)?),
-            nbpc: Field::from_numeric(
-                "Number of Blocks per Columns",
-                parse_string_from_bytes(&nbpc)?,
-            ),
-            nppbh: Field::from_numeric(
-                "Number of Pixels per Block Horizontal",
-                parse_string_from_bytes(&nppbh)?,
-            ),
-            nppbv: Field::from_numeric(
-                "Number of Pixels per Block Vertical",
-                parse_string_from_bytes(&nppbv)?,
-            ),
-            nbpp: Field::from_numeric(
-                "Number of Bits per Pixel per Band",
-                parse_string_from_bytes(&nbpp)?,
-            ),
-            idlvl: Field::from_numeric("Image Display Level", parse_string_from_bytes(&idlvl)?),
-            ialvl: Field::from_numeric("Image Attachment Level", parse_string_from_bytes(&ialvl)?),
-            iloc: Field::from_numeric("Image Location", parse_string_from_bytes(&iloc)?),
-            imag: Field::from_alphanumeric("Image Magnification", parse_string_from_bytes(&imag)?),
-            udidl: Field::from_numeric(
-                "User-Defined Image Data Length",
-                parse_string_from_bytes(&udidl)?,
-            ),
-            udofl: Field::from_numeric("User-Defined Overflow", parse_string_from_bytes(&udofl)?),
-            udid: Field::from_alphanumeric(
-                "User-Defined Image Data",
-                parse_string_from_bytes(&udid)?,
-            ),
-            ixshdl: Field::from_numeric(
-                "Image Extended Subheader Length",
-                parse_string_from_bytes(&ixshdl)?,
-            ),
-            ixsofl: Field::from_numeric(
-                "Image Extended Subheader Overflow",
-                parse_string_from_bytes(&ixsofl)?,
-            ),
-            ixshd: Field::from_alphanumeric(
-                "Image Extended Subheader Data",
-                parse_string_from_bytes(&ixshd)
-                    .map_err(|_| "Failed to parse Extended Subheader Data")?,
-            ),
-        })
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for field in [
+            &self.im,
+            &self.iid1,
+            &self.idatim,
+            &self.tgtid,
+            &self.iid2,
+            &self.isclas,
+            &self.isclsy,
+            &self.iscode,
+            &self.isctlh,
+            &self.isrel,
+            &self.isdctp,
+            &self.isdcdt,
+            &self.isdcxm,
+            &self.isdg,
+            &self.isdgdt,
+            &self.iscltx,
+            &self.iscatp,
+            &self.iscaut,
+            &self.iscrsn,
+            &self.issrdt,
+            &self.isctln,
+            &self.encryp,
+            &self.isorce,
+            &self.nrows,
+            &self.ncols,
+            &self.pvtype,
+            &self.irep,
+            &self.icat,
+            &self.abpp,
+            &self.pjust,
+            &self.icords,
+            &self.igeolo,
+            &self.nicom,
+            &self.icoms,
+            &self.ic,
+            &self.comrat,
+            &self.nbands,
+            &self.xbands,
+        ] {
+            bytes.extend(field.bytes());
+        }
+        write_interleaved(
+            &mut bytes,
+            &[
+                &self.irepbands,
+                &self.isubcats,
+                &self.ifcs,
+                &self.imflts,
+                &self.nlutss,
+                &self.neluts,
+                &self.lutdss,
+            ],
+        );
+        for field in [
+            &self.isync,
+            &self.imode,
+            &self.nbpr,
+            &self.nbpc,
+            &self.nppbh,
+            &self.nppbv,
+            &self.nbpp,
+            &self.idlvl,
+            &self.ialvl,
+            &self.iloc,
+            &self.imag,
+            &self.udidl,
+            &self.udofl,
+            &self.udid,
+            &self.ixshdl,
+            &self.ixsofl,
+            &self.ixshd,
+        ] {
+            bytes.extend(field.bytes());
+        }
+        bytes
     }
 }
 
 impl PrettyPrint for ImageSubheader {}
 
+impl HeaderFields for ImageSubheader {
+    const COMPUTED_FIELDS: &'static [&'static str] = COMPUTED;
+}
+
 impl PrettyPrint for ImageSegment {
     fn pretty_print(&self, include_empty_fields: bool) -> String {
         self.sub_header.pretty_print(include_empty_fields)
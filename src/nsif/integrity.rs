@@ -0,0 +1,399 @@
+use super::error::NsifError;
+use super::fileheader::FileHeader;
+use super::imagesegment::{ImageSegment, ImageSubheader};
+use super::rawsegment::RawSegment;
+use super::validation::{validate, Issue, Location, Severity, ValidationReport};
+use super::NSIF;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Where a segment lies in the file according to the file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentExtent {
+    pub location: Location,
+    pub offset: u64,
+    pub declared_subheader_length: u64,
+    /// The number of bytes consumed when parsing the sub header, only known for image segments
+    pub parsed_subheader_length: Option<u64>,
+    pub declared_data_length: u64,
+    /// The number of bytes of the segment that are present in the file
+    pub available_length: u64,
+}
+
+impl SegmentExtent {
+    /// The length of the sub header, preferring the parsed over the declared length
+    pub fn subheader_length(&self) -> u64 {
+        self.parsed_subheader_length
+            .unwrap_or(self.declared_subheader_length)
+    }
+
+    pub fn length(&self) -> u64 {
+        self.subheader_length() + self.declared_data_length
+    }
+
+    pub fn missing_length(&self) -> u64 {
+        self.length() - self.available_length
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub file_length: u64,
+    pub declared_file_length: Option<u64>,
+    pub declared_header_length: Option<u64>,
+    pub parsed_header_length: u64,
+    pub segments: Vec<SegmentExtent>,
+}
+
+impl IntegrityReport {
+    /// The length of the file according to the parsed headers and the data lengths
+    pub fn expected_file_length(&self) -> u64 {
+        self.parsed_header_length + self.segments.iter().map(SegmentExtent::length).sum::<u64>()
+    }
+
+    pub fn missing_length(&self) -> u64 {
+        self.expected_file_length().saturating_sub(self.file_length)
+    }
+
+    pub fn trailing_length(&self) -> u64 {
+        self.file_length.saturating_sub(self.expected_file_length())
+    }
+
+    pub fn is_intact(&self) -> bool {
+        self.issues().is_empty()
+    }
+
+    pub fn issues(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        let mut push = |location, mnemonic, message| {
+            issues.push(Issue {
+                severity: Severity::Error,
+                location,
+                mnemonic,
+                message,
+            })
+        };
+
+        match self.declared_file_length {
+            Some(length) if length == self.file_length => {}
+            Some(length) => push(
+                Location::FileHeader,
+                Some("FL"),
+                format!(
+                    "declares {length} bytes but the file is {} bytes long",
+                    self.file_length
+                ),
+            ),
+            None => push(
+                Location::FileHeader,
+                Some("FL"),
+                String::from("is not a number"),
+            ),
+        }
+        match self.declared_header_length {
+            Some(length) if length == self.parsed_header_length => {}
+            Some(length) => push(
+                Location::FileHeader,
+                Some("HL"),
+                format!(
+                    "declares {length} bytes but the header is {} bytes long",
+                    self.parsed_header_length
+                ),
+            ),
+            None => push(
+                Location::FileHeader,
+                Some("HL"),
+                String::from("is not a number"),
+            ),
+        }
+
+        for segment in &self.segments {
+            let (subheader_mnemonic, data_mnemonic) = length_mnemonics(segment.location);
+            if let Some(parsed) = segment.parsed_subheader_length {
+                if parsed != segment.declared_subheader_length {
+                    push(
+                        segment.location,
+                        Some(subheader_mnemonic),
+                        format!(
+                            "declares {} bytes but the sub header is {parsed} bytes long",
+                            segment.declared_subheader_length
+                        ),
+                    );
+                }
+            }
+            if segment.missing_length() > 0 {
+                push(
+                    segment.location,
+                    Some(data_mnemonic),
+                    format!(
+                        "the file is truncated, {} of {} bytes are missing",
+                        segment.missing_length(),
+                        segment.length()
+                    ),
+                );
+            }
+        }
+
+        if self.trailing_length() > 0 {
+            push(
+                Location::FileHeader,
+                None,
+                format!(
+                    "the file is padded, {} bytes follow the last segment",
+                    self.trailing_length()
+                ),
+            );
+        }
+        issues
+    }
+}
+
+/// Compares the length fields with the file. Segments are located by the parsed rather than
+/// the declared sub header lengths, so a single wrong field does not shift the rest.
+pub fn check_integrity<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<IntegrityReport, Box<dyn std::error::Error>> {
+    let file_length = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let file_header = FileHeader::parse_unchecked(reader)?;
+    let parsed_header_length = reader.stream_position()?;
+
+    let mut offset = parsed_header_length;
+    let mut segments = Vec::new();
+    for (location, declared_subheader_length, declared_data_length) in
+        declared_segments(&file_header)?
+    {
+        let parsed_subheader_length = match location {
+            Location::ImageSegment(_) => {
+                reader.seek(SeekFrom::Start(offset))?;
+                match ImageSubheader::parse(reader) {
+                    Ok(_) => Some(reader.stream_position()? - offset),
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+        let mut segment = SegmentExtent {
+            location,
+            offset,
+            declared_subheader_length,
+            parsed_subheader_length,
+            declared_data_length,
+            available_length: 0,
+        };
+        segment.available_length = segment.length().min(file_length.saturating_sub(offset));
+        offset += segment.length();
+        segments.push(segment);
+    }
+
+    Ok(IntegrityReport {
+        file_length,
+        declared_file_length: file_header.file_length().ok(),
+        declared_header_length: file_header.header_length().ok(),
+        parsed_header_length,
+        segments,
+    })
+}
+
+/// Validates a file and adds the issues of [`check_integrity`], which are still reported
+/// along with the parse error if the segments cannot be parsed
+pub fn validate_with_integrity<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<ValidationReport, Box<dyn std::error::Error>> {
+    let integrity = check_integrity(reader)?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut report = match NSIF::parse(&mut *reader) {
+        Ok(nsif) => validate(&nsif),
+        Err(e) => {
+            reader.seek(SeekFrom::Start(0))?;
+            let file_header = FileHeader::parse_unchecked(reader)?;
+            ValidationReport {
+                issues: vec![Issue {
+                    severity: Severity::Error,
+                    location: Location::FileHeader,
+                    mnemonic: None,
+                    message: e.to_string(),
+                }],
+                declared_complexity_level: file_header.complexity_level().ok(),
+                required_complexity_level: None,
+            }
+        }
+    };
+    report.issues.extend(integrity.issues());
+    Ok(report)
+}
+
+/// A length field whose value was changed by [`repair`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LengthChange {
+    pub location: Location,
+    pub mnemonic: &'static str,
+    pub declared: Option<u64>,
+    pub repaired: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Repair {
+    pub nsif: NSIF,
+    pub changes: Vec<LengthChange>,
+    /// Segments dropped because their sub header is cut off by the end of the file
+    pub removed_segments: Vec<Location>,
+    /// The number of bytes following the last segment that were dropped
+    pub removed_trailing_length: u64,
+}
+
+/// Rebuilds a file whose length fields disagree with its content. Truncated data is shortened
+/// and segments without a complete sub header are dropped. Trailing bytes are dropped unless
+/// `keep_trailing` is set and there is a last segment to append them to.
+pub fn repair<R: Read + Seek>(
+    reader: &mut R,
+    keep_trailing: bool,
+) -> Result<Repair, Box<dyn std::error::Error>> {
+    let report = check_integrity(reader)?;
+    reader.seek(SeekFrom::Start(0))?;
+    let file_header = FileHeader::parse_unchecked(reader)?;
+    let mut nsif = NSIF {
+        file_header,
+        image_segments: Vec::new(),
+        graphic_segments: Vec::new(),
+        text_segments: Vec::new(),
+        data_extension_segments: Vec::new(),
+        reserved_extension_segments: Vec::new(),
+    };
+
+    let trailing_length = report.trailing_length();
+    let mut removed_segments = Vec::new();
+    let mut kept = Vec::new();
+    let mut trailing_kept = false;
+    for (i, extent) in report.segments.iter().enumerate() {
+        let subheader_length = extent.subheader_length();
+        if extent.available_length < subheader_length {
+            removed_segments.push(extent.location);
+            continue;
+        }
+        let mut data_length = extent.available_length - subheader_length;
+        if keep_trailing && i == report.segments.len() - 1 {
+            data_length += trailing_length;
+            trailing_kept = true;
+        }
+        let raw_segments = match extent.location {
+            Location::ImageSegment(position) => {
+                nsif.image_segments.push(ImageSegment::parse(
+                    reader,
+                    position,
+                    extent.offset,
+                    subheader_length,
+                    data_length,
+                    &mut Vec::new(),
+                )?);
+                kept.push(extent);
+                continue;
+            }
+            Location::GraphicSegment(_) => &mut nsif.graphic_segments,
+            Location::TextSegment(_) => &mut nsif.text_segments,
+            Location::DataExtensionSegment(_) => &mut nsif.data_extension_segments,
+            Location::ReservedExtensionSegment(_) => &mut nsif.reserved_extension_segments,
+            Location::FileHeader => unreachable!("the file header is not a segment"),
+        };
+        raw_segments.push(RawSegment::parse(
+            reader,
+            extent.offset,
+            subheader_length,
+            data_length,
+        )?);
+        kept.push(extent);
+    }
+    nsif.update_lengths()?;
+
+    let file_header = &nsif.file_header;
+    let mut changes = Vec::new();
+    let mut compare = |location, mnemonic, declared: Option<u64>, repaired: u64| {
+        if declared != Some(repaired) {
+            changes.push(LengthChange {
+                location,
+                mnemonic,
+                declared,
+                repaired,
+            });
+        }
+    };
+    compare(
+        Location::FileHeader,
+        "FL",
+        report.declared_file_length,
+        file_header.file_length()?,
+    );
+    compare(
+        Location::FileHeader,
+        "HL",
+        report.declared_header_length,
+        file_header.header_length()?,
+    );
+    for (extent, (_, subheader_length, data_length)) in
+        kept.into_iter().zip(declared_segments(file_header)?)
+    {
+        let (subheader_mnemonic, data_mnemonic) = length_mnemonics(extent.location);
+        compare(
+            extent.location,
+            subheader_mnemonic,
+            Some(extent.declared_subheader_length),
+            subheader_length,
+        );
+        compare(
+            extent.location,
+            data_mnemonic,
+            Some(extent.declared_data_length),
+            data_length,
+        );
+    }
+
+    Ok(Repair {
+        nsif,
+        changes,
+        removed_segments,
+        removed_trailing_length: if trailing_kept { 0 } else { trailing_length },
+    })
+}
+
+pub(crate) fn declared_segments(
+    file_header: &FileHeader,
+) -> Result<Vec<(Location, u64, u64)>, NsifError> {
+    let mut segments = file_header
+        .image_subheader_lengths()?
+        .into_iter()
+        .zip(file_header.image_segment_lengths()?)
+        .enumerate()
+        .map(|(i, (subheader_length, data_length))| {
+            (Location::ImageSegment(i + 1), subheader_length, data_length)
+        })
+        .collect::<Vec<_>>();
+    let mut extend = |location: fn(usize) -> Location, lengths: Vec<(u64, u64)>| {
+        segments.extend(lengths.into_iter().enumerate().map(
+            |(i, (subheader_length, data_length))| (location(i + 1), subheader_length, data_length),
+        ))
+    };
+    extend(
+        Location::GraphicSegment,
+        file_header.graphic_segment_lengths()?,
+    );
+    extend(Location::TextSegment, file_header.text_segment_lengths()?);
+    extend(
+        Location::DataExtensionSegment,
+        file_header.data_extension_segment_lengths()?,
+    );
+    extend(
+        Location::ReservedExtensionSegment,
+        file_header.reserved_extension_segment_lengths()?,
+    );
+    Ok(segments)
+}
+
+fn length_mnemonics(location: Location) -> (&'static str, &'static str) {
+    match location {
+        Location::FileHeader => ("HL", "FL"),
+        Location::ImageSegment(_) => ("LISH", "LI"),
+        Location::GraphicSegment(_) => ("LSSH", "LS"),
+        Location::TextSegment(_) => ("LTSH", "LT"),
+        Location::DataExtensionSegment(_) => ("LDSH", "LD"),
+        Location::ReservedExtensionSegment(_) => ("LRESH", "LRE"),
+    }
+}
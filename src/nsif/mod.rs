@@ -1,32 +1,47 @@
-use self::field::Value;
+use crate::nsif::field::IsEmpty;
 use bevy_reflect::Reflect;
 use bevy_reflect::Struct;
+use error::NsifError;
 use field::Field;
 use fileheader::FileHeader;
-use imagesegment::ImageSegment;
+use imagesegment::{ImageSegment, ImageSubheader};
+use integrity::declared_segments;
+use rawsegment::RawSegment;
 use std::collections::BTreeMap;
-use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::num::ParseIntError;
-use std::string::FromUtf8Error;
-use crate::nsif::field::IsEmpty;
+use validation::{required_complexity_level, Location};
 
+pub mod builder;
+pub mod chip;
+pub mod codes;
+pub mod compression;
+pub mod conversion;
+pub mod datetime;
+pub mod definitions;
 pub mod error;
 pub mod export;
 pub mod field;
 pub mod fileheader;
+pub mod geolocation;
 pub mod imagesegment;
+pub mod integrity;
+pub mod rawsegment;
+pub mod redaction;
+pub mod sanitize;
+pub mod security;
+pub mod segments;
+pub mod tre;
+pub mod validation;
 
-#[derive(Debug, Reflect)]
+#[derive(Debug, Clone, Reflect)]
 pub struct NSIF {
     pub file_header: FileHeader,
     pub image_segments: Vec<ImageSegment>,
-    /*
-    graphic_segments: Vec<GraphicSegment>,
-    reserved_segments: Vec<ReservedSegment>,
-    text_segments: Vec<TextSegment>,
-    data_extension_segments: Vec<DataExtensionSegment>,
-    reserved_extension_segments: Vec<ReservedExtensionSegment>,
-    */
+    pub graphic_segments: Vec<RawSegment>,
+    pub text_segments: Vec<RawSegment>,
+    pub data_extension_segments: Vec<RawSegment>,
+    pub reserved_extension_segments: Vec<RawSegment>,
 }
 
 pub trait PrettyPrint {
@@ -43,8 +58,7 @@ pub trait PrettyPrint {
             .for_each(|f| {
                 if let Some(field) = f {
                     let line = &format!("{}", field);
-                    if !line.trim().is_empty() && (!exclude_empty_fields || !field.is_empty())
-                    {
+                    if !line.trim().is_empty() && (!exclude_empty_fields || !field.is_empty()) {
                         pretty.push_str(&format!("    {}\n", line));
                     }
                 }
@@ -54,49 +68,244 @@ pub trait PrettyPrint {
     }
 }
 
+/// Access to the fields of a header or sub header by their mnemonic
+pub trait HeaderFields: Struct + Sized {
+    /// Mnemonics of the fields that are recomputed on write and therefore cannot be set
+    const COMPUTED_FIELDS: &'static [&'static str];
 
-/*
-#[derive(Debug)]
-struct GraphicSegment {}
-
-#[derive(Debug)]
-struct ReservedSegment {}
+    fn find_field(&self, mnemonic: &str) -> Option<&Field> {
+        self.iter_fields()
+            .filter_map(|field| field.try_downcast_ref::<Field>())
+            .find(|field| field.mnemonic() == mnemonic)
+    }
 
-#[derive(Debug)]
-struct TextSegment {}
+    fn find_field_mut(&mut self, mnemonic: &str) -> Option<&mut Field> {
+        let index = (0..self.field_len()).find(|&i| {
+            self.field_at(i)
+                .and_then(|field| field.try_downcast_ref::<Field>())
+                .is_some_and(|field| field.mnemonic() == mnemonic)
+        })?;
+        self.field_at_mut(index)?.try_downcast_mut::<Field>()
+    }
 
-#[derive(Debug)]
-struct DataExtensionSegment {}
+    /// Sets a single-valued field, see [`Field::set`]. Length fields that depend on
+    /// the new value are recomputed when the file is written.
+    fn set_field(&mut self, mnemonic: &str, value: &str) -> Result<(), NsifError> {
+        let field = self
+            .find_field_mut(mnemonic)
+            .ok_or_else(|| NsifError::UnknownField(mnemonic.to_owned()))?;
+        if Self::COMPUTED_FIELDS.contains(&mnemonic) {
+            return Err(NsifError::ComputedField(field.mnemonic()));
+        }
+        field.set(value)
+    }
+}
 
-#[derive(Debug)]
-struct ReservedExtensionSegment {}
-*/
 impl NSIF {
-    pub fn parse(file: &File) -> Result<Self, Box<dyn std::error::Error>> {
-        let file_header = FileHeader::parse(file)?;
+    /// Parses an NSIF file. Segments are located using the offsets implied by HL, LISH and LI
+    /// rather than by wherever the previous parse step happened to stop. Disagreements between
+    /// these lengths and the bytes consumed are tolerated, see [`Self::parse_with_diagnostics`].
+    pub fn parse<R: Read + Seek>(reader: R) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::parse_with_diagnostics(reader).map(|(nsif, _)| nsif)
+    }
+
+    /// Like [`Self::parse`], but also returns a [`NsifError::LengthMismatch`] for every header
+    /// and image sub header whose declared length (HL, LISH) differs from the bytes consumed.
+    /// Fails only if the declared segments extend beyond the end of the file.
+    pub fn parse_with_diagnostics<R: Read + Seek>(
+        mut reader: R,
+    ) -> Result<(Self, Vec<NsifError>), Box<dyn std::error::Error>> {
+        let mut diagnostics = Vec::new();
+        let start = reader.stream_position()?;
+        let file_header = FileHeader::parse_unchecked(&mut reader)?;
+        let header_length = file_header.header_length()?;
+        let consumed = reader.stream_position()? - start;
+        if consumed != header_length {
+            diagnostics.push(NsifError::LengthMismatch {
+                field: String::from("HL"),
+                declared: header_length,
+                consumed,
+            });
+        }
+        let expected = header_length
+            + declared_segments(&file_header)?
+                .iter()
+                .map(|(_, subheader_length, data_length)| subheader_length + data_length)
+                .sum::<u64>();
+        let actual = reader.seek(SeekFrom::End(0))?;
+        if actual < expected {
+            return Err(Box::new(NsifError::TruncatedFile { expected, actual }));
+        }
+
         let mut image_segments = Vec::new();
+        let mut offset = header_length;
 
-        if let (
-            Value::MultipleNumeric(image_segment_subheader_lengths),
-            Value::MultipleNumeric(image_segment_lengths),
-        ) = (&file_header.lishs.value, &file_header.lis.value)
+        for (i, (subheader_length, segment_length)) in file_header
+            .image_subheader_lengths()?
+            .into_iter()
+            .zip(file_header.image_segment_lengths()?)
+            .enumerate()
         {
-            for (subheader_length, segment_length) in image_segment_subheader_lengths
-                .iter()
-                .zip(image_segment_lengths.iter())
-            {
-                image_segments.push(ImageSegment::parse(
-                    file,
-                    parse_number_from_string(&subheader_length.value)?,
-                    parse_number_from_string(&segment_length.value)?,
-                )?);
-            }
+            image_segments.push(ImageSegment::parse(
+                &mut reader,
+                i + 1,
+                offset,
+                subheader_length,
+                segment_length,
+                &mut diagnostics,
+            )?);
+            offset += subheader_length + segment_length;
         }
 
-        Ok(NSIF {
+        let mut parse_raw_segments = |lengths: Vec<(u64, u64)>| {
+            lengths
+                .into_iter()
+                .map(|(subheader_length, segment_length)| {
+                    let segment =
+                        RawSegment::parse(&mut reader, offset, subheader_length, segment_length);
+                    offset += subheader_length + segment_length;
+                    segment
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let graphic_segments = parse_raw_segments(file_header.graphic_segment_lengths()?)?;
+        let text_segments = parse_raw_segments(file_header.text_segment_lengths()?)?;
+        let data_extension_segments =
+            parse_raw_segments(file_header.data_extension_segment_lengths()?)?;
+        let reserved_extension_segments =
+            parse_raw_segments(file_header.reserved_extension_segment_lengths()?)?;
+
+        let nsif = NSIF {
             file_header,
             image_segments,
-        })
+            graphic_segments,
+            text_segments,
+            data_extension_segments,
+            reserved_extension_segments,
+        };
+        Ok((nsif, diagnostics))
+    }
+
+    /// Recomputes the length fields of the file header and the image sub headers, which
+    /// [`NSIF::write`] otherwise does on the fly
+    pub fn update_lengths(&mut self) -> Result<(), NsifError> {
+        let (file_header, image_subheaders) = self.updated_headers()?;
+        self.file_header = file_header;
+        for (segment, sub_header) in self.image_segments.iter_mut().zip(image_subheaders) {
+            segment.sub_header = sub_header;
+        }
+        Ok(())
+    }
+
+    /// Recomputes the length fields like [`Self::update_lengths`] and sets CLEVEL to the lowest
+    /// complexity level the file satisfies, e.g. after segments were added, removed or encoded
+    pub fn update_lengths_and_complexity_level(&mut self) -> Result<(), NsifError> {
+        self.update_lengths()?;
+        let complexity_level = required_complexity_level(self);
+        self.file_header
+            .clevel
+            .set_number(u64::from(complexity_level))
+    }
+
+    /// Writes the file, recomputing the file header's length fields (FL, HL, the segment
+    /// length tables, ...) from the actual segments. Writing an unmodified file reproduces
+    /// it byte for byte.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Box<dyn std::error::Error>> {
+        let (file_header, image_subheaders) = self.updated_headers()?;
+        writer.write_all(&file_header.to_bytes())?;
+        for (sub_header, segment) in image_subheaders.iter().zip(&self.image_segments) {
+            writer.write_all(&sub_header.to_bytes())?;
+            writer.write_all(&segment.data)?;
+        }
+        for segment in self
+            .graphic_segments
+            .iter()
+            .chain(&self.text_segments)
+            .chain(&self.data_extension_segments)
+            .chain(&self.reserved_extension_segments)
+        {
+            writer.write_all(&segment.sub_header)?;
+            writer.write_all(&segment.data)?;
+        }
+        Ok(())
+    }
+
+    fn updated_headers(&self) -> Result<(FileHeader, Vec<ImageSubheader>), NsifError> {
+        let image_subheaders = self
+            .image_segments
+            .iter()
+            .map(|segment| {
+                let mut sub_header = segment.sub_header.clone();
+                sub_header.update_lengths()?;
+                Ok(sub_header)
+            })
+            .collect::<Result<Vec<_>, NsifError>>()?;
+        let image_segment_lengths = image_subheaders
+            .iter()
+            .zip(&self.image_segments)
+            .map(|(sub_header, segment)| {
+                (
+                    sub_header.to_bytes().len() as u64,
+                    segment.data.len() as u64,
+                )
+            })
+            .collect::<Vec<_>>();
+        let raw_segment_lengths =
+            |segments: &[RawSegment]| segments.iter().map(RawSegment::lengths).collect::<Vec<_>>();
+
+        let mut file_header = self.file_header.clone();
+        file_header.update_lengths(
+            &image_segment_lengths,
+            &raw_segment_lengths(&self.graphic_segments),
+            &raw_segment_lengths(&self.text_segments),
+            &raw_segment_lengths(&self.data_extension_segments),
+            &raw_segment_lengths(&self.reserved_extension_segments),
+        )?;
+        Ok((file_header, image_subheaders))
+    }
+
+    /// Sets a field by its mnemonic. With a segment (counted from 1), fields of its image sub
+    /// header are set there, also if the file header has a field of the same mnemonic (e.g.
+    /// ENCRYP), and all others in the file header. Without a segment, fields of the file header
+    /// are set there and all others in the first image sub header.
+    pub fn set_header_field(
+        &mut self,
+        segment: Option<usize>,
+        mnemonic: &str,
+        value: &str,
+    ) -> Result<(), NsifError> {
+        let position = segment.unwrap_or(1);
+        let missing = || NsifError::MissingSegment(Location::ImageSegment(position));
+        let in_file_header = self.file_header.find_field(mnemonic).is_some();
+        if segment.is_none() && in_file_header {
+            return self.file_header.set_field(mnemonic, value);
+        }
+        let sub_header = &mut position
+            .checked_sub(1)
+            .and_then(|i| self.image_segments.get_mut(i))
+            .ok_or_else(missing)?
+            .sub_header;
+        if sub_header.find_field(mnemonic).is_none() && in_file_header {
+            return self.file_header.set_field(mnemonic, value);
+        }
+        sub_header.set_field(mnemonic, value)
+    }
+
+    /// The fields whose values differ between two versions of a file as (header, before,
+    /// after), e.g. to show the effect of an edit including the recomputed lengths
+    pub fn changed_fields<'a>(&'a self, after: &'a NSIF) -> Vec<(String, &'a Field, &'a Field)> {
+        self.fields()
+            .into_iter()
+            .zip(after.fields().into_values())
+            .flat_map(|((header, before_fields), after_fields)| {
+                before_fields
+                    .into_iter()
+                    .zip(after_fields)
+                    .filter(|(before, after)| before.bytes() != after.bytes())
+                    .map(move |(before, after)| (header.clone(), before, after))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
     }
 
     pub fn fields(&self) -> BTreeMap<String, Vec<&Field>> {
@@ -127,16 +336,46 @@ impl PrettyPrint for NSIF {
         pretty.push_str("File Header:\n");
         pretty.push_str(self.file_header.pretty_print(include_empty_fields).as_str());
         for (i, image_segment) in self.image_segments.iter().enumerate() {
-            pretty.push_str("\n");
-            pretty.push_str(format!("Image Segment {}:\n", i+1).as_str());
+            pretty.push('\n');
+            pretty.push_str(format!("Image Segment {}:\n", i + 1).as_str());
             pretty.push_str(image_segment.pretty_print(include_empty_fields).as_str());
         }
         pretty
     }
 }
 
-pub fn parse_string_from_bytes(vec: &Vec<u8>) -> Result<String, FromUtf8Error> {
-    String::from_utf8(vec.clone())
+/// Decodes BCS-A/ECS-A bytes. Both character sets are subsets of ISO 8859-1, whose code points
+/// map one-to-one onto Unicode, so decoding never fails and never loses information.
+pub fn parse_string_from_bytes(vec: &[u8]) -> String {
+    vec.iter().map(|&byte| byte as char).collect()
+}
+
+/// Encodes a string as ISO 8859-1, the inverse of [`parse_string_from_bytes`]
+pub fn encode_string_to_bytes(s: &str) -> Result<Vec<u8>, NsifError> {
+    s.chars()
+        .map(|c| u8::try_from(c).map_err(|_| NsifError::UnencodableCharacter(c)))
+        .collect()
+}
+
+/// Checks whether the bytes belong to the Basic Character Set (BCS-A),
+/// i.e. printable ASCII plus the control characters LF, FF and CR
+pub fn is_bcs(vec: &[u8]) -> bool {
+    vec.iter()
+        .all(|byte| matches!(byte, 0x20..=0x7E | 0x0A | 0x0C | 0x0D))
+}
+
+/// Checks whether the bytes belong to the numeric Basic Character Set (BCS-N),
+/// i.e. the digits plus the signs, the decimal point and the slash
+pub fn is_bcs_n(vec: &[u8]) -> bool {
+    vec.iter()
+        .all(|byte| matches!(byte, b'0'..=b'9' | b'+' | b'-' | b'.' | b'/'))
+}
+
+/// Checks whether the bytes belong to the Extended Character Set (ECS-A),
+/// i.e. BCS-A extended by the printable upper half of ISO 8859-1
+pub fn is_ecs(vec: &[u8]) -> bool {
+    vec.iter()
+        .all(|byte| matches!(byte, 0x20..=0x7E | 0xA0..=0xFF | 0x0A | 0x0C | 0x0D))
 }
 
 pub fn parse_unsigned_integers_from_byte(vec: &[u8]) -> String {
@@ -146,8 +385,8 @@ pub fn parse_unsigned_integers_from_byte(vec: &[u8]) -> String {
         .join(" ")
 }
 
-pub fn parse_number_from_bytes(vec: &Vec<u8>) -> Result<i32, Box<dyn std::error::Error>> {
-    let s = parse_string_from_bytes(vec)?;
+pub fn parse_number_from_bytes(vec: &[u8]) -> Result<i32, Box<dyn std::error::Error>> {
+    let s = parse_string_from_bytes(vec);
     parse_number_from_string(&s).map_err(Into::into)
 }
 
@@ -0,0 +1,31 @@
+use bevy_reflect::Reflect;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A segment whose sub header is not interpreted (graphic, text, data extension and
+/// reserved extension segments). It is kept verbatim so that it can be written back.
+#[derive(Debug, Clone, Reflect)]
+pub struct RawSegment {
+    pub sub_header: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl RawSegment {
+    pub fn parse<R: Read + Seek>(
+        reader: &mut R,
+        offset: u64,
+        subheader_length: u64,
+        segment_length: u64,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut sub_header = vec![0; subheader_length as usize];
+        reader.read_exact(&mut sub_header)?;
+        let mut data = vec![0; segment_length as usize];
+        reader.read_exact(&mut data)?;
+        Ok(RawSegment { sub_header, data })
+    }
+
+    /// The lengths of the sub header and the data as recorded in the file header
+    pub fn lengths(&self) -> (u64, u64) {
+        (self.sub_header.len() as u64, self.data.len() as u64)
+    }
+}
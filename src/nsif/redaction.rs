@@ -0,0 +1,171 @@
+//! Redaction of pixel regions of an image segment. The pixels are overwritten with zeros and
+//! the segment is encoded again, so that the redacted content is not left in the file.
+
+use super::error::NsifError;
+use super::geolocation::ground_to_image;
+use super::imagesegment::ImageSubheader;
+use super::validation::Location;
+use super::NSIF;
+use std::str::FromStr;
+
+/// A region of an image to redact
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    /// A rectangle of pixels given by its top left pixel and size
+    Rectangle {
+        row: u32,
+        column: u32,
+        height: u32,
+        width: u32,
+    },
+    /// A polygon with vertices in (row, column) image coordinates, where pixel (r, c) covers
+    /// r to r + 1 and c to c + 1
+    Polygon(Vec<(f64, f64)>),
+    /// A polygon with vertices as (latitude, longitude) in decimal degrees, located in the
+    /// image through its corner coordinates (IGEOLO)
+    GeoPolygon(Vec<(f64, f64)>),
+}
+
+impl Region {
+    /// The rectangle between two (latitude, longitude) corners as a polygon
+    pub fn geo_rectangle(first: (f64, f64), second: (f64, f64)) -> Self {
+        Region::GeoPolygon(vec![
+            (first.0, first.1),
+            (first.0, second.1),
+            (second.0, second.1),
+            (second.0, first.1),
+        ])
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parses a rectangle as ROW,COL,HEIGHT,WIDTH or a polygon as space separated ROW,COL
+    /// vertices
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split(',')
+            .map(|number| number.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok([row, column, height, width]) = numbers.as_deref() {
+            return Ok(Region::Rectangle {
+                row: *row,
+                column: *column,
+                height: *height,
+                width: *width,
+            });
+        }
+        parse_points(s).map(Region::Polygon)
+    }
+}
+
+/// Parses at least three space separated points of two comma separated numbers
+pub fn parse_points(s: &str) -> Result<Vec<(f64, f64)>, String> {
+    let points = s
+        .split_whitespace()
+        .map(|point| {
+            let (first, second) = point.split_once(',').ok_or(())?;
+            Ok((
+                first.parse::<f64>().map_err(|_| ())?,
+                second.parse::<f64>().map_err(|_| ())?,
+            ))
+        })
+        .collect::<Result<Vec<_>, ()>>()
+        .map_err(|_| format!("expected points as 'A,B A,B A,B ...' but got '{s}'"))?;
+    if points.len() < 3 {
+        return Err(format!(
+            "a polygon needs at least three points but got '{s}'"
+        ));
+    }
+    Ok(points)
+}
+
+/// Overwrites the pixels of the regions in an image segment (counted from 1) with zeros in
+/// every band, encodes the segment again in its original compression and recomputes the
+/// lengths. Returns the number of redacted pixels.
+pub fn redact(
+    nsif: &mut NSIF,
+    segment: usize,
+    regions: &[Region],
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let image_segment = segment
+        .checked_sub(1)
+        .and_then(|i| nsif.image_segments.get_mut(i))
+        .ok_or(NsifError::MissingSegment(Location::ImageSegment(segment)))?;
+    let mask = mask(&image_segment.sub_header, regions)?;
+    let redacted = mask.iter().filter(|&&masked| masked).count() as u64;
+    if redacted == 0 {
+        return Ok(0);
+    }
+
+    let mut pixels = image_segment.pixels()?;
+    let pixel_length = pixels.len() / mask.len();
+    for (pixel, _) in pixels
+        .chunks_exact_mut(pixel_length)
+        .zip(&mask)
+        .filter(|(_, &masked)| masked)
+    {
+        pixel.fill(0);
+    }
+    image_segment.set_pixels(&pixels)?;
+    nsif.update_lengths()?;
+    Ok(redacted)
+}
+
+/// Which pixels of the image lie within any of the regions, row by row
+fn mask(sub_header: &ImageSubheader, regions: &[Region]) -> Result<Vec<bool>, NsifError> {
+    let (rows, columns) = (
+        sub_header.num_rows()? as usize,
+        sub_header.num_cols()? as usize,
+    );
+    let mut mask = vec![false; rows * columns];
+    for region in regions {
+        let polygon = match region {
+            Region::Rectangle {
+                row,
+                column,
+                height,
+                width,
+            } => {
+                let (row, column) = (*row as usize, *column as usize);
+                for y in row.min(rows)..(row + *height as usize).min(rows) {
+                    mask[y * columns + column.min(columns)
+                        ..y * columns + (column + *width as usize).min(columns)]
+                        .fill(true);
+                }
+                continue;
+            }
+            Region::Polygon(points) => points.clone(),
+            Region::GeoPolygon(points) => {
+                let to_image = ground_to_image(sub_header)?;
+                points.iter().map(|&point| to_image.apply(point)).collect()
+            }
+        };
+        fill_polygon(&mut mask, rows, columns, &polygon);
+    }
+    Ok(mask)
+}
+
+/// Marks the pixels whose centre lies within the polygon, using the even-odd rule
+fn fill_polygon(mask: &mut [bool], rows: usize, columns: usize, polygon: &[(f64, f64)]) {
+    for row in 0..rows {
+        let y = row as f64 + 0.5;
+        let mut crossings = polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .filter(|((y1, _), (y2, _))| (*y1 <= y) != (*y2 <= y))
+            .map(|((y1, x1), (y2, x2))| x1 + (y - y1) / (y2 - y1) * (x2 - x1))
+            .collect::<Vec<_>>();
+        crossings.sort_by(f64::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            // The pixels from the first centre at or right of the start to the last centre left
+            // of the end
+            let first = (span[0] - 0.5).ceil().clamp(0.0, columns as f64) as usize;
+            let end = (span[1] - 0.5).ceil().clamp(0.0, columns as f64) as usize;
+            if first < end {
+                mask[row * columns + first..row * columns + end].fill(true);
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Removal of originator details, user-defined data, TREs and comments before a file is
+//! released.
+
+use super::error::NsifError;
+use super::field::{Field, FieldKind};
+use super::fileheader::FileHeader;
+use super::imagesegment::ImageSubheader;
+use super::rawsegment::RawSegment;
+use super::tre::{parse_tres, set_tres, Tre};
+use super::validation::Location;
+use super::{parse_number_from_bytes, parse_string_from_bytes, HeaderFields, NSIF};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Action {
+    Keep,
+    /// Removes the values of variable-length and repeated fields and TREs. Fields of fixed
+    /// length are blanked.
+    Strip,
+    /// Overwrites the values with spaces (or zeros for numeric and binary fields)
+    Blank,
+}
+
+/// What to do with each field and TRE. Items that are not listed are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Actions by field mnemonic, applied to the file header and every image sub header
+    pub fields: BTreeMap<String, Action>,
+    /// Actions by TRE tag, applied to the TREs of every header and of the TRE overflow segments
+    pub tres: BTreeMap<String, Action>,
+}
+
+impl SanitizePolicy {
+    /// A policy that keeps everything
+    pub fn empty() -> Self {
+        SanitizePolicy {
+            fields: BTreeMap::new(),
+            tres: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the action for a field mnemonic or for a TRE tag given as TRE:TAG
+    pub fn set(&mut self, item: &str, action: Action) {
+        let item = item.trim().to_uppercase();
+        match item.strip_prefix("TRE:") {
+            Some(tag) => self.tres.insert(tag.to_owned(), action),
+            None => self.fields.insert(item, action),
+        };
+    }
+}
+
+impl Default for SanitizePolicy {
+    /// Blanks the originator details and strips user-defined data and image comments
+    fn default() -> Self {
+        let mut policy = SanitizePolicy::empty();
+        policy.set("ONAME", Action::Blank);
+        policy.set("OPHONE", Action::Blank);
+        policy.set("UDHD", Action::Strip);
+        policy.set("UDID", Action::Strip);
+        policy.set("ICOM", Action::Strip);
+        policy
+    }
+}
+
+impl FromStr for SanitizePolicy {
+    type Err = String;
+
+    /// Parses one ITEM=ACTION assignment per line, e.g. `OPHONE=blank` or `TRE:PIAIMC=strip`.
+    /// Empty lines and lines starting with # are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = SanitizePolicy::empty();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (item, action) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Line {}: expected ITEM=ACTION", number + 1))?;
+            let action = Action::from_str(action.trim()).map_err(|_| {
+                format!(
+                    "Line {}: expected keep, strip or blank but got '{}'",
+                    number + 1,
+                    action.trim()
+                )
+            })?;
+            policy.set(item, action);
+        }
+        Ok(policy)
+    }
+}
+
+/// An item removed or blanked by [`sanitize`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Removal {
+    pub location: Location,
+    /// The mnemonic of the field or TRE:TAG
+    pub item: String,
+    /// The action that was applied, which is [`Action::Blank`] for stripped fixed-length fields
+    pub action: Action,
+    /// The previous value
+    pub value: String,
+}
+
+impl Display for Removal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self.action {
+            Action::Keep => "kept",
+            Action::Strip => "stripped",
+            Action::Blank => "blanked",
+        };
+        write!(
+            f,
+            "{}: {} {action}: {}",
+            self.location, self.item, self.value
+        )
+    }
+}
+
+/// The offsets of the extended sub header data length (SXSHDL, TXSHDL) in graphic and text sub
+/// headers, which is followed by the overflow field and the TREs
+const GRAPHIC_EXTENDED_DATA: usize = 253;
+const TEXT_EXTENDED_DATA: usize = 277;
+
+/// Applies the policy to the file header and every image sub header, and its TRE actions to
+/// the graphic and text sub headers and the TRE overflow segments. The lengths are recomputed
+/// and a log of everything that was removed or blanked is returned.
+pub fn sanitize(nsif: &mut NSIF, policy: &SanitizePolicy) -> Result<Vec<Removal>, NsifError> {
+    for mnemonic in policy.fields.keys() {
+        let mnemonic = mnemonic.as_str();
+        if let Some(computed) = FileHeader::COMPUTED_FIELDS
+            .iter()
+            .chain(ImageSubheader::COMPUTED_FIELDS)
+            .find(|computed| **computed == mnemonic)
+        {
+            return Err(NsifError::ComputedField(computed));
+        }
+        let found = nsif.file_header.find_field(mnemonic).is_some()
+            || nsif
+                .image_segments
+                .iter()
+                .any(|segment| segment.sub_header.find_field(mnemonic).is_some());
+        if !found && !nsif.image_segments.is_empty() {
+            return Err(NsifError::UnknownField(mnemonic.to_owned()));
+        }
+    }
+
+    let mut removals = Vec::new();
+    sanitize_header(
+        &mut nsif.file_header,
+        Location::FileHeader,
+        &[("UDHD", "UDHOFL"), ("XHD", "XHDLOFL")],
+        policy,
+        &mut removals,
+    )?;
+    for (i, segment) in nsif.image_segments.iter_mut().enumerate() {
+        sanitize_header(
+            &mut segment.sub_header,
+            Location::ImageSegment(i + 1),
+            &[("UDID", "UDOFL"), ("IXSHD", "IXSOFL")],
+            policy,
+            &mut removals,
+        )?;
+    }
+    for (segments, offset, mnemonic, location) in [
+        (
+            &mut nsif.graphic_segments,
+            GRAPHIC_EXTENDED_DATA,
+            "SXSHDL",
+            Location::GraphicSegment as fn(usize) -> Location,
+        ),
+        (
+            &mut nsif.text_segments,
+            TEXT_EXTENDED_DATA,
+            "TXSHDL",
+            Location::TextSegment,
+        ),
+    ] {
+        for (i, segment) in segments.iter_mut().enumerate() {
+            sanitize_extended_data(
+                segment,
+                offset,
+                mnemonic,
+                location(i + 1),
+                policy,
+                &mut removals,
+            )?;
+        }
+    }
+    for (i, segment) in nsif.data_extension_segments.iter_mut().enumerate() {
+        if segment.sub_header.get(2..27).map(parse_string_from_bytes)
+            != Some(format!("{:<25}", "TRE_OVERFLOW"))
+        {
+            continue;
+        }
+        let mut tres = Tre::parse_all(&segment.data)?;
+        let location = Location::DataExtensionSegment(i + 1);
+        if sanitize_tres(&mut tres, location, policy, &mut removals) {
+            segment.data = tres.iter().flat_map(Tre::to_bytes).collect();
+        }
+    }
+    nsif.update_lengths()?;
+    Ok(removals)
+}
+
+/// Applies the policy to a header whose TREs are stored in the given data fields, each paired
+/// with its overflow field
+fn sanitize_header<H: HeaderFields>(
+    header: &mut H,
+    location: Location,
+    tre_fields: &[(&str, &str)],
+    policy: &SanitizePolicy,
+    removals: &mut Vec<Removal>,
+) -> Result<(), NsifError> {
+    for (mnemonic, &action) in &policy.fields {
+        if H::COMPUTED_FIELDS.contains(&mnemonic.as_str()) {
+            continue;
+        }
+        let Some(field) = header.find_field_mut(mnemonic) else {
+            continue;
+        };
+        let values = field
+            .value_bytes()
+            .into_iter()
+            .filter(|bytes| !is_blank(field, bytes))
+            .map(|bytes| display_value(field, &bytes))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            continue;
+        }
+        let action = match action {
+            Action::Keep => continue,
+            Action::Strip if field.clear() => Action::Strip,
+            Action::Strip => Action::Blank,
+            Action::Blank => {
+                field.blank_values();
+                Action::Blank
+            }
+        };
+        removals.extend(values.into_iter().map(|value| Removal {
+            location,
+            item: field.mnemonic().to_owned(),
+            action,
+            value,
+        }));
+    }
+
+    for &(data, overflow) in tre_fields {
+        let Some(field) = header.find_field_mut(data) else {
+            continue;
+        };
+        let mut tres = parse_tres(field)?;
+        if sanitize_tres(&mut tres, location, policy, removals) {
+            set_tres(field, &tres)?;
+        }
+        // Without data the overflow field is only kept if it points to an overflow segment
+        let is_empty = field.bytes().is_empty();
+        if let Some(overflow) = header.find_field_mut(overflow) {
+            if is_empty && overflow.bytes().iter().all(|&byte| byte == b'0') {
+                overflow.set_present(false);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies the TRE actions of the policy to the extended data (SXSHD, TXSHD) of a graphic or
+/// text sub header
+fn sanitize_extended_data(
+    segment: &mut RawSegment,
+    offset: usize,
+    mnemonic: &'static str,
+    location: Location,
+    policy: &SanitizePolicy,
+    removals: &mut Vec<Removal>,
+) -> Result<(), NsifError> {
+    let sub_header = &mut segment.sub_header;
+    let length_bytes = sub_header.get(offset..offset + 5).unwrap_or_default();
+    let invalid = || NsifError::InvalidFieldValue {
+        mnemonic,
+        value: parse_string_from_bytes(length_bytes),
+    };
+    let length = parse_number_from_bytes(length_bytes).map_err(|_| invalid())? as usize;
+    if length == 0 {
+        return Ok(());
+    }
+    let data = sub_header
+        .get(offset + 8..offset + 5 + length)
+        .ok_or_else(invalid)?;
+    let mut tres = Tre::parse_all(data)?;
+    if !sanitize_tres(&mut tres, location, policy, removals) {
+        return Ok(());
+    }
+    let overflow = sub_header[offset + 5..offset + 8].to_vec();
+    sub_header.truncate(offset);
+    // Without data the overflow field is only kept if it points to an overflow segment
+    if tres.is_empty() && overflow.iter().all(|&byte| byte == b'0') {
+        sub_header.extend_from_slice(b"00000");
+    } else {
+        let data = tres.iter().flat_map(Tre::to_bytes).collect::<Vec<_>>();
+        sub_header.extend_from_slice(format!("{:05}", data.len() + 3).as_bytes());
+        sub_header.extend_from_slice(&overflow);
+        sub_header.extend_from_slice(&data);
+    }
+    Ok(())
+}
+
+/// Applies the TRE actions of the policy, returning whether any TRE was stripped or blanked
+fn sanitize_tres(
+    tres: &mut Vec<Tre>,
+    location: Location,
+    policy: &SanitizePolicy,
+    removals: &mut Vec<Removal>,
+) -> bool {
+    let mut changed = false;
+    for tre in tres.iter_mut() {
+        let action = policy.tres.get(&tre.tag).copied().unwrap_or(Action::Keep);
+        if action == Action::Keep {
+            continue;
+        }
+        removals.push(Removal {
+            location,
+            item: format!("TRE:{}", tre.tag),
+            action,
+            value: parse_string_from_bytes(&tre.data),
+        });
+        if action == Action::Blank {
+            tre.data.fill(b' ');
+        }
+        changed = true;
+    }
+    tres.retain(|tre| policy.tres.get(&tre.tag) != Some(&Action::Strip));
+    changed
+}
+
+fn is_blank(field: &Field, bytes: &[u8]) -> bool {
+    let filler = match field.kind() {
+        FieldKind::BcsN => b'0',
+        FieldKind::Binary => 0,
+        FieldKind::BcsA | FieldKind::EcsA => b' ',
+    };
+    bytes.iter().all(|&byte| byte == filler)
+}
+
+fn display_value(field: &Field, bytes: &[u8]) -> String {
+    match field.kind() {
+        FieldKind::Binary => format!("{} bytes", bytes.len()),
+        _ => parse_string_from_bytes(bytes).trim_end().to_owned(),
+    }
+}
@@ -0,0 +1,297 @@
+//! The security fields (xxCLAS through xxCTLN) that the file header and every sub header
+//! share, differing only in the prefix of their mnemonics.
+
+use super::codes::{
+    AuthorityType, Classification, ClassificationReason, Code, DeclassificationType, Downgrade,
+};
+use super::datetime::Date;
+use super::error::NsifError;
+use super::rawsegment::RawSegment;
+use super::validation::Location;
+use super::{parse_string_from_bytes, NSIF};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The suffixes and lengths of the security fields in file order
+const SECURITY_FIELDS: [(&str, usize); 16] = [
+    ("CLAS", 1),
+    ("CLSY", 2),
+    ("CODE", 11),
+    ("CTLH", 2),
+    ("REL", 20),
+    ("DCTP", 2),
+    ("DCDT", 8),
+    ("DCXM", 4),
+    ("DG", 1),
+    ("DGDT", 8),
+    ("CLTX", 43),
+    ("CATP", 1),
+    ("CAUT", 40),
+    ("CRSN", 1),
+    ("SRDT", 8),
+    ("CTLN", 15),
+];
+
+/// The combined length of the security fields
+pub const SECURITY_LENGTH: usize = 167;
+
+/// The offsets of the security fields in the graphic, text, data extension and reserved
+/// extension sub headers, which are otherwise not interpreted
+pub(crate) const GRAPHIC_SECURITY_OFFSET: usize = 32;
+pub(crate) const TEXT_SECURITY_OFFSET: usize = 106;
+pub(crate) const EXTENSION_SECURITY_OFFSET: usize = 29;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityMetadata {
+    pub classification: Classification,
+    /// The classification system (xxCLSY), e.g. NS for NATO
+    pub system: String,
+    pub codewords: Vec<String>,
+    pub control_and_handling: String,
+    /// The countries or groups the segment may be released to (xxREL)
+    pub release_instructions: Vec<String>,
+    pub declassification_type: Option<DeclassificationType>,
+    pub declassification_date: Option<Date>,
+    pub declassification_exemption: String,
+    pub downgrade: Option<Downgrade>,
+    pub downgrade_date: Option<Date>,
+    pub classification_text: String,
+    pub authority_type: Option<AuthorityType>,
+    pub authority: String,
+    pub reason: Option<ClassificationReason>,
+    pub source_date: Option<Date>,
+    pub control_number: String,
+}
+
+impl SecurityMetadata {
+    /// Parses the security fields from their bytes in file order. `prefix` is the prefix of
+    /// their mnemonics (FS, IS, ...) and only used in errors.
+    pub fn parse(prefix: &str, bytes: &[u8]) -> Result<Self, NsifError> {
+        if bytes.len() < SECURITY_LENGTH {
+            return Err(NsifError::LengthMismatch {
+                field: format!("{prefix}CLAS to {prefix}CTLN"),
+                declared: SECURITY_LENGTH as u64,
+                consumed: bytes.len() as u64,
+            });
+        }
+        let mut values = HashMap::new();
+        let mut offset = 0;
+        for (suffix, length) in SECURITY_FIELDS {
+            values.insert(
+                suffix,
+                parse_string_from_bytes(&bytes[offset..offset + length]),
+            );
+            offset += length;
+        }
+        let value = |suffix: &str| values[suffix].trim_end();
+        let invalid = |suffix: &str| NsifError::InvalidSecurityField {
+            field: format!("{prefix}{suffix}"),
+            value: value(suffix).to_owned(),
+        };
+        fn optional<T: FromStr>(value: &str) -> Result<Option<T>, ()> {
+            match value {
+                "" => Ok(None),
+                value => T::from_str(value).map(Some).map_err(|_| ()),
+            }
+        }
+        let words = |suffix: &str| {
+            value(suffix)
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        Ok(SecurityMetadata {
+            classification: Classification::from_str(value("CLAS")).map_err(|_| invalid("CLAS"))?,
+            system: value("CLSY").to_owned(),
+            codewords: words("CODE"),
+            control_and_handling: value("CTLH").to_owned(),
+            release_instructions: words("REL"),
+            declassification_type: optional(value("DCTP")).map_err(|_| invalid("DCTP"))?,
+            declassification_date: optional(value("DCDT")).map_err(|_| invalid("DCDT"))?,
+            declassification_exemption: value("DCXM").to_owned(),
+            downgrade: optional(value("DG")).map_err(|_| invalid("DG"))?,
+            downgrade_date: optional(value("DGDT")).map_err(|_| invalid("DGDT"))?,
+            classification_text: value("CLTX").to_owned(),
+            authority_type: optional(value("CATP")).map_err(|_| invalid("CATP"))?,
+            authority: value("CAUT").to_owned(),
+            reason: optional(value("CRSN")).map_err(|_| invalid("CRSN"))?,
+            source_date: optional(value("SRDT")).map_err(|_| invalid("SRDT"))?,
+            control_number: value("CTLN").to_owned(),
+        })
+    }
+
+    /// Metadata carrying only a classification, e.g. for a header whose other security fields
+    /// are invalid
+    pub fn classification_only(classification: Classification) -> Self {
+        SecurityMetadata {
+            classification,
+            system: String::new(),
+            codewords: Vec::new(),
+            control_and_handling: String::new(),
+            release_instructions: Vec::new(),
+            declassification_type: None,
+            declassification_date: None,
+            declassification_exemption: String::new(),
+            downgrade: None,
+            downgrade_date: None,
+            classification_text: String::new(),
+            authority_type: None,
+            authority: String::new(),
+            reason: None,
+            source_date: None,
+            control_number: String::new(),
+        }
+    }
+
+    /// Whether handling `self` requires more than `other` allows, i.e. a higher
+    /// classification or codewords that `other` does not carry
+    pub fn is_more_restrictive_than(&self, other: &SecurityMetadata) -> bool {
+        self.classification > other.classification
+            || self
+                .codewords
+                .iter()
+                .any(|codeword| !other.codewords.contains(codeword))
+    }
+}
+
+/// The security metadata of the file header and of every segment, in file order
+pub fn security_metadata(nsif: &NSIF) -> Vec<(Location, Result<SecurityMetadata, NsifError>)> {
+    let mut metadata = vec![(Location::FileHeader, nsif.file_header.security())];
+    metadata.extend(
+        nsif.image_segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| (Location::ImageSegment(i + 1), segment.sub_header.security())),
+    );
+    let mut extend =
+        |location: fn(usize) -> Location, segments: &[RawSegment], prefix: &str, offset: usize| {
+            metadata.extend(segments.iter().enumerate().map(|(i, segment)| {
+                let bytes = segment.sub_header.get(offset..).unwrap_or_default();
+                (location(i + 1), SecurityMetadata::parse(prefix, bytes))
+            }))
+        };
+    extend(
+        Location::GraphicSegment,
+        &nsif.graphic_segments,
+        "SS",
+        GRAPHIC_SECURITY_OFFSET,
+    );
+    extend(
+        Location::TextSegment,
+        &nsif.text_segments,
+        "TS",
+        TEXT_SECURITY_OFFSET,
+    );
+    extend(
+        Location::DataExtensionSegment,
+        &nsif.data_extension_segments,
+        "DES",
+        EXTENSION_SECURITY_OFFSET,
+    );
+    extend(
+        Location::ReservedExtensionSegment,
+        &nsif.reserved_extension_segments,
+        "RES",
+        EXTENSION_SECURITY_OFFSET,
+    );
+    metadata
+}
+
+/// The highest classification of the file header and all segments
+pub fn highest_classification(nsif: &NSIF) -> Result<Classification, NsifError> {
+    security_metadata(nsif)
+        .into_iter()
+        .map(|(_, metadata)| metadata.map(|metadata| metadata.classification))
+        .try_fold(Classification::Unclassified, |highest, classification| {
+            Ok(highest.max(classification?))
+        })
+}
+
+/// The segments whose security metadata is more restrictive than that of the file header
+pub fn more_restrictive_segments(nsif: &NSIF) -> Result<Vec<Location>, NsifError> {
+    let file_header = nsif.file_header.security()?;
+    let mut locations = Vec::new();
+    for (location, metadata) in security_metadata(nsif).into_iter().skip(1) {
+        if metadata?.is_more_restrictive_than(&file_header) {
+            locations.push(location);
+        }
+    }
+    Ok(locations)
+}
+
+/// The marking shown on the classification banners above and below displayed imagery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Banner {
+    pub classification: Classification,
+    /// The marking, e.g. SECRET//ALPHA//REL TO DEU, FRA
+    pub text: String,
+}
+
+impl Banner {
+    /// Combines the markings of the given metadata, e.g. of the file header and the displayed
+    /// segment: the highest classification, all codewords and control markings and only the
+    /// release instructions that every one of them allows.
+    pub fn new(metadata: &[&SecurityMetadata]) -> Self {
+        let classification = metadata
+            .iter()
+            .map(|metadata| metadata.classification)
+            .max()
+            .unwrap_or(Classification::Unclassified);
+        let mut markings = Vec::new();
+        for metadata in metadata {
+            for marking in metadata
+                .codewords
+                .iter()
+                .chain(Some(&metadata.control_and_handling).filter(|c| !c.is_empty()))
+            {
+                if !markings.contains(marking) {
+                    markings.push(marking.clone());
+                }
+            }
+        }
+        let release = metadata
+            .iter()
+            .map(|metadata| &metadata.release_instructions)
+            .filter(|release| !release.is_empty())
+            .fold(None::<Vec<String>>, |common, release| {
+                Some(match common {
+                    Some(common) => common.into_iter().filter(|r| release.contains(r)).collect(),
+                    None => release.clone(),
+                })
+            });
+
+        let mut text = classification.description().to_uppercase();
+        if !markings.is_empty() {
+            text.push_str("//");
+            text.push_str(&markings.join("/"));
+        }
+        if let Some(release) = release {
+            text.push_str("//REL TO ");
+            text.push_str(&release.join(", "));
+        }
+        Banner {
+            classification,
+            text,
+        }
+    }
+
+    /// The background colour of the banner as RGB, following the usual colour coding
+    pub fn color(&self) -> [u8; 3] {
+        match self.classification {
+            Classification::Unclassified => [0, 122, 51],
+            Classification::Restricted => [80, 43, 133],
+            Classification::Confidential => [0, 51, 160],
+            Classification::Secret => [200, 16, 46],
+            Classification::TopSecret => [255, 103, 31],
+        }
+    }
+
+    /// The colour of the text on the banner as RGB
+    pub fn text_color(&self) -> [u8; 3] {
+        match self.classification {
+            Classification::TopSecret => [0, 0, 0],
+            _ => [255, 255, 255],
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use super::error::NsifError;
+use super::field::Field;
+use super::rawsegment::RawSegment;
+use super::validation::Location;
+use super::{parse_number_from_bytes, parse_string_from_bytes, NSIF};
+use std::collections::{BTreeMap, BTreeSet};
+
+// SDLVL, SALVL and SLOC in graphic sub headers, TXTALVL in text sub headers
+const GRAPHIC_DISPLAY_LEVEL: usize = 214;
+const GRAPHIC_ATTACHMENT_LEVEL: usize = 217;
+const GRAPHIC_LOCATION: usize = 220;
+const TEXT_ATTACHMENT_LEVEL: usize = 9;
+
+const DES_ITEM: usize = 202;
+
+/// A file holding only the given segment, detached from its parent, and its TRE overflow.
+/// With `attachments` the graphics and texts attached to it are included as well.
+pub fn extract(nsif: &NSIF, location: Location, attachments: bool) -> Result<NSIF, NsifError> {
+    let mut extracted = NSIF {
+        file_header: nsif.file_header.clone(),
+        image_segments: Vec::new(),
+        graphic_segments: Vec::new(),
+        text_segments: Vec::new(),
+        data_extension_segments: Vec::new(),
+        reserved_extension_segments: Vec::new(),
+    };
+    let missing = || NsifError::MissingSegment(location);
+    let mut levels = BTreeSet::new();
+    match location {
+        Location::ImageSegment(position) => {
+            let mut image_segment = position
+                .checked_sub(1)
+                .and_then(|i| nsif.image_segments.get(i))
+                .ok_or_else(missing)?
+                .clone();
+            let sub_header = &mut image_segment.sub_header;
+            levels.insert(sub_header.display_level()?);
+            if sub_header.attachment_level()? != 0 {
+                sub_header.ialvl.set_number(0)?;
+                sub_header.iloc.set_number(0)?;
+            }
+            extracted.image_segments.push(image_segment);
+        }
+        Location::GraphicSegment(position) => {
+            let mut graphic_segment = position
+                .checked_sub(1)
+                .and_then(|i| nsif.graphic_segments.get(i))
+                .ok_or_else(missing)?
+                .clone();
+            levels.insert(level(&graphic_segment, GRAPHIC_DISPLAY_LEVEL)?);
+            if level(&graphic_segment, GRAPHIC_ATTACHMENT_LEVEL)? != 0 {
+                set_level(&mut graphic_segment, GRAPHIC_ATTACHMENT_LEVEL, 0);
+                if let Some(location) = graphic_segment
+                    .sub_header
+                    .get_mut(GRAPHIC_LOCATION..GRAPHIC_LOCATION + 10)
+                {
+                    location.copy_from_slice(b"0000000000");
+                }
+            }
+            extracted.graphic_segments.push(graphic_segment);
+        }
+        Location::TextSegment(position) => {
+            let mut text_segment = position
+                .checked_sub(1)
+                .and_then(|i| nsif.text_segments.get(i))
+                .ok_or_else(missing)?
+                .clone();
+            set_level(&mut text_segment, TEXT_ATTACHMENT_LEVEL, 0);
+            extracted.text_segments.push(text_segment);
+        }
+        _ => return Err(NsifError::MissingSegment(location)),
+    }
+
+    if attachments && !levels.is_empty() {
+        // Graphics may be attached to graphics, so their levels are collected until no
+        // further graphic is attached
+        let mut graphics = BTreeSet::new();
+        loop {
+            let mut attached = false;
+            for (i, graphic_segment) in nsif.graphic_segments.iter().enumerate() {
+                if !graphics.contains(&i)
+                    && levels.contains(&level(graphic_segment, GRAPHIC_ATTACHMENT_LEVEL)?)
+                {
+                    graphics.insert(i);
+                    levels.insert(level(graphic_segment, GRAPHIC_DISPLAY_LEVEL)?);
+                    attached = true;
+                }
+            }
+            if !attached {
+                break;
+            }
+        }
+        extracted.graphic_segments.extend(
+            graphics
+                .into_iter()
+                .filter(|&i| location != Location::GraphicSegment(i + 1))
+                .map(|i| nsif.graphic_segments[i].clone()),
+        );
+        for text_segment in &nsif.text_segments {
+            if levels.contains(&level(text_segment, TEXT_ATTACHMENT_LEVEL)?) {
+                extracted.text_segments.push(text_segment.clone());
+            }
+        }
+    }
+
+    let mut overflow = find_overflow(nsif);
+    overflow.images = match location {
+        Location::ImageSegment(position) => vec![overflow.images.swap_remove(position - 1)],
+        _ => Vec::new(),
+    };
+    restore_overflow(&mut extracted, overflow)?;
+    finish(&mut extracted)?;
+    Ok(extracted)
+}
+
+/// Appends an image segment of another file and its TRE overflow above all other segments
+pub fn append_image(nsif: &mut NSIF, source: &NSIF, position: usize) -> Result<(), NsifError> {
+    let index = segment_index(position, source.image_segments.len())
+        .ok_or(NsifError::MissingSegment(Location::ImageSegment(position)))?;
+    let mut source_overflow = find_overflow(source);
+
+    let mut overflow = take_overflow(nsif);
+    let mut image_segment = source.image_segments[index].clone();
+    let top = display_levels(nsif)?
+        .into_iter()
+        .map(|(level, _)| level)
+        .max()
+        .unwrap_or(0);
+    let sub_header = &mut image_segment.sub_header;
+    sub_header.idlvl.set_number(u64::from(top) + 1)?;
+    sub_header.ialvl.set_number(0)?;
+    sub_header.iloc.set_number(0)?;
+    nsif.image_segments.push(image_segment);
+    overflow
+        .images
+        .push(source_overflow.images.swap_remove(index));
+    restore_overflow(nsif, overflow)?;
+    finish(nsif)
+}
+
+/// Removes a segment, attaching the segments attached to it to its parent instead
+pub fn remove_segment(nsif: &mut NSIF, location: Location) -> Result<(), NsifError> {
+    let missing = NsifError::MissingSegment(location);
+    let levels = match location {
+        Location::ImageSegment(position) => {
+            let index = segment_index(position, nsif.image_segments.len()).ok_or(missing)?;
+            let sub_header = &nsif.image_segments[index].sub_header;
+            Some((sub_header.display_level()?, sub_header.attachment_level()?))
+        }
+        Location::GraphicSegment(position) => {
+            let index = segment_index(position, nsif.graphic_segments.len()).ok_or(missing)?;
+            let graphic_segment = &nsif.graphic_segments[index];
+            Some((
+                level(graphic_segment, GRAPHIC_DISPLAY_LEVEL)?,
+                level(graphic_segment, GRAPHIC_ATTACHMENT_LEVEL)?,
+            ))
+        }
+        Location::TextSegment(position) => {
+            segment_index(position, nsif.text_segments.len()).ok_or(missing)?;
+            None
+        }
+        _ => return Err(missing),
+    };
+
+    let mut overflow = take_overflow(nsif);
+    match location {
+        Location::ImageSegment(position) => {
+            nsif.image_segments.remove(position - 1);
+            overflow.images.remove(position - 1);
+        }
+        Location::GraphicSegment(position) => {
+            nsif.graphic_segments.remove(position - 1);
+        }
+        Location::TextSegment(position) => {
+            nsif.text_segments.remove(position - 1);
+        }
+        _ => unreachable!("other segments are rejected above"),
+    }
+    if let Some((display_level, attachment_level)) = levels {
+        map_attachment_levels(nsif, |level| {
+            if level == display_level {
+                attachment_level
+            } else {
+                level
+            }
+        })?;
+    }
+    restore_overflow(nsif, overflow)?;
+    finish(nsif)
+}
+
+/// Moves a segment to another position among the segments of its type
+pub fn move_segment(nsif: &mut NSIF, location: Location, position: usize) -> Result<(), NsifError> {
+    fn reorder<T>(segments: &mut Vec<T>, from: usize, to: usize) {
+        let segment = segments.remove(from - 1);
+        segments.insert(to - 1, segment);
+    }
+    let (from, count, target) = match location {
+        Location::ImageSegment(from) => (
+            from,
+            nsif.image_segments.len(),
+            Location::ImageSegment(position),
+        ),
+        Location::GraphicSegment(from) => (
+            from,
+            nsif.graphic_segments.len(),
+            Location::GraphicSegment(position),
+        ),
+        Location::TextSegment(from) => (
+            from,
+            nsif.text_segments.len(),
+            Location::TextSegment(position),
+        ),
+        _ => return Err(NsifError::MissingSegment(location)),
+    };
+    segment_index(from, count).ok_or(NsifError::MissingSegment(location))?;
+    segment_index(position, count).ok_or(NsifError::MissingSegment(target))?;
+
+    let mut overflow = take_overflow(nsif);
+    match location {
+        Location::ImageSegment(_) => {
+            reorder(&mut nsif.image_segments, from, position);
+            reorder(&mut overflow.images, from, position);
+        }
+        Location::GraphicSegment(_) => reorder(&mut nsif.graphic_segments, from, position),
+        _ => reorder(&mut nsif.text_segments, from, position),
+    }
+    restore_overflow(nsif, overflow)?;
+    finish(nsif)
+}
+
+/// Renumbers the display levels from 1 and updates the attachment levels and lengths
+fn finish(nsif: &mut NSIF) -> Result<(), NsifError> {
+    let mut levels = display_levels(nsif)?;
+    levels.sort_by_key(|(level, _)| *level);
+    let mut renumbered = BTreeMap::new();
+    for (new_level, (level, location)) in levels.into_iter().enumerate() {
+        let new_level = new_level as u16 + 1;
+        renumbered.entry(level).or_insert(new_level);
+        match location {
+            Location::ImageSegment(position) => nsif.image_segments[position - 1]
+                .sub_header
+                .idlvl
+                .set_number(u64::from(new_level))?,
+            Location::GraphicSegment(position) => set_level(
+                &mut nsif.graphic_segments[position - 1],
+                GRAPHIC_DISPLAY_LEVEL,
+                new_level,
+            ),
+            _ => unreachable!("only images and graphics have display levels"),
+        }
+    }
+    // Attachments to levels that no longer exist are dropped
+    map_attachment_levels(nsif, |level| renumbered.get(&level).copied().unwrap_or(0))?;
+
+    nsif.update_lengths_and_complexity_level()
+}
+
+fn display_levels(nsif: &NSIF) -> Result<Vec<(u16, Location)>, NsifError> {
+    let mut levels = Vec::new();
+    for (i, image_segment) in nsif.image_segments.iter().enumerate() {
+        levels.push((
+            image_segment.sub_header.display_level()?,
+            Location::ImageSegment(i + 1),
+        ));
+    }
+    for (i, graphic_segment) in nsif.graphic_segments.iter().enumerate() {
+        levels.push((
+            level(graphic_segment, GRAPHIC_DISPLAY_LEVEL)?,
+            Location::GraphicSegment(i + 1),
+        ));
+    }
+    Ok(levels)
+}
+
+fn map_attachment_levels(nsif: &mut NSIF, map: impl Fn(u16) -> u16) -> Result<(), NsifError> {
+    for image_segment in &mut nsif.image_segments {
+        let sub_header = &mut image_segment.sub_header;
+        let level = sub_header.attachment_level()?;
+        if level != 0 {
+            sub_header.ialvl.set_number(u64::from(map(level)))?;
+        }
+    }
+    for (segments, offset) in [
+        (&mut nsif.graphic_segments, GRAPHIC_ATTACHMENT_LEVEL),
+        (&mut nsif.text_segments, TEXT_ATTACHMENT_LEVEL),
+    ] {
+        for segment in segments {
+            let level = level(segment, offset)?;
+            if level != 0 {
+                set_level(segment, offset, map(level));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn segment_index(position: usize, count: usize) -> Option<usize> {
+    position.checked_sub(1).filter(|&i| i < count)
+}
+
+/// The TRE overflow segments of the file header and of each image
+pub(crate) struct Overflow {
+    pub(crate) file_header: [Option<RawSegment>; 2],
+    pub(crate) images: Vec<[Option<RawSegment>; 2]>,
+}
+
+pub(crate) fn find_overflow(nsif: &NSIF) -> Overflow {
+    let segment = |field: &Field| {
+        overflow_segment(field)
+            .and_then(|position| nsif.data_extension_segments.get(position - 1))
+            .cloned()
+    };
+    Overflow {
+        file_header: [
+            segment(&nsif.file_header.udhofl),
+            segment(&nsif.file_header.xhdlofl),
+        ],
+        images: nsif
+            .image_segments
+            .iter()
+            .map(|image_segment| {
+                let sub_header = &image_segment.sub_header;
+                [segment(&sub_header.udofl), segment(&sub_header.ixsofl)]
+            })
+            .collect(),
+    }
+}
+
+fn take_overflow(nsif: &mut NSIF) -> Overflow {
+    let overflow = find_overflow(nsif);
+    let taken = [&nsif.file_header.udhofl, &nsif.file_header.xhdlofl]
+        .into_iter()
+        .chain(nsif.image_segments.iter().flat_map(|image_segment| {
+            [
+                &image_segment.sub_header.udofl,
+                &image_segment.sub_header.ixsofl,
+            ]
+        }))
+        .filter_map(overflow_segment)
+        .filter(|&position| position <= nsif.data_extension_segments.len())
+        .collect::<BTreeSet<_>>();
+    for position in taken.into_iter().rev() {
+        nsif.data_extension_segments.remove(position - 1);
+    }
+    overflow
+}
+
+/// Appends the overflow segments and points the overflow fields and DESITEM at them
+pub(crate) fn restore_overflow(nsif: &mut NSIF, overflow: Overflow) -> Result<(), NsifError> {
+    let data_extension_segments = &mut nsif.data_extension_segments;
+    let mut restore =
+        |field: &mut Field, segment: Option<RawSegment>, item: usize| -> Result<(), NsifError> {
+            let Some(mut segment) = segment else {
+                return Ok(());
+            };
+            if let Some(bytes) = segment.sub_header.get_mut(DES_ITEM..DES_ITEM + 3) {
+                bytes.copy_from_slice(format!("{item:03}").as_bytes());
+            }
+            data_extension_segments.push(segment);
+            field.set_number(data_extension_segments.len() as u64)
+        };
+    let [user_defined, extended] = overflow.file_header;
+    restore(&mut nsif.file_header.udhofl, user_defined, 0)?;
+    restore(&mut nsif.file_header.xhdlofl, extended, 0)?;
+    for (i, (image_segment, [user_defined, extended])) in nsif
+        .image_segments
+        .iter_mut()
+        .zip(overflow.images)
+        .enumerate()
+    {
+        restore(&mut image_segment.sub_header.udofl, user_defined, i + 1)?;
+        restore(&mut image_segment.sub_header.ixsofl, extended, i + 1)?;
+    }
+    Ok(())
+}
+
+fn overflow_segment(field: &Field) -> Option<usize> {
+    match parse_number_from_bytes(&field.bytes()) {
+        Ok(position) if position > 0 => Some(position as usize),
+        _ => None,
+    }
+}
+
+fn level(segment: &RawSegment, offset: usize) -> Result<u16, NsifError> {
+    let bytes = segment
+        .sub_header
+        .get(offset..offset + 3)
+        .unwrap_or_default();
+    parse_number_from_bytes(bytes)
+        .map(|level| level as u16)
+        .map_err(|_| NsifError::InvalidFieldValue {
+            mnemonic: match offset {
+                GRAPHIC_DISPLAY_LEVEL => "SDLVL",
+                GRAPHIC_ATTACHMENT_LEVEL => "SALVL",
+                _ => "TXTALVL",
+            },
+            value: parse_string_from_bytes(bytes),
+        })
+}
+
+fn set_level(segment: &mut RawSegment, offset: usize, level: u16) {
+    if let Some(bytes) = segment.sub_header.get_mut(offset..offset + 3) {
+        bytes.copy_from_slice(format!("{level:03}").as_bytes());
+    }
+}
@@ -0,0 +1,61 @@
+//! Tagged record extensions (TREs), the records stored one after another in the user-defined
+//! and extended data fields (UDHD, XHD, UDID, IXSHD).
+
+use super::error::NsifError;
+use super::field::Field;
+use super::{parse_number_from_bytes, parse_string_from_bytes};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tre {
+    /// The tag (CETAG) without trailing spaces, e.g. ICHIPB
+    pub tag: String,
+    /// The user-defined data (CEDATA)
+    pub data: Vec<u8>,
+}
+
+impl Tre {
+    pub fn new(tag: &str, data: Vec<u8>) -> Self {
+        Tre {
+            tag: tag.to_owned(),
+            data,
+        }
+    }
+
+    /// Parses the TREs stored one after another in `bytes`
+    pub fn parse_all(bytes: &[u8]) -> Result<Vec<Tre>, NsifError> {
+        let mut tres = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let malformed = || NsifError::MalformedTre(offset);
+            let header = bytes.get(offset..offset + 11).ok_or_else(malformed)?;
+            let length = parse_number_from_bytes(&header[6..]).map_err(|_| malformed())? as usize;
+            let data = bytes
+                .get(offset + 11..offset + 11 + length)
+                .ok_or_else(malformed)?;
+            tres.push(Tre {
+                tag: parse_string_from_bytes(&header[..6]).trim_end().to_owned(),
+                data: data.to_vec(),
+            });
+            offset += 11 + length;
+        }
+        Ok(tres)
+    }
+
+    /// Serializes the TRE as CETAG, CEL and CEDATA
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!("{:<6}{:05}", self.tag, self.data.len()).into_bytes();
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+}
+
+/// The TREs stored in a user-defined or extended data field
+pub fn parse_tres(field: &Field) -> Result<Vec<Tre>, NsifError> {
+    Tre::parse_all(&field.bytes())
+}
+
+/// Replaces the content of a user-defined or extended data field with the given TREs. The
+/// length fields are recomputed when the file is written.
+pub fn set_tres(field: &mut Field, tres: &[Tre]) -> Result<(), NsifError> {
+    field.set_bytes(&tres.iter().flat_map(Tre::to_bytes).collect::<Vec<_>>())
+}
@@ -0,0 +1,756 @@
+//! Conformance checks of a parsed file against STANAG 4545 / MIL-STD-2500C.
+
+use super::codes::{describe, Code, Compression};
+use super::definitions::{
+    data_extension_subheader, graphic_subheader, reserved_extension_subheader, text_subheader,
+};
+use super::error::NsifError;
+use super::field::{Field, FieldDefinition, FieldKind};
+use super::security::{security_metadata, SECURITY_LENGTH};
+use super::{is_bcs, is_bcs_n, is_ecs, parse_string_from_bytes, NSIF};
+use bevy_reflect::Struct;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// The part of the file an issue was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Location {
+    FileHeader,
+    ImageSegment(usize),
+    GraphicSegment(usize),
+    TextSegment(usize),
+    DataExtensionSegment(usize),
+    ReservedExtensionSegment(usize),
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::FileHeader => write!(f, "File Header"),
+            Location::ImageSegment(i) => write!(f, "Image Segment {i}"),
+            Location::GraphicSegment(i) => write!(f, "Graphic Segment {i}"),
+            Location::TextSegment(i) => write!(f, "Text Segment {i}"),
+            Location::DataExtensionSegment(i) => write!(f, "Data Extension Segment {i}"),
+            Location::ReservedExtensionSegment(i) => write!(f, "Reserved Extension Segment {i}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub location: Location,
+    /// The mnemonic of the offending field, if the issue concerns a single field
+    pub mnemonic: Option<&'static str>,
+    pub message: String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mnemonic {
+            Some(mnemonic) => write!(f, "{}: {mnemonic}: {}", self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+    /// The complexity level claimed by CLEVEL, if it could be parsed
+    pub declared_complexity_level: Option<u8>,
+    /// The lowest complexity level the file satisfies, if its segments could be parsed
+    pub required_complexity_level: Option<u8>,
+}
+
+impl ValidationReport {
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    /// The issues grouped by the part of the file they were found in, in file order
+    pub fn by_location(&self) -> Vec<(Location, Vec<&Issue>)> {
+        let mut groups: Vec<(Location, Vec<&Issue>)> = Vec::new();
+        for issue in &self.issues {
+            match groups
+                .iter_mut()
+                .find(|(location, _)| *location == issue.location)
+            {
+                Some((_, issues)) => issues.push(issue),
+                None => groups.push((issue.location, vec![issue])),
+            }
+        }
+        groups.sort_by_key(|(location, _)| *location);
+        groups
+    }
+
+    /// Serializes the report as JSON with the issues grouped by location
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<&str>| value.map_or(String::from("null"), json_string);
+        let groups = self
+            .by_location()
+            .iter()
+            .map(|(location, issues)| {
+                let issues = issues
+                    .iter()
+                    .map(|issue| {
+                        format!(
+                            r#"{{"severity":{},"field":{},"message":{}}}"#,
+                            json_string(&issue.severity.to_string()),
+                            optional(issue.mnemonic),
+                            json_string(&issue.message)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    r#"{{"location":{},"issues":[{}]}}"#,
+                    json_string(&location.to_string()),
+                    issues.join(",")
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            r#"{{"valid":{},"errors":{},"warnings":{},"declared_complexity_level":{},"required_complexity_level":{},"segments":[{}]}}"#,
+            !self.has_errors(),
+            self.errors().count(),
+            self.warnings().count(),
+            self.declared_complexity_level
+                .map_or(String::from("null"), |level| level.to_string()),
+            self.required_complexity_level
+                .map_or(String::from("null"), |level| level.to_string()),
+            groups.join(",")
+        )
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        location: Location,
+        mnemonic: Option<&'static str>,
+        message: impl Into<String>,
+    ) {
+        self.issues.push(Issue {
+            severity,
+            location,
+            mnemonic,
+            message: message.into(),
+        });
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// The limits of a complexity level, after MIL-STD-2500C Table A-10
+struct ComplexityLimits {
+    level: u8,
+    /// The largest row or column of the common coordinate system an image may extend to
+    ccs_extent: u64,
+    file_size: u64,
+    image_size: u32,
+    block_size: u32,
+    image_segments: usize,
+    bands: u32,
+}
+
+const COMPLEXITY_LIMITS: [ComplexityLimits; 5] = [
+    ComplexityLimits {
+        level: 3,
+        ccs_extent: 2_047,
+        file_size: 52_428_799,
+        image_size: 2_048,
+        block_size: 2_048,
+        image_segments: 20,
+        bands: 9,
+    },
+    ComplexityLimits {
+        level: 5,
+        ccs_extent: 8_191,
+        file_size: 1_073_741_823,
+        image_size: 8_192,
+        block_size: 8_192,
+        image_segments: 100,
+        bands: 256,
+    },
+    ComplexityLimits {
+        level: 6,
+        ccs_extent: 65_535,
+        file_size: 2_147_483_647,
+        image_size: 65_536,
+        block_size: 8_192,
+        image_segments: 100,
+        bands: 256,
+    },
+    ComplexityLimits {
+        level: 7,
+        ccs_extent: 99_999_999,
+        file_size: 10_737_418_239,
+        image_size: 99_999_999,
+        block_size: 8_192,
+        image_segments: 100,
+        bands: 999,
+    },
+    ComplexityLimits {
+        level: 9,
+        ccs_extent: u64::MAX,
+        file_size: u64::MAX,
+        image_size: u32::MAX,
+        block_size: u32::MAX,
+        image_segments: usize::MAX,
+        bands: u32::MAX,
+    },
+];
+
+/// The figures of a file that determine its complexity level
+#[derive(Default)]
+struct ComplexityFigures {
+    ccs_extent: u64,
+    file_size: u64,
+    image_size: u32,
+    block_size: u32,
+    image_segments: usize,
+    bands: u32,
+}
+
+/// Computes the lowest complexity level (CLEVEL) whose limits the file satisfies, based on
+/// the image dimensions and locations, block sizes, band and segment counts and file size
+pub fn required_complexity_level(nsif: &NSIF) -> u8 {
+    let figures = complexity_figures(nsif);
+    COMPLEXITY_LIMITS
+        .iter()
+        .find(|limits| {
+            figures.ccs_extent <= limits.ccs_extent
+                && figures.file_size <= limits.file_size
+                && figures.image_size <= limits.image_size
+                && figures.block_size <= limits.block_size
+                && figures.image_segments <= limits.image_segments
+                && figures.bands <= limits.bands
+        })
+        .map_or(99, |limits| limits.level)
+}
+
+fn complexity_figures(nsif: &NSIF) -> ComplexityFigures {
+    let mut figures = ComplexityFigures {
+        file_size: nsif.file_header.file_length().unwrap_or(0),
+        image_segments: nsif.image_segments.len(),
+        ..Default::default()
+    };
+    // Images are located relative to the item they are attached to, so the absolute
+    // location of each display level is accumulated along the attachments
+    let mut origins = HashMap::new();
+    for segment in &nsif.image_segments {
+        let sub_header = &segment.sub_header;
+        let rows = sub_header.num_rows().unwrap_or(0);
+        let cols = sub_header.num_cols().unwrap_or(0);
+        let (row, col) = sub_header.location().unwrap_or((0, 0));
+        let (parent_row, parent_col) = sub_header
+            .attachment_level()
+            .ok()
+            .and_then(|level| origins.get(&level).copied())
+            .unwrap_or((0, 0));
+        let origin = (parent_row + i64::from(row), parent_col + i64::from(col));
+        if let Ok(level) = sub_header.display_level() {
+            origins.insert(level, origin);
+        }
+        let extent = (origin.0 + i64::from(rows)).max(origin.1 + i64::from(cols)) - 1;
+
+        let block_size = |pixels: Result<u32, _>, total: u32| match pixels {
+            Ok(0) | Err(_) => total,
+            Ok(pixels) => pixels,
+        };
+        figures.ccs_extent = figures.ccs_extent.max(extent.max(0) as u64);
+        figures.image_size = figures.image_size.max(rows).max(cols);
+        figures.block_size = figures
+            .block_size
+            .max(block_size(sub_header.pixels_per_block_horizontal(), cols))
+            .max(block_size(sub_header.pixels_per_block_vertical(), rows));
+        figures.bands = figures.bands.max(sub_header.num_bands().unwrap_or(0));
+    }
+    figures
+}
+
+/// Checks every field of the file header and the image sub headers and the fixed fields of
+/// the other sub headers against its definition (character set, length, range and allowed
+/// codes), the relations between fields and segments, and the declared complexity level
+pub fn validate(nsif: &NSIF) -> ValidationReport {
+    let mut report = ValidationReport {
+        declared_complexity_level: nsif.file_header.complexity_level().ok(),
+        required_complexity_level: Some(required_complexity_level(nsif)),
+        ..Default::default()
+    };
+
+    check_fields(&mut report, Location::FileHeader, &nsif.file_header);
+    check_file_header(&mut report, nsif);
+    for (i, segment) in nsif.image_segments.iter().enumerate() {
+        let location = Location::ImageSegment(i + 1);
+        check_fields(&mut report, location, &segment.sub_header);
+        check_image_segment(&mut report, location, nsif, i);
+    }
+    for (i, segment) in nsif.graphic_segments.iter().enumerate() {
+        check_raw_sub_header(
+            &mut report,
+            Location::GraphicSegment(i + 1),
+            &segment.sub_header,
+            graphic_subheader::BEFORE_SECURITY,
+            &[graphic_subheader::AFTER_SECURITY],
+        );
+    }
+    for (i, segment) in nsif.text_segments.iter().enumerate() {
+        check_raw_sub_header(
+            &mut report,
+            Location::TextSegment(i + 1),
+            &segment.sub_header,
+            text_subheader::BEFORE_SECURITY,
+            &[text_subheader::AFTER_SECURITY],
+        );
+    }
+    for (i, segment) in nsif.data_extension_segments.iter().enumerate() {
+        let overflow =
+            segment.sub_header.get(2..27) == Some(format!("{:<25}", "TRE_OVERFLOW").as_bytes());
+        let after: &[&[FieldDefinition]] = if overflow {
+            &[
+                data_extension_subheader::OVERFLOW,
+                data_extension_subheader::AFTER_SECURITY,
+            ]
+        } else {
+            &[data_extension_subheader::AFTER_SECURITY]
+        };
+        check_raw_sub_header(
+            &mut report,
+            Location::DataExtensionSegment(i + 1),
+            &segment.sub_header,
+            data_extension_subheader::BEFORE_SECURITY,
+            after,
+        );
+    }
+    for (i, segment) in nsif.reserved_extension_segments.iter().enumerate() {
+        check_raw_sub_header(
+            &mut report,
+            Location::ReservedExtensionSegment(i + 1),
+            &segment.sub_header,
+            reserved_extension_subheader::BEFORE_SECURITY,
+            &[reserved_extension_subheader::AFTER_SECURITY],
+        );
+    }
+    check_security(&mut report, nsif);
+    check_complexity_level(&mut report);
+    report
+}
+
+/// Reports invalid security fields and segments more restrictive than the file header,
+/// which has to carry the highest classification and every codeword of the file
+fn check_security(report: &mut ValidationReport, nsif: &NSIF) {
+    let metadata = security_metadata(nsif);
+    let file_header = metadata
+        .first()
+        .and_then(|(_, file_header)| file_header.as_ref().ok())
+        .cloned();
+    for (location, segment) in metadata {
+        let segment = match segment {
+            Ok(segment) => segment,
+            // FSCLAS and ISCLAS are already checked as coded fields
+            Err(NsifError::InvalidSecurityField { field, .. })
+                if field.ends_with("CLAS")
+                    && matches!(location, Location::FileHeader | Location::ImageSegment(_)) =>
+            {
+                continue
+            }
+            Err(e) => {
+                report.push(Severity::Error, location, None, e.to_string());
+                continue;
+            }
+        };
+        let Some(file_header) = file_header
+            .as_ref()
+            .filter(|_| location != Location::FileHeader)
+        else {
+            continue;
+        };
+        if segment.classification > file_header.classification {
+            report.push(
+                Severity::Error,
+                location,
+                Some(classification_mnemonic(location)),
+                format!(
+                    "{} is more restrictive than the file header's {}",
+                    segment.classification.description(),
+                    file_header.classification.description()
+                ),
+            );
+        }
+        let codewords = segment
+            .codewords
+            .iter()
+            .filter(|codeword| !file_header.codewords.contains(codeword))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !codewords.is_empty() {
+            report.push(
+                Severity::Error,
+                location,
+                None,
+                format!("codewords {} are missing from FSCODE", codewords.join(" ")),
+            );
+        }
+    }
+}
+
+fn classification_mnemonic(location: Location) -> &'static str {
+    match location {
+        Location::FileHeader => "FSCLAS",
+        Location::ImageSegment(_) => "ISCLAS",
+        Location::GraphicSegment(_) => "SSCLAS",
+        Location::TextSegment(_) => "TSCLAS",
+        Location::DataExtensionSegment(_) => "DESCLAS",
+        Location::ReservedExtensionSegment(_) => "RESCLAS",
+    }
+}
+
+/// Mnemonics whose values are drawn from the enumerations in [`super::codes`]
+const CODED_FIELDS: [&str; 8] = [
+    "FSCLAS", "ISCLAS", "IC", "IMODE", "IREP", "ICAT", "PVTYPE", "ICORDS",
+];
+
+fn check_fields(report: &mut ValidationReport, location: Location, header: &dyn Struct) {
+    let fields = header
+        .iter_fields()
+        .filter_map(|field| field.try_downcast_ref::<Field>());
+    for field in fields {
+        check_field(report, location, field);
+    }
+}
+
+/// Checks the fixed fields of a graphic, text, data extension or reserved extension sub header,
+/// which are followed by the security fields and the groups of fields in `after`. The last of
+/// them gives the length of the rest of the sub header.
+fn check_raw_sub_header(
+    report: &mut ValidationReport,
+    location: Location,
+    sub_header: &[u8],
+    before: &[FieldDefinition],
+    after: &[&[FieldDefinition]],
+) {
+    let mut offset = 0;
+    let mut length = 0;
+    for (i, definition) in before
+        .iter()
+        .chain(after.iter().copied().flatten())
+        .enumerate()
+    {
+        if i == before.len() {
+            offset += SECURITY_LENGTH;
+        }
+        let end = offset + definition.length.unwrap_or(0);
+        let Some(bytes) = sub_header.get(offset..end) else {
+            report.push(
+                Severity::Error,
+                location,
+                Some(definition.mnemonic),
+                format!("the sub header ends after {} bytes", sub_header.len()),
+            );
+            return;
+        };
+        let field = Field::single(*definition, offset as u64, bytes.to_vec());
+        check_field(report, location, &field);
+        if i == 0 && bytes != definition.mnemonic.as_bytes() {
+            report.push(
+                Severity::Error,
+                location,
+                Some(definition.mnemonic),
+                format!("must be {}", definition.mnemonic),
+            );
+        }
+        length = parse_string_from_bytes(bytes).parse::<usize>().unwrap_or(0);
+        offset = end;
+    }
+    if offset + length != sub_header.len() {
+        let mnemonic = after.last().and_then(|fields| fields.last());
+        report.push(
+            Severity::Error,
+            location,
+            mnemonic.map(|definition| definition.mnemonic),
+            format!(
+                "declares {length} bytes but {} follow",
+                sub_header.len().saturating_sub(offset)
+            ),
+        );
+    }
+}
+
+fn check_field(report: &mut ValidationReport, location: Location, field: &Field) {
+    let definition = field.definition();
+    let mnemonic = Some(definition.mnemonic);
+    for bytes in field.value_bytes() {
+        if let Some(length) = definition.length {
+            // Conditional fields that are not present have no bytes at all
+            if !bytes.is_empty() && bytes.len() != length && field.kind() != FieldKind::Binary {
+                report.push(
+                    Severity::Error,
+                    location,
+                    mnemonic,
+                    format!("is {} bytes long instead of {length}", bytes.len()),
+                );
+                continue;
+            }
+        }
+        let valid = match field.kind() {
+            FieldKind::BcsA => is_bcs(&bytes),
+            FieldKind::BcsN => is_bcs_n(&bytes),
+            FieldKind::EcsA => is_ecs(&bytes),
+            FieldKind::Binary => true,
+        };
+        let value = parse_string_from_bytes(&bytes);
+        if !valid {
+            report.push(
+                Severity::Error,
+                location,
+                mnemonic,
+                format!("'{value}' contains characters outside of {}", field.kind()),
+            );
+            continue;
+        }
+        if let (Some((min, max)), false) = (definition.range, bytes.is_empty()) {
+            match value.parse::<u64>() {
+                Ok(number) if (min..=max).contains(&number) => {}
+                Ok(number) => report.push(
+                    Severity::Error,
+                    location,
+                    mnemonic,
+                    format!("{number} is outside of the range {min} to {max}"),
+                ),
+                Err(_) => report.push(
+                    Severity::Error,
+                    location,
+                    mnemonic,
+                    format!("'{value}' is not a number"),
+                ),
+            }
+        }
+    }
+    if CODED_FIELDS.contains(&definition.mnemonic) {
+        let value = parse_string_from_bytes(&field.bytes());
+        if describe(definition.mnemonic, &value).is_none() {
+            // Image categories are extended by profiles more often than other codes
+            let severity = if definition.mnemonic == "ICAT" {
+                Severity::Warning
+            } else {
+                Severity::Error
+            };
+            report.push(
+                severity,
+                location,
+                mnemonic,
+                format!("'{}' is not a valid code", value.trim_end()),
+            );
+        }
+    }
+    if definition.mnemonic == "ENCRYP" && field.bytes() != b"0" {
+        report.push(
+            Severity::Error,
+            location,
+            mnemonic,
+            "encrypted files are not defined by the standard and must be 0",
+        );
+    }
+}
+
+fn check_file_header(report: &mut ValidationReport, nsif: &NSIF) {
+    let location = Location::FileHeader;
+    let file_header = &nsif.file_header;
+    if file_header.profile().is_err() {
+        report.push(
+            Severity::Error,
+            location,
+            Some("FVER"),
+            format!(
+                "{} {} is neither NSIF 01.00 nor NITF 02.10",
+                parse_string_from_bytes(&file_header.fhdr.bytes()),
+                parse_string_from_bytes(&file_header.fver.bytes())
+            ),
+        );
+    }
+    if file_header.stype.bytes() != b"BF01" {
+        report.push(Severity::Warning, location, Some("STYPE"), "should be BF01");
+    }
+    if file_header.file_datetime().is_err() {
+        report.push(
+            Severity::Error,
+            location,
+            Some("FDT"),
+            "is not a valid date and time",
+        );
+    }
+
+    let limits = [
+        ("NUMS", nsif.graphic_segments.len(), 100),
+        ("NUMT", nsif.text_segments.len(), 32),
+        ("NUMDES", nsif.data_extension_segments.len(), 100),
+        ("NUMRES", nsif.reserved_extension_segments.len(), 10),
+    ];
+    for (mnemonic, count, limit) in limits {
+        if count > limit {
+            report.push(
+                Severity::Error,
+                location,
+                Some(mnemonic),
+                format!("{count} segments exceed the limit of {limit}"),
+            );
+        }
+    }
+}
+
+fn check_image_segment(report: &mut ValidationReport, location: Location, nsif: &NSIF, i: usize) {
+    let segment = &nsif.image_segments[i];
+    let sub_header = &segment.sub_header;
+    if sub_header.im.bytes() != b"IM" {
+        report.push(Severity::Error, location, Some("IM"), "must be IM");
+    }
+    if sub_header.image_datetime().is_err() {
+        report.push(
+            Severity::Warning,
+            location,
+            Some("IDATIM"),
+            "is not a complete date and time",
+        );
+    }
+    if let (Ok(abpp), Ok(nbpp)) = (
+        sub_header.actual_bits_per_pixel(),
+        sub_header.bits_per_pixel(),
+    ) {
+        if abpp > nbpp {
+            report.push(
+                Severity::Error,
+                location,
+                Some("ABPP"),
+                format!("{abpp} actual bits per pixel exceed the {nbpp} stored bits (NBPP)"),
+            );
+        }
+    }
+
+    let display_level = sub_header.display_level();
+    let display_levels = nsif
+        .image_segments
+        .iter()
+        .filter_map(|segment| segment.sub_header.display_level().ok())
+        .collect::<Vec<_>>();
+    if let Ok(level) = display_level {
+        if display_levels
+            .iter()
+            .filter(|&&other| other == level)
+            .count()
+            > 1
+        {
+            report.push(
+                Severity::Error,
+                location,
+                Some("IDLVL"),
+                format!("display level {level} is used by more than one segment"),
+            );
+        }
+    }
+    if let Ok(level) = sub_header.attachment_level() {
+        if level != 0 && !display_levels.contains(&level) {
+            report.push(
+                Severity::Error,
+                location,
+                Some("IALVL"),
+                format!("no segment has the display level {level} to attach to"),
+            );
+        } else if display_level.is_ok_and(|display_level| level >= display_level) {
+            report.push(
+                Severity::Error,
+                location,
+                Some("IALVL"),
+                "must be lower than the display level of the segment",
+            );
+        }
+    }
+
+    // Uncompressed data has a size determined by the sub header, blocks included
+    if matches!(sub_header.compression(), Ok(Compression::NotCompressed)) {
+        let expected = (|| {
+            let blocks = u64::from(sub_header.blocks_per_row()?)
+                * u64::from(sub_header.blocks_per_column()?);
+            let block_width = match sub_header.pixels_per_block_horizontal()? {
+                0 => sub_header.num_cols()?,
+                pixels => pixels,
+            };
+            let block_height = match sub_header.pixels_per_block_vertical()? {
+                0 => sub_header.num_rows()?,
+                pixels => pixels,
+            };
+            let bits = blocks
+                * u64::from(block_width)
+                * u64::from(block_height)
+                * u64::from(sub_header.num_bands()?)
+                * u64::from(sub_header.bits_per_pixel()?);
+            Ok::<_, NsifError>(bits.div_ceil(8))
+        })();
+        if let Ok(expected) = expected {
+            if expected != segment.data.len() as u64 {
+                report.push(
+                    Severity::Error,
+                    location,
+                    None,
+                    format!(
+                        "the uncompressed image requires {expected} bytes but the segment holds {}",
+                        segment.data.len()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+fn check_complexity_level(report: &mut ValidationReport) {
+    let Some(required) = report.required_complexity_level else {
+        return;
+    };
+    match report.declared_complexity_level {
+        Some(declared) if declared < required => report.push(
+            Severity::Error,
+            Location::FileHeader,
+            Some("CLEVEL"),
+            format!("{declared:02} is declared but the file requires {required:02}"),
+        ),
+        Some(declared) if declared > required => report.push(
+            Severity::Warning,
+            Location::FileHeader,
+            Some("CLEVEL"),
+            format!("{declared:02} is declared but the file only requires {required:02}"),
+        ),
+        _ => {}
+    }
+}
@@ -0,0 +1,168 @@
+use image::{DynamicImage, GrayImage};
+use nimage::nsif::builder::NsifBuilder;
+use nimage::nsif::codes::{Compression, ImageRepresentation};
+use nimage::nsif::compression::CompressionOptions;
+use nimage::nsif::error::NsifError;
+use nimage::nsif::fileheader::Profile;
+use nimage::nsif::imagesegment::ImageSegment;
+use nimage::nsif::{HeaderFields, NSIF};
+use std::io::Cursor;
+
+#[test]
+fn build_file_from_pixels() {
+    let rgb = (0..4 * 3 * 3).map(|i| i as u8).collect::<Vec<_>>();
+    let gray = GrayImage::from_fn(2, 2, |x, y| image::Luma([(x * 100 + y * 50) as u8]));
+    let nsif = NsifBuilder::new(Profile::Nitf21)
+        .header_field("FTITLE", "Generated")
+        .unwrap()
+        .pixels(4, 3, &["R", "G", "B"], rgb.clone())
+        .unwrap()
+        .dynamic_image(&DynamicImage::ImageLuma8(gray))
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let file_header = &reparsed.file_header;
+    assert_eq!(file_header.profile().unwrap(), Profile::Nitf21);
+    assert_eq!(file_header.file_length().unwrap(), written.len() as u64);
+    assert_eq!(file_header.ftitle.bytes().len(), 80);
+    assert_eq!(file_header.num_image_segments().unwrap(), 2);
+
+    let first = &reparsed.image_segments[0];
+    assert_eq!(first.sub_header.num_rows().unwrap(), 3);
+    assert_eq!(first.sub_header.num_cols().unwrap(), 4);
+    assert_eq!(
+        first.sub_header.image_representation().unwrap(),
+        ImageRepresentation::Rgb
+    );
+    assert_eq!(
+        first.sub_header.compression().unwrap(),
+        Compression::NotCompressed
+    );
+    assert_eq!(first.as_rgb().unwrap(), rgb);
+
+    let second = &reparsed.image_segments[1];
+    assert_eq!(
+        second.sub_header.image_representation().unwrap(),
+        ImageRepresentation::Monochrome
+    );
+    assert_eq!(second.sub_header.display_level().unwrap(), 2);
+    assert_eq!(second.sub_header.attachment_level().unwrap(), 1);
+    assert_eq!(second.data, vec![0, 100, 50, 150]);
+}
+
+#[test]
+fn compress_image_segments() {
+    let rgb = (0..16 * 16 * 3)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let mut jpeg = ImageSegment::from_pixels(16, 16, &["R", "G", "B"], rgb.clone()).unwrap();
+    jpeg.compress(Compression::Jpeg).unwrap();
+    let mut jpeg2000 = ImageSegment::from_pixels(16, 16, &["R", "G", "B"], rgb.clone()).unwrap();
+    jpeg2000.compress(Compression::Jpeg2000).unwrap();
+    let mut written = Vec::new();
+    NsifBuilder::default()
+        .image(jpeg)
+        .unwrap()
+        .image(jpeg2000)
+        .unwrap()
+        .build()
+        .unwrap()
+        .write(&mut written)
+        .unwrap();
+
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let jpeg = &reparsed.image_segments[0];
+    assert_eq!(jpeg.sub_header.compression().unwrap(), Compression::Jpeg);
+    assert_eq!(jpeg.sub_header.comrat.bytes(), b"00.0");
+    assert_eq!(jpeg.as_rgb().unwrap().len(), rgb.len());
+    let jpeg2000 = &reparsed.image_segments[1];
+    assert_eq!(
+        jpeg2000.sub_header.compression().unwrap(),
+        Compression::Jpeg2000
+    );
+    assert!(jpeg2000.sub_header.comrat.bytes().starts_with(b"N"));
+    assert_eq!(jpeg2000.as_rgb().unwrap(), rgb);
+}
+
+#[test]
+fn compress_in_blocks() {
+    let (width, height) = (40, 24);
+    let rgb = (0..width * height * 3)
+        .map(|i| ((i / 3) % width * 6) as u8)
+        .collect::<Vec<_>>();
+    let options = CompressionOptions {
+        block_size: 16,
+        ..Default::default()
+    };
+    let mut jpeg = ImageSegment::from_pixels(40, 24, &["R", "G", "B"], rgb.clone()).unwrap();
+    jpeg.compress_with(Compression::Jpeg, &options).unwrap();
+    assert_eq!(jpeg.sub_header.blocks_per_row().unwrap(), 3);
+    assert_eq!(jpeg.sub_header.blocks_per_column().unwrap(), 2);
+    assert_eq!(jpeg.sub_header.pixels_per_block_horizontal().unwrap(), 16);
+    let decoded = jpeg.as_rgb().unwrap();
+    assert_eq!(decoded.len(), rgb.len());
+    reject_inconsistent_blocks(&jpeg);
+    let error = decoded
+        .iter()
+        .zip(&rgb)
+        .map(|(&a, &b)| a.abs_diff(b) as u64)
+        .sum::<u64>()
+        / rgb.len() as u64;
+    assert!(error < 4);
+
+    let mut lossless = ImageSegment::from_pixels(40, 24, &["R", "G", "B"], rgb.clone()).unwrap();
+    lossless
+        .compress_with(Compression::Jpeg2000, &options)
+        .unwrap();
+    assert_eq!(lossless.as_rgb().unwrap(), rgb);
+
+    let mut lossy = ImageSegment::from_pixels(40, 24, &["R", "G", "B"], rgb.clone()).unwrap();
+    let options = CompressionOptions {
+        rate: Some(1.5),
+        ..options
+    };
+    lossy
+        .compress_with(Compression::Jpeg2000, &options)
+        .unwrap();
+    assert_eq!(lossy.sub_header.comrat.bytes(), b"01.5");
+    assert_eq!(lossy.as_rgb().unwrap().len(), rgb.len());
+    assert!(lossy.data.len() < lossless.data.len());
+}
+
+/// Decoding fails instead of panicking when the block fields disagree with the JPEG streams
+fn reject_inconsistent_blocks(jpeg: &ImageSegment) {
+    let mut larger_blocks = jpeg.clone();
+    larger_blocks.sub_header.set_field("NPPBH", "0032").unwrap();
+    let error = larger_blocks.as_rgb().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<NsifError>(),
+        Some(NsifError::InvalidDimensions)
+    ));
+
+    // NBPR is only range checked when set, so write 0000 into a file
+    let nsif = NsifBuilder::default()
+        .image(jpeg.clone())
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut bytes = Vec::new();
+    nsif.write(&mut bytes).unwrap();
+    let offset = NSIF::parse(Cursor::new(&bytes)).unwrap().image_segments[0]
+        .sub_header
+        .nbpr
+        .offset() as usize;
+    bytes[offset..offset + 4].copy_from_slice(b"0000");
+    let nsif = NSIF::parse(Cursor::new(&bytes)).unwrap();
+    let error = nsif.image_segments[0].as_rgb().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<NsifError>(),
+        Some(NsifError::InvalidFieldValue {
+            mnemonic: "NBPR",
+            ..
+        })
+    ));
+}
@@ -1,5 +1,12 @@
+use nimage::nsif::codes::{
+    describe, Classification, Compression, ImageMode, ImageRepresentation, PixelValueType,
+};
+use nimage::nsif::datetime::DateTime;
+use nimage::nsif::error::NsifError;
+use nimage::nsif::field::FieldKind;
 use nimage::nsif::{parse_number_from_string, NSIF};
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::Cursor;
 
 #[test]
 fn parse_nsif_file() {
@@ -12,6 +19,114 @@ fn parse_nsif_file() {
     assert_int_eq!(number_of_image_segments.value, 1);
 }
 
+#[test]
+fn report_subheader_length_mismatch() {
+    let original = fs::read("tests/Image.nsif").unwrap();
+    let mut bytes = original.clone();
+    let lish_offset = 363;
+    assert_eq!(&bytes[lish_offset..lish_offset + 6], b"000605");
+    bytes[lish_offset..lish_offset + 6].copy_from_slice(b"000604");
+    // One byte of padding keeps the declared segments within the file
+    bytes.push(0);
+    let (nsif, diagnostics) = NSIF::parse_with_diagnostics(Cursor::new(&bytes)).unwrap();
+    assert!(matches!(
+        diagnostics.as_slice(),
+        [NsifError::LengthMismatch { field, declared: 604, consumed: 605 }] if field == "LISH001"
+    ));
+    // The data is read from the offset declared by LISH
+    let data_offset = nsif.file_header.header_length().unwrap() as usize + 604;
+    let data = &nsif.image_segments[0].data;
+    assert_eq!(
+        data.as_slice(),
+        &bytes[data_offset..data_offset + data.len()]
+    );
+
+    let (_, diagnostics) = NSIF::parse_with_diagnostics(Cursor::new(original)).unwrap();
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn decode_ecs_fields_as_latin1() {
+    let mut bytes = fs::read("tests/Image.nsif").unwrap();
+    let ftitle_offset = 39;
+    let title = b"Pr\xfcfbild f\xfcr \xc9tude";
+    bytes[ftitle_offset..ftitle_offset + title.len()].copy_from_slice(title);
+    let nsif = NSIF::parse(Cursor::new(bytes)).unwrap();
+    let ftitle = nsif
+        .file_header
+        .ftitle
+        .value
+        .as_single_alphanumeric()
+        .unwrap();
+    assert!(ftitle.value.starts_with("Prüfbild für Étude"));
+    assert_eq!(&ftitle.bytes[..title.len()], title);
+}
+
+#[test]
+fn expose_field_definitions() {
+    let file = File::open("tests/Image.nsif").unwrap();
+    let nsif = NSIF::parse(&file).unwrap();
+    let ftitle = &nsif.file_header.ftitle;
+    assert_eq!(ftitle.mnemonic(), "FTITLE");
+    assert_eq!(ftitle.length(), 80);
+    assert_eq!(ftitle.kind(), FieldKind::EcsA);
+    assert_eq!(ftitle.offset(), 39);
+    let lis = &nsif.file_header.lis;
+    assert_eq!(lis.mnemonic(), "LI");
+    assert_eq!(lis.kind(), FieldKind::BcsN);
+    assert_eq!(lis.offset(), 369);
+    let nrows = &nsif.image_segments[0].sub_header.nrows;
+    assert_eq!(nrows.definition().range, Some((1, 99_999_999)));
+}
+
+#[test]
+fn typed_accessors() {
+    let file = File::open("tests/Image.nsif").unwrap();
+    let nsif = NSIF::parse(&file).unwrap();
+    let file_header = &nsif.file_header;
+    assert_eq!(file_header.complexity_level().unwrap(), 3);
+    assert_eq!(
+        file_header.classification().unwrap(),
+        Classification::Unclassified
+    );
+    assert_eq!(
+        file_header.file_datetime().unwrap(),
+        DateTime::new(2025, 1, 1, 12, 0, 0).unwrap()
+    );
+    assert_eq!(file_header.image_segment_lengths().unwrap(), vec![192]);
+    let sub_header = &nsif.image_segments[0].sub_header;
+    assert_eq!(sub_header.num_rows().unwrap(), 8);
+    assert_eq!(sub_header.num_cols().unwrap(), 8);
+    assert_eq!(sub_header.num_bands().unwrap(), 3);
+    assert_eq!(
+        sub_header.compression().unwrap(),
+        Compression::NotCompressed
+    );
+    assert_eq!(
+        sub_header.image_mode().unwrap(),
+        ImageMode::BandInterleavedByPixel
+    );
+    assert_eq!(sub_header.pixel_type().unwrap(), PixelValueType::Integer);
+    assert_eq!(
+        sub_header.image_representation().unwrap(),
+        ImageRepresentation::Rgb
+    );
+}
+
+#[test]
+fn describe_coded_fields() {
+    let file = File::open("tests/Image.nsif").unwrap();
+    let nsif = NSIF::parse(&file).unwrap();
+    let sub_header = &nsif.image_segments[0].sub_header;
+    assert_eq!(
+        sub_header.ic.to_string(),
+        "Image compression: NC (Not compressed)"
+    );
+    assert_eq!(describe("IC", "C8"), Some("JPEG 2000"));
+    assert_eq!(describe("ISCLAS", "S"), Some("Secret"));
+    assert_eq!(describe("IID1", "C8"), None);
+}
+
 #[macro_export]
 macro_rules! assert_string_eq {
     ($left:expr, $right:expr $(,)?) => {
@@ -0,0 +1,216 @@
+use nimage::nsif::builder::NsifBuilder;
+use nimage::nsif::codes::{Classification, DeclassificationType};
+use nimage::nsif::error::NsifError;
+use nimage::nsif::integrity::{check_integrity, validate_with_integrity};
+use nimage::nsif::security::{
+    highest_classification, more_restrictive_segments, Banner, SecurityMetadata,
+};
+use nimage::nsif::validation::{required_complexity_level, validate, Location, Severity};
+use nimage::nsif::{HeaderFields, NSIF};
+use std::fs::{self, File};
+use std::io::Cursor;
+
+#[test]
+fn validate_fixtures() {
+    for path in ["tests/Image.nsif", "tests/MultiSegment.nsif"] {
+        let nsif = NSIF::parse(&File::open(path).unwrap()).unwrap();
+        let report = validate(&nsif);
+        assert!(!report.has_errors(), "{path}: {:?}", report.issues);
+        assert_eq!(report.required_complexity_level, Some(3));
+    }
+}
+
+#[test]
+fn report_invalid_fields_and_complexity_level() {
+    let mut nsif = NsifBuilder::default()
+        .pixels(3000, 1, &["M"], vec![0; 3000])
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(required_complexity_level(&nsif), 5);
+    assert_eq!(nsif.file_header.complexity_level().unwrap(), 5);
+
+    nsif.file_header.set_field("CLEVEL", "03").unwrap();
+    let sub_header = &mut nsif.image_segments[0].sub_header;
+    sub_header.set_field("ISCLAS", "X").unwrap();
+    sub_header.set_field("IALVL", "7").unwrap();
+    let report = validate(&nsif);
+
+    let issues = report
+        .errors()
+        .map(|issue| (issue.location, issue.mnemonic))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        issues,
+        vec![
+            (Location::ImageSegment(1), Some("ISCLAS")),
+            (Location::ImageSegment(1), Some("IALVL")),
+            (Location::FileHeader, Some("CLEVEL")),
+        ]
+    );
+    assert_eq!(report.declared_complexity_level, Some(3));
+    assert!(report
+        .issues
+        .iter()
+        .all(|issue| issue.severity == Severity::Error));
+}
+
+#[test]
+fn report_invalid_graphic_and_text_sub_headers() {
+    let mut nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    nsif.graphic_segments[0].sub_header[0..2].copy_from_slice(b"GR");
+    let text = &mut nsif.text_segments[0].sub_header;
+    text[9..12].copy_from_slice(b"0x1");
+    text[277..282].copy_from_slice(b"00010");
+    let report = validate(&nsif);
+
+    let issues = report
+        .errors()
+        .map(|issue| (issue.location, issue.mnemonic))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        issues,
+        vec![
+            (Location::GraphicSegment(1), Some("SY")),
+            (Location::TextSegment(1), Some("TXTALVL")),
+            (Location::TextSegment(1), Some("TXSHDL")),
+        ]
+    );
+}
+
+#[test]
+fn serialize_report_as_json() {
+    let mut nsif = NSIF::parse(&File::open("tests/Image.nsif").unwrap()).unwrap();
+    nsif.file_header.set_field("FDT", "20261399999999").unwrap();
+    let json = validate(&nsif).to_json();
+    assert!(json.starts_with(r#"{"valid":false,"errors":1,"warnings":0,"#));
+    assert!(
+        json.contains(r#"{"location":"File Header","issues":[{"severity":"error","field":"FDT","#)
+    );
+}
+
+#[test]
+fn check_lengths_against_file() {
+    let original = fs::read("tests/MultiSegment.nsif").unwrap();
+    let report = check_integrity(&mut Cursor::new(&original)).unwrap();
+    assert!(report.is_intact());
+    assert_eq!(report.segments.len(), 4);
+
+    let mut padded = original.clone();
+    padded.extend_from_slice(b"garbage");
+    let report = check_integrity(&mut Cursor::new(&padded)).unwrap();
+    assert_eq!(report.trailing_length(), 7);
+    let mnemonics = report
+        .issues()
+        .iter()
+        .map(|issue| issue.mnemonic)
+        .collect::<Vec<_>>();
+    assert_eq!(mnemonics, vec![Some("FL"), None]);
+
+    let truncated = &original[..original.len() - 20];
+    let report = check_integrity(&mut Cursor::new(truncated)).unwrap();
+    assert_eq!(report.missing_length(), 20);
+    let issues = report.issues();
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[1].location, Location::TextSegment(1));
+    assert_eq!(issues[1].mnemonic, Some("LT"));
+    assert!(matches!(
+        NSIF::parse(Cursor::new(truncated)),
+        Err(e) if matches!(
+            e.downcast_ref::<NsifError>(),
+            Some(NsifError::TruncatedFile { expected: 2271, actual: 2251 })
+        )
+    ));
+}
+
+#[test]
+fn validate_truncated_file() {
+    let original = fs::read("tests/Image.nsif").unwrap();
+    let report = validate_with_integrity(&mut Cursor::new(&original[..1100])).unwrap();
+    assert!(report.has_errors());
+    assert_eq!(report.declared_complexity_level, Some(3));
+    assert_eq!(report.required_complexity_level, None);
+    let issues = report
+        .errors()
+        .map(|issue| (issue.location, issue.mnemonic))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        issues,
+        vec![
+            (Location::FileHeader, None),
+            (Location::FileHeader, Some("FL")),
+            (Location::ImageSegment(1), Some("LI")),
+        ]
+    );
+    assert!(report
+        .to_json()
+        .contains(r#""required_complexity_level":null"#));
+
+    let report = validate_with_integrity(&mut Cursor::new(&original)).unwrap();
+    assert!(!report.has_errors());
+    assert_eq!(report.required_complexity_level, Some(3));
+}
+
+#[test]
+fn compare_security_metadata() {
+    let mut nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    assert_eq!(
+        highest_classification(&nsif).unwrap(),
+        Classification::Restricted
+    );
+    assert!(more_restrictive_segments(&nsif).unwrap().is_empty());
+
+    let sub_header = &mut nsif.image_segments[0].sub_header;
+    sub_header.set_field("ISCLAS", "S").unwrap();
+    sub_header.set_field("ISCODE", "ALPHA").unwrap();
+    sub_header.set_field("ISDCTP", "DD").unwrap();
+    sub_header.set_field("ISDCDT", "20301231").unwrap();
+    let security = sub_header.security().unwrap();
+    assert_eq!(security.codewords, vec!["ALPHA"]);
+    assert_eq!(
+        security.declassification_type,
+        Some(DeclassificationType::Date)
+    );
+    assert_eq!(
+        security.declassification_date.unwrap().to_string(),
+        "2030-12-31"
+    );
+
+    assert_eq!(
+        highest_classification(&nsif).unwrap(),
+        Classification::Secret
+    );
+    assert_eq!(
+        more_restrictive_segments(&nsif).unwrap(),
+        vec![Location::ImageSegment(1)]
+    );
+    let report = validate(&nsif);
+    assert_eq!(report.errors().count(), 2);
+    assert!(report
+        .errors()
+        .all(|issue| issue.location == Location::ImageSegment(1)));
+}
+
+#[test]
+fn combine_banner_markings() {
+    let mut nsif = NSIF::parse(&File::open("tests/Image.nsif").unwrap()).unwrap();
+    nsif.file_header.set_field("FSCLAS", "C").unwrap();
+    nsif.file_header.set_field("FSREL", "DEU FRA").unwrap();
+    let sub_header = &mut nsif.image_segments[0].sub_header;
+    sub_header.set_field("ISCLAS", "S").unwrap();
+    sub_header.set_field("ISCODE", "ALPHA").unwrap();
+    sub_header.set_field("ISREL", "FRA GBR").unwrap();
+
+    let banner = Banner::new(&[
+        &nsif.file_header.security().unwrap(),
+        &nsif.image_segments[0].sub_header.security().unwrap(),
+    ]);
+    assert_eq!(banner.classification, Classification::Secret);
+    assert_eq!(banner.text, "SECRET//ALPHA//REL TO FRA");
+
+    let banner = Banner::new(&[
+        &nsif.file_header.security().unwrap(),
+        &SecurityMetadata::classification_only(Classification::TopSecret),
+    ]);
+    assert_eq!(banner.text, "TOP SECRET//REL TO DEU, FRA");
+}
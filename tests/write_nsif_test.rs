@@ -0,0 +1,660 @@
+use nimage::nsif::builder::NsifBuilder;
+use nimage::nsif::chip::{chip, Window};
+use nimage::nsif::codes::Compression;
+use nimage::nsif::compression::CompressionOptions;
+use nimage::nsif::conversion::convert;
+use nimage::nsif::error::NsifError;
+use nimage::nsif::fileheader::Profile;
+use nimage::nsif::imagesegment::ImageSegment;
+use nimage::nsif::integrity::repair;
+use nimage::nsif::rawsegment::RawSegment;
+use nimage::nsif::redaction::{redact, Region};
+use nimage::nsif::sanitize::{sanitize, Action, SanitizePolicy};
+use nimage::nsif::segments::{append_image, extract, move_segment, remove_segment};
+use nimage::nsif::tre::{set_tres, Tre};
+use nimage::nsif::validation::{validate, Location};
+use nimage::nsif::{HeaderFields, NSIF};
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::str::FromStr;
+
+fn round_trip(path: &str) -> (Vec<u8>, Vec<u8>) {
+    let original = fs::read(path).unwrap();
+    let nsif = NSIF::parse(&File::open(path).unwrap()).unwrap();
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    (original, written)
+}
+
+#[test]
+fn write_single_image_file_unchanged() {
+    let (original, written) = round_trip("tests/Image.nsif");
+    assert_eq!(original, written);
+}
+
+#[test]
+fn write_multi_segment_file_unchanged() {
+    let (original, written) = round_trip("tests/MultiSegment.nsif");
+    assert_eq!(original, written);
+}
+
+#[test]
+fn recompute_lengths_on_write() {
+    let mut nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    nsif.text_segments[0]
+        .data
+        .extend_from_slice(b"More notes.\r\n");
+    nsif.image_segments.remove(1);
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let file_header = &reparsed.file_header;
+    assert_eq!(file_header.file_length().unwrap(), written.len() as u64);
+    assert_eq!(file_header.num_image_segments().unwrap(), 1);
+    assert_eq!(file_header.text_segment_lengths().unwrap(), vec![(282, 46)]);
+    assert_eq!(reparsed.text_segments[0].data, nsif.text_segments[0].data);
+}
+
+#[test]
+fn set_fields_with_padding() {
+    let mut nsif = NSIF::parse(&File::open("tests/Image.nsif").unwrap()).unwrap();
+    nsif.file_header.set_field("FTITLE", "Übersicht").unwrap();
+    nsif.file_header.set_field("CLEVEL", "5").unwrap();
+    let sub_header = &mut nsif.image_segments[0].sub_header;
+    sub_header.set_field("IID2", "Corrected").unwrap();
+    sub_header.set_field("ICORDS", " ").unwrap();
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let ftitle = reparsed.file_header.find_field("FTITLE").unwrap();
+    assert_eq!(ftitle.bytes().len(), 80);
+    assert!(ftitle.bytes().starts_with(b"\xdcbersicht  "));
+    assert_eq!(reparsed.file_header.clevel.bytes(), b"05");
+    let sub_header = &reparsed.image_segments[0].sub_header;
+    assert!(sub_header.igeolo.bytes().is_empty());
+    assert_eq!(
+        reparsed.file_header.image_subheader_lengths().unwrap(),
+        vec![605 - 60]
+    );
+}
+
+#[test]
+fn set_fields_in_selected_header() {
+    // ENCRYP is a field of the file header and of every image sub header
+    let mut bytes = fs::read("tests/MultiSegment.nsif").unwrap();
+    let original = NSIF::parse(Cursor::new(&bytes)).unwrap();
+    let offset = original.image_segments[1].sub_header.encryp.offset() as usize;
+    bytes[offset] = b'1';
+    let original = NSIF::parse(Cursor::new(&bytes)).unwrap();
+
+    let mut nsif = original.clone();
+    nsif.set_header_field(Some(2), "ENCRYP", "0").unwrap();
+    nsif.set_header_field(Some(2), "FTITLE", "Edited").unwrap();
+    nsif.set_header_field(None, "IID2", "Edited").unwrap();
+    assert!(matches!(
+        nsif.set_header_field(Some(3), "IID2", "Edited"),
+        Err(NsifError::MissingSegment(Location::ImageSegment(3)))
+    ));
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    let edited = NSIF::parse(Cursor::new(&written)).unwrap();
+
+    // The diff shown by a dry run
+    let changes = original
+        .changed_fields(&edited)
+        .into_iter()
+        .map(|(header, before, _)| format!("{header}: {}", before.mnemonic()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            "File Header: FTITLE",
+            "Image Segment 1: IID2",
+            "Image Segment 2: ENCRYP",
+        ]
+    );
+}
+
+#[test]
+fn reject_invalid_field_values() {
+    let mut nsif = NSIF::parse(&File::open("tests/Image.nsif").unwrap()).unwrap();
+    let file_header = &mut nsif.file_header;
+    assert!(matches!(
+        file_header.set_field("OSTAID", "A station name that is too long"),
+        Err(NsifError::InvalidFieldLength {
+            mnemonic: "OSTAID",
+            length: 10,
+            actual: 31
+        })
+    ));
+    assert!(matches!(
+        file_header.set_field("OSTAID", "Straße"),
+        Err(NsifError::InvalidCharacterSet {
+            mnemonic: "OSTAID",
+            ..
+        })
+    ));
+    assert!(matches!(
+        file_header.set_field("CLEVEL", "00"),
+        Err(NsifError::ValueOutOfRange {
+            mnemonic: "CLEVEL",
+            value: 0,
+            ..
+        })
+    ));
+    assert!(matches!(
+        file_header.set_field("HL", "000404"),
+        Err(NsifError::ComputedField("HL"))
+    ));
+    assert!(matches!(
+        file_header.set_field("NOPE", ""),
+        Err(NsifError::UnknownField(_))
+    ));
+}
+
+#[test]
+fn repair_mis_lengthed_file() {
+    let original = fs::read("tests/MultiSegment.nsif").unwrap();
+    let mut broken = original.clone();
+    broken[342..354].copy_from_slice(b"000000002275");
+    broken[363..369].copy_from_slice(b"000520");
+    broken.extend_from_slice(b"12345");
+
+    let repaired = repair(&mut Cursor::new(&broken), false).unwrap();
+    let changes = repaired
+        .changes
+        .iter()
+        .map(|change| {
+            (
+                change.location,
+                change.mnemonic,
+                change.declared,
+                change.repaired,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            (Location::FileHeader, "FL", Some(2275), 2271),
+            (Location::ImageSegment(1), "LISH", Some(520), 525),
+        ]
+    );
+    assert_eq!(repaired.removed_trailing_length, 5);
+    let mut written = Vec::new();
+    repaired.nsif.write(&mut written).unwrap();
+    assert_eq!(written, original);
+
+    let truncated = &original[..2260];
+    let repaired = repair(&mut Cursor::new(truncated), false).unwrap();
+    assert_eq!(repaired.nsif.text_segments[0].data.len(), 22);
+    assert_eq!(repaired.nsif.file_header.file_length().unwrap(), 2260);
+
+    // Without segments trailing bytes cannot be kept
+    let mut empty = Vec::new();
+    NsifBuilder::new(Profile::Nsif10)
+        .build()
+        .unwrap()
+        .write(&mut empty)
+        .unwrap();
+    let header_length = empty.len();
+    empty.extend_from_slice(b"12345");
+    let repaired = repair(&mut Cursor::new(&empty), true).unwrap();
+    assert_eq!(repaired.removed_trailing_length, 5);
+    let mut written = Vec::new();
+    repaired.nsif.write(&mut written).unwrap();
+    assert_eq!(written.len(), header_length);
+}
+
+#[test]
+fn sanitize_with_policy() {
+    let mut nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    assert_eq!(
+        nsif.file_header.tres().unwrap(),
+        vec![Tre::new("TESTAA", b"hello".to_vec())]
+    );
+    let policy = SanitizePolicy::from_str(
+        "# release policy\nONAME=blank\nUDHD=keep\ntre:TESTAA=blank\nIID2=strip\n",
+    )
+    .unwrap();
+    assert_eq!(policy.tres.get("TESTAA"), Some(&Action::Blank));
+
+    let removals = sanitize(&mut nsif, &policy).unwrap();
+    let items = removals
+        .iter()
+        .map(|removal| (removal.item.as_str(), removal.action))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        items,
+        vec![
+            ("ONAME", Action::Blank),
+            ("TRE:TESTAA", Action::Blank),
+            ("IID2", Action::Blank),
+            ("IID2", Action::Blank),
+        ]
+    );
+
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    assert_eq!(reparsed.file_header.oname.bytes(), vec![b' '; 24]);
+    assert_eq!(
+        reparsed.file_header.tres().unwrap(),
+        vec![Tre::new("TESTAA", b"     ".to_vec())]
+    );
+
+    let removals = sanitize(&mut nsif, &SanitizePolicy::default()).unwrap();
+    assert_eq!(removals.len(), 2);
+    assert!(nsif.file_header.udhd.bytes().is_empty());
+    assert_eq!(nsif.file_header.udhdl.bytes(), b"00000");
+}
+
+#[test]
+fn sanitize_overflow_and_text_tres() {
+    let mut nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    let tres = [
+        Tre::new("PIAIMC", b"secret".to_vec()),
+        Tre::new("TESTAA", b"hello".to_vec()),
+    ];
+    let bytes = tres.iter().flat_map(Tre::to_bytes).collect::<Vec<_>>();
+    // The TREs of the first image overflow into a TRE_OVERFLOW segment
+    let overflow = nsif.data_extension_segments.len() + 1;
+    nsif.image_segments[0]
+        .sub_header
+        .ixsofl
+        .set(&overflow.to_string())
+        .unwrap();
+    let sub_header = format!(
+        "DE{:<25}01U{}IXSHD 0010000",
+        "TRE_OVERFLOW",
+        " ".repeat(166)
+    );
+    nsif.data_extension_segments.push(RawSegment {
+        sub_header: sub_header.into_bytes(),
+        data: bytes.clone(),
+    });
+    let text = &mut nsif.text_segments[0].sub_header;
+    assert_eq!(&text[277..], b"00000");
+    text.truncate(277);
+    text.extend_from_slice(format!("{:05}000", bytes.len() + 3).as_bytes());
+    text.extend_from_slice(&bytes);
+
+    let policy = SanitizePolicy::from_str("TRE:PIAIMC=strip").unwrap();
+    let removals = sanitize(&mut nsif, &policy).unwrap();
+    let locations = removals
+        .iter()
+        .map(|removal| (removal.location, removal.item.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        locations,
+        vec![
+            (Location::TextSegment(1), "TRE:PIAIMC"),
+            (Location::DataExtensionSegment(overflow), "TRE:PIAIMC"),
+        ]
+    );
+
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let kept = tres[1].to_bytes();
+    assert_eq!(reparsed.data_extension_segments[overflow - 1].data, kept);
+    assert_eq!(
+        reparsed.text_segments[0].sub_header[277..],
+        [format!("{:05}000", kept.len() + 3).into_bytes(), kept].concat()
+    );
+    assert!(!validate(&reparsed).has_errors());
+}
+
+#[test]
+fn redact_regions_and_reencode() {
+    for compression in [Compression::NotCompressed, Compression::Jpeg2000] {
+        let pixels = vec![200; 8 * 6 * 3];
+        let mut image_segment = ImageSegment::from_pixels(8, 6, &["R", "G", "B"], pixels).unwrap();
+        // One hundredth of a degree per pixel, north up
+        image_segment
+            .sub_header
+            .set_corners([(50.0, 10.0), (50.0, 10.07), (49.95, 10.07), (49.95, 10.0)])
+            .unwrap();
+        image_segment.compress(compression).unwrap();
+        let mut nsif = NsifBuilder::new(Profile::Nsif10)
+            .image(image_segment)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let regions = [
+            Region::Rectangle {
+                row: 0,
+                column: 0,
+                height: 2,
+                width: 3,
+            },
+            Region::Polygon(vec![(4.0, 6.0), (4.0, 8.0), (6.0, 8.0)]),
+            Region::geo_rectangle((49.965, 10.0), (49.955, 10.02)),
+        ];
+        assert_eq!(redact(&mut nsif, 1, &regions).unwrap(), 6 + 3 + 2);
+        let mut written = Vec::new();
+        nsif.write(&mut written).unwrap();
+
+        let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+        let image_segment = &reparsed.image_segments[0];
+        assert_eq!(image_segment.sub_header.compression().unwrap(), compression);
+        let redacted = image_segment
+            .pixels()
+            .unwrap()
+            .chunks(3)
+            .enumerate()
+            .filter(|(_, pixel)| pixel == &[0, 0, 0])
+            .map(|(i, _)| (i / 8, i % 8))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            redacted,
+            vec![
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (4, 0),
+                (4, 1),
+                (4, 6),
+                (4, 7),
+                (5, 7),
+            ]
+        );
+    }
+
+    // Geographic regions need corners in decimal degrees or degrees, minutes and seconds
+    let mut nsif = NsifBuilder::new(Profile::Nsif10)
+        .pixels(8, 6, &["M"], vec![200; 8 * 6])
+        .unwrap()
+        .build()
+        .unwrap();
+    let sub_header = &mut nsif.image_segments[0].sub_header;
+    sub_header
+        .set_corners([(50.0, 10.0), (50.0, 10.07), (49.95, 10.07), (49.95, 10.0)])
+        .unwrap();
+    let geo_region = [Region::geo_rectangle((49.965, 10.0), (49.955, 10.02))];
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    let igeolo = NSIF::parse(Cursor::new(&written)).unwrap().image_segments[0]
+        .sub_header
+        .igeolo
+        .offset() as usize;
+    written[igeolo + 3] = 0xe9;
+    let mut non_ascii = NSIF::parse(Cursor::new(&written)).unwrap();
+    let error = redact(&mut non_ascii, 1, &geo_region).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<NsifError>(),
+        Some(NsifError::InvalidFieldValue {
+            mnemonic: "IGEOLO",
+            ..
+        })
+    ));
+    nsif.image_segments[0]
+        .sub_header
+        .set_field("ICORDS", "U")
+        .unwrap();
+    let error = redact(&mut nsif, 1, &geo_region).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<NsifError>(),
+        Some(NsifError::InvalidFieldValue {
+            mnemonic: "ICORDS",
+            ..
+        })
+    ));
+    let error = redact(&mut nsif, 2, &geo_region).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<NsifError>(),
+        Some(NsifError::MissingSegment(Location::ImageSegment(2)))
+    ));
+}
+
+/// An RPC00B TRE with LINE_OFF 3, SAMP_OFF 2 and SAMP_SCALE 4
+fn rpc00b() -> Tre {
+    // SUCCESS to HEIGHT_SCALE
+    let mut rpc = [
+        "1",
+        "0000.00",
+        "0000.00",
+        "000003",
+        "00002",
+        "+50.0000",
+        "+010.0000",
+        "+0000",
+        "000003",
+        "00004",
+        "+00.0500",
+        "+000.0700",
+        "+0000",
+    ]
+    .concat();
+    rpc.push_str(&format!("+1.000000E+0{}", "+0.000000E+0".repeat(19)).repeat(4));
+    Tre::new("RPC00B", rpc.into_bytes())
+}
+
+#[test]
+fn chip_window_with_ichipb() {
+    let pixels = (0..8 * 6).map(|i| i as u8).collect::<Vec<_>>();
+    let mut image_segment = ImageSegment::from_pixels(8, 6, &["M"], pixels).unwrap();
+    let sub_header = &mut image_segment.sub_header;
+    sub_header
+        .set_corners([(50.0, 10.0), (50.0, 10.07), (49.95, 10.07), (49.95, 10.0)])
+        .unwrap();
+    set_tres(&mut sub_header.ixshd, &[rpc00b()]).unwrap();
+    let nsif = NsifBuilder::new(Profile::Nsif10)
+        .image(image_segment)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let window = Window::from_str("2,3,3,4").unwrap();
+    let chipped = chip(&nsif, 1, &window).unwrap();
+    let mut written = Vec::new();
+    chipped.write(&mut written).unwrap();
+
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let image_segment = &reparsed.image_segments[0];
+    assert_eq!(
+        image_segment.pixels().unwrap(),
+        vec![19, 20, 21, 22, 27, 28, 29, 30, 35, 36, 37, 38]
+    );
+    assert_eq!(
+        image_segment.sub_header.igeolo.bytes(),
+        b"+49.980+010.030+49.980+010.060+49.960+010.060+49.960+010.030"
+    );
+    let tres = image_segment.sub_header.tres().unwrap();
+    // The sample offset of 2 is moved into the numerator, 0.25 sample scales to the left
+    assert_eq!(&tres[0].data[15..26], b"00000100000");
+    assert_eq!(&tres[0].data[561..573], b"+7.500000E-1");
+    assert_eq!(
+        tres[1],
+        Tre::new(
+            "ICHIPB",
+            [
+                "000001.000000000",
+                "00000000.50000000000.50000000000.50000000003.500",
+                "00000002.50000000000.50000000002.50000000003.500",
+                "00000002.50000000003.50000000002.50000000006.500",
+                "00000004.50000000003.50000000004.50000000006.500",
+                "0000000600000008",
+            ]
+            .concat()
+            .into_bytes()
+        )
+    );
+
+    // A chip of the chip maps to the original full image
+    let window = Window::from_str("1,1,2,2").unwrap();
+    let chipped = chip(&reparsed, 1, &window).unwrap();
+    let image_segment = &chipped.image_segments[0];
+    assert_eq!(image_segment.pixels().unwrap(), vec![28, 29, 36, 37]);
+    let ichipb = image_segment.sub_header.ichipb().unwrap().unwrap();
+    assert_eq!((ichipb.full_image_rows, ichipb.full_image_columns), (6, 8));
+    let (row, column) = image_segment.chip_to_full_image((1.5, 0.5)).unwrap();
+    assert!((row - 4.5).abs() < 1e-9 && (column - 4.5).abs() < 1e-9);
+}
+
+#[test]
+fn chip_with_tre_overflow() {
+    let mut nsif = NsifBuilder::new(Profile::Nsif10)
+        .pixels(8, 6, &["M"], vec![0; 8 * 6])
+        .unwrap()
+        .build()
+        .unwrap();
+    // The RPC00B TRE overflows the IXSHD field of the image into a TRE_OVERFLOW segment
+    nsif.image_segments[0].sub_header.ixsofl.set("001").unwrap();
+    let sub_header = format!(
+        "DE{:<25}01U{}IXSHD 0010000",
+        "TRE_OVERFLOW",
+        " ".repeat(166)
+    );
+    nsif.data_extension_segments.push(RawSegment {
+        sub_header: sub_header.into_bytes(),
+        data: rpc00b().to_bytes(),
+    });
+
+    let chipped = chip(&nsif, 1, &Window::from_str("2,3,3,4").unwrap()).unwrap();
+    let mut written = Vec::new();
+    chipped.write(&mut written).unwrap();
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    assert_eq!(reparsed.image_segments[0].sub_header.ixsofl.bytes(), b"001");
+    assert_eq!(reparsed.data_extension_segments.len(), 1);
+    let tres = Tre::parse_all(&reparsed.data_extension_segments[0].data).unwrap();
+    assert_eq!(&tres[0].data[15..26], b"00000100000");
+    assert!(!validate(&reparsed).has_errors());
+}
+
+#[test]
+fn extract_segment_with_attachments() {
+    let nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    let extracted = extract(&nsif, Location::ImageSegment(2), true).unwrap();
+    let mut written = Vec::new();
+    extracted.write(&mut written).unwrap();
+
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    assert_eq!(reparsed.image_segments.len(), 1);
+    assert!(reparsed.graphic_segments.is_empty());
+    assert_eq!(reparsed.text_segments.len(), 1);
+    let sub_header = &reparsed.image_segments[0].sub_header;
+    assert_eq!(sub_header.attachment_level().unwrap(), 0);
+    assert_eq!(reparsed.image_segments[0].data, nsif.image_segments[1].data);
+    assert!(!validate(&reparsed).has_errors());
+
+    let extracted = extract(&nsif, Location::ImageSegment(1), false).unwrap();
+    assert_eq!(extracted.image_segments.len(), 1);
+    assert!(extracted.graphic_segments.is_empty() && extracted.text_segments.is_empty());
+    assert!(matches!(
+        extract(&nsif, Location::ImageSegment(3), false),
+        Err(NsifError::MissingSegment(Location::ImageSegment(3)))
+    ));
+}
+
+#[test]
+fn compose_segments_from_several_files() {
+    let mut nsif = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    let other = NSIF::parse(&File::open("tests/Image.nsif").unwrap()).unwrap();
+    // The second image and the graphic are attached to the first image, the text to the second
+    remove_segment(&mut nsif, Location::ImageSegment(1)).unwrap();
+    append_image(&mut nsif, &other, 1).unwrap();
+    move_segment(&mut nsif, Location::ImageSegment(2), 1).unwrap();
+    assert!(matches!(
+        move_segment(&mut nsif, Location::TextSegment(1), 2),
+        Err(NsifError::MissingSegment(Location::TextSegment(2)))
+    ));
+
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    let levels = reparsed
+        .image_segments
+        .iter()
+        .map(|segment| {
+            let sub_header = &segment.sub_header;
+            (
+                sub_header.display_level().unwrap(),
+                sub_header.attachment_level().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(levels, vec![(3, 0), (1, 0)]);
+    assert_eq!(
+        reparsed.image_segments[0].data,
+        other.image_segments[0].data
+    );
+    assert_eq!(
+        &reparsed.graphic_segments[0].sub_header[214..220],
+        b"002000"
+    );
+    assert_eq!(&reparsed.text_segments[0].sub_header[9..12], b"001");
+    assert!(!validate(&reparsed).has_errors());
+}
+
+#[test]
+fn recompress_between_compressions() {
+    let nsif = NSIF::parse(&File::open("tests/Image.nsif").unwrap()).unwrap();
+    let original = &nsif.image_segments[0];
+    let pixels = original.pixels().unwrap();
+
+    // Numerically lossless C8 decodes to the same pixels, also after going back to NC
+    let mut segment = original.clone();
+    segment
+        .recompress(Compression::Jpeg2000, &CompressionOptions::default())
+        .unwrap();
+    assert_eq!(
+        segment.sub_header.compression().unwrap(),
+        Compression::Jpeg2000
+    );
+    assert_eq!(segment.pixels().unwrap(), pixels);
+    segment
+        .recompress(Compression::NotCompressed, &CompressionOptions::default())
+        .unwrap();
+    assert_eq!(segment.data, pixels);
+    assert_eq!(segment.sub_header.blocks_per_row().unwrap(), 1);
+
+    let mut segment = original.clone();
+    let options = CompressionOptions {
+        rate: Some(1.5),
+        block_size: 64,
+        ..CompressionOptions::default()
+    };
+    segment.recompress(Compression::Jpeg2000, &options).unwrap();
+    assert_eq!(segment.sub_header.comrat.bytes(), b"01.5");
+    assert_eq!(segment.sub_header.imode.bytes(), b"B");
+    assert_eq!(segment.pixels().unwrap().len(), pixels.len());
+
+    let mut recompressed = nsif.clone();
+    recompressed.image_segments[0] = segment;
+    recompressed.update_lengths_and_complexity_level().unwrap();
+    let mut written = Vec::new();
+    recompressed.write(&mut written).unwrap();
+    let reparsed = NSIF::parse(Cursor::new(&written)).unwrap();
+    assert!(!validate(&reparsed).has_errors());
+}
+
+#[test]
+fn convert_between_nsif_and_nitf() {
+    let original = NSIF::parse(&File::open("tests/MultiSegment.nsif").unwrap()).unwrap();
+    let mut nsif = original.clone();
+    convert(&mut nsif, Profile::Nitf21, Some("US")).unwrap();
+    let mut written = Vec::new();
+    nsif.write(&mut written).unwrap();
+    let converted = NSIF::parse(Cursor::new(&written)).unwrap();
+    assert_eq!(converted.file_header.profile().unwrap(), Profile::Nitf21);
+    // The file header is restricted, the first image unclassified without a system
+    assert_eq!(converted.file_header.fsclsy.bytes(), b"US");
+    assert_eq!(converted.image_segments[0].sub_header.isclsy.bytes(), b"  ");
+    assert!(!validate(&converted).has_errors());
+
+    let mut nsif = converted;
+    convert(&mut nsif, Profile::Nsif10, None).unwrap();
+    assert_eq!(nsif.file_header.profile().unwrap(), Profile::Nsif10);
+    assert_eq!(nsif.file_header.fsclsy.bytes(), b"US");
+    assert!(matches!(
+        convert(&mut nsif, Profile::Nitf21, Some("USA")),
+        Err(NsifError::InvalidFieldValue { .. })
+    ));
+}